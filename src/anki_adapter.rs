@@ -1,7 +1,17 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use reqwest::Client;
-use serde::{ Deserialize, Serialize };
-use serde_json::{Value, json};
-use std::env;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::anki_connect::*;
+use crate::config::Config;
+use crate::error::WordcraftError;
+use crate::generation::Flashcard;
+use crate::tts::{synthesize_audio, MediaFile, TtsEngine};
+use crate::note_type::{
+    model_version, NoteKind, WORDCRAFT_FIELDS, WORDCRAFT_MODEL_NAME, WORDCRAFT_MODEL_VERSION, WORDCRAFT_TAGS,
+};
 
 pub struct AnkiAdapter {
     pub(crate) url: String,
@@ -9,45 +19,57 @@ pub struct AnkiAdapter {
 }
 
 impl AnkiAdapter {
-    pub fn new() -> Result<AnkiAdapter, &'static str> {
-        let mut adapter = AnkiAdapter {
-            url: "http://localhost:8765".to_string(),
+    pub fn new(url: impl Into<String>) -> AnkiAdapter {
+        AnkiAdapter {
+            url: url.into(),
             client: Client::new(),
-        };
-
-        if let Ok(url) = env::var("ANKI_CONNECT_URL") {
-            adapter.url = url;
-        } else {
-            println!("ANKI_CONNECT_URL not set. Using default url: {}", adapter.url);
         }
+    }
 
-        Ok(adapter)
+    pub fn from_config(config: &Config) -> AnkiAdapter {
+        AnkiAdapter::new(config.anki_connect_url.clone())
     }
 
-    pub async fn create_deck(&self, deck_name: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let request =
-            json!({
-        "action": "createDeck",
-        "version": 6,
-        "params": {
-            "deck": deck_name
+    // Send a typed action to AnkiConnect and unwrap the `{result, error}` envelope
+    pub async fn invoke<A: AnkiAction>(&self, action: A) -> Result<A::Output, WordcraftError> {
+        let mut request = json!({
+            "action": A::NAME,
+            "version": ANKI_CONNECT_VERSION
+        });
+
+        let params = serde_json::to_value(&action)?;
+        if !params.is_null() {
+            request["params"] = params;
         }
-    });
 
-        let response = &self.client
+        let response = self.client
             .post(&self.url)
             .json(&request)
-            .send().await?
-            .json::<serde_json::Value>().await?;
-
-        if let Some(error) = response.get("error") {
-            if !error.is_null() {
-                println!("Error creating deck: {:?}", error.to_string());
-            } else {
-                println!("Deck '{}' created successfully.", deck_name);
+            .send().await
+            .map_err(|err| WordcraftError::AnkiUnavailable { url: self.url.clone(), message: err.to_string() })?
+            .json::<AnkiResponse>().await
+            .map_err(|err| WordcraftError::anki_action(A::NAME, format!("unreadable response: {}", err)))?;
+
+        if let Some(error) = response.error.filter(|error| !error.is_null()) {
+            let message = match error {
+                Value::String(message) => message,
+                other => other.to_string(),
+            };
+            return Err(WordcraftError::anki_action(A::NAME, message));
+        }
+
+        serde_json::from_value(response.result)
+            .map_err(|err| WordcraftError::anki_action(A::NAME, format!("unexpected result: {}", err)))
+    }
+
+    // A deck that already exists is not an error
+    pub async fn create_deck(&self, deck_name: &str) -> Result<(), WordcraftError> {
+        match self.invoke(CreateDeck { deck: deck_name.to_string() }).await {
+            Ok(_) => println!("Deck '{}' created successfully.", deck_name),
+            Err(WordcraftError::AnkiActionFailed { message, .. }) if message.contains("already exists") => {
+                println!("Deck '{}' already exists.", deck_name);
             }
-        } else {
-            println!("Deck '{}' ccreated successfully.", deck_name);
+            Err(err) => return Err(err.context("Error creating deck")),
         }
 
         Ok(())
@@ -60,131 +82,533 @@ impl AnkiAdapter {
         back: &str,
         example: &str,
         example_translate: &str
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let request = json!({
-            "action": "addNote",
-            "version": 6,
-            "params": {
-                "note": {
-                    "deckName": deck_name,
-                    "modelName": "Wordcraft",
-                    "fields": {
-                        "Front": front,
-                        "Back": back,
-                        "Example": example,
-                        "ExampleTranslation": example_translate
-                    },
-                    "options": {
-                        "allowDuplicate": false
-                    },
-                    "tags": ["wordcraft", "language_learning"]
-                }
-            }
-        });
-
-        let response = self.client
-            .post(&self.url)
-            .json(&request)
-            .send().await?
-            .json::<serde_json::Value>().await?;
+    ) -> Result<(), WordcraftError> {
+        let note = wordcraft_note(deck_name, front, back, example, example_translate);
 
-        if let Some(error) = response.get("error") {
-            if !error.is_null() {
-                return Err(format!("Error adding card: {}", error).into());
+        self.add_note(note).await.map_err(|err| match err {
+            WordcraftError::AnkiActionFailed { message, .. } if message.contains("duplicate") => {
+                WordcraftError::DuplicateNote { front: front.to_string() }
             }
-        }
+            err => err.context("Error adding card"),
+        })?;
 
         println!("Card added successfully.");
         Ok(())
     }
 
-    pub async fn check_connection(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let request = json!({
-            "action": "version",
-            "version": 6
-        });
-
-        let response: Value = self.client.post(&self.url)
-            .json(&request)
-            .send()
-            .await?
-            .json()
-            .await?;
+    // Insert many cards with one `canAddNotesWithErrorDetail` pre-check and one
+    // `addNotes` call. A duplicate or rejected card is recorded in the report
+    // instead of aborting the batch.
+    pub async fn add_cards(&self, deck_name: &str, cards: &[Flashcard]) -> Result<BatchReport, WordcraftError> {
+        self.add_cards_as(deck_name, cards, NoteKind::Basic).await
+    }
 
-        if response["error"].is_null() {
-            Ok(())
-        } else {
-            Err(format!("AnkiConnect error: {}", response["error"]).into())
-        }
+    // `add_cards` with the notes built for the given Wordcraft note type
+    pub async fn add_cards_as(&self, deck_name: &str, cards: &[Flashcard], kind: NoteKind) -> Result<BatchReport, WordcraftError> {
+        self.add_cards_tagged(deck_name, cards, kind, &[]).await
     }
 
-    pub async fn ensure_wordcraft_model_exists(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let model_name = "Wordcraft";
-        
-        // Check if the model exists
-        let request = json!({
-            "action": "modelNames",
-            "version": 6
-        });
+    // `add_cards_as` with extra tags on every note next to the Wordcraft ones
+    pub async fn add_cards_tagged(
+        &self,
+        deck_name: &str,
+        cards: &[Flashcard],
+        kind: NoteKind,
+        tags: &[String],
+    ) -> Result<BatchReport, WordcraftError> {
+        let note_type = kind.note_type();
+        let notes: Vec<Note> = cards.iter()
+            .map(|card| {
+                let mut note = note_type.note(deck_name, card);
+                note.tags.extend(tags.iter().filter(|tag| !note.tags.contains(tag)).cloned().collect::<Vec<_>>());
+                note
+            })
+            .collect();
 
-        let response = self.client
-            .post(&self.url)
-            .json(&request)
-            .send().await?
-            .json::<serde_json::Value>().await?;
+        let mut outcomes: Vec<Option<CardOutcome>> = vec![None; cards.len()];
+        let mut seen_fronts = HashSet::new();
+        for (index, card) in cards.iter().enumerate() {
+            let front = card.front.trim();
+            if front.is_empty() {
+                outcomes[index] = Some(CardOutcome::Failed("Front field is empty".to_string()));
+            } else if !seen_fronts.insert(front.to_string()) {
+                outcomes[index] = Some(CardOutcome::Duplicate);
+            }
+        }
 
-        let model_names = response["result"].as_array()
-            .ok_or("Failed to get model names")?;
+        let candidates: Vec<usize> = (0..cards.len()).filter(|&index| outcomes[index].is_none()).collect();
+        if !candidates.is_empty() {
+            let can_add = self.can_add_notes_with_error_detail(candidates.iter().map(|&index| notes[index].clone()).collect()).await?;
+            if can_add.len() != candidates.len() {
+                return Err(WordcraftError::anki_action(
+                    CanAddNotesWithErrorDetail::NAME,
+                    format!("{} results for {} notes", can_add.len(), candidates.len()),
+                ));
+            }
+            for (&index, result) in candidates.iter().zip(can_add) {
+                if !result.can_add {
+                    let reason = result.error.unwrap_or_else(|| "Anki cannot add the note".to_string());
+                    outcomes[index] = Some(rejected(reason));
+                }
+            }
+        }
 
-        if !model_names.contains(&json!(model_name)) {
-            // Create the model if it doesn't exist
-            let request = json!({
-                "action": "createModel",
-                "version": 6,
-                "params": {
-                    "modelName": model_name,
-                    "inOrderFields": ["Front", "Back", "Example", "ExampleTranslation"],
-                    "css": ".card {
-                        font-family: arial;
-                        font-size: 20px;
-                        text-align: center;
-                        color: black;
-                        background-color: white;
+        let to_add: Vec<usize> = (0..cards.len()).filter(|&index| outcomes[index].is_none()).collect();
+        if !to_add.is_empty() {
+            match self.add_notes(to_add.iter().map(|&index| notes[index].clone()).collect()).await {
+                Ok(note_ids) => {
+                    for (position, &index) in to_add.iter().enumerate() {
+                        outcomes[index] = Some(match note_ids.get(position).copied().flatten() {
+                            Some(note_id) => CardOutcome::Added(note_id),
+                            None => CardOutcome::Failed("Anki did not create the note".to_string()),
+                        });
                     }
-                    .front {
-                        font-weight: bold;
+                }
+                Err(WordcraftError::AnkiActionFailed { .. }) => {
+                    // Newer AnkiConnect versions fail the whole call when any note
+                    // is rejected, so add the notes one by one to find which
+                    for &index in &to_add {
+                        outcomes[index] = Some(match self.add_note(notes[index].clone()).await {
+                            Ok(note_id) => CardOutcome::Added(note_id),
+                            Err(err) => rejected(err.to_string()),
+                        });
                     }
-                    .example {
-                        font-style: italic;
-                        color: #AAA;
-                    }",
-                    "cardTemplates": [
-                        {
-                            "Name": "Card 1",
-                            "Front": "<div class='front'>{{Front}}</div><br><div class='example'>{{Example}}</div>",
-                            "Back": "<div class='front'>{{Front}}</div><hr id=answer><div>{{Back}}</div><br><div class='example'>{{Example}}</div><br><div>{{ExampleTranslation}}</div>"
-                        }
-                    ]
                 }
-            });
-
-            let response = self.client
-                .post(&self.url)
-                .json(&request)
-                .send().await?
-                .json::<serde_json::Value>().await?;
-
-            if let Some(error) = response.get("error") {
-                if !error.is_null() {
-                    return Err(format!("Error creating Wordcraft model: {}", error).into());
+                Err(err) => {
+                    for &index in &to_add {
+                        outcomes[index] = Some(CardOutcome::Failed(err.to_string()));
+                    }
                 }
             }
+        }
+
+        Ok(BatchReport {
+            entries: cards.iter()
+                .zip(outcomes)
+                .map(|(card, outcome)| BatchEntry {
+                    front: card.front.clone(),
+                    outcome: outcome.unwrap_or_else(|| CardOutcome::Failed("Card was not processed".to_string())),
+                })
+                .collect(),
+        })
+    }
+
+    // Front field of every note in the deck, with HTML stripped
+    pub async fn fetch_deck_fronts(&self, deck_name: &str) -> Result<Vec<String>, WordcraftError> {
+        let note_ids = self.find_notes(&deck_query(deck_name)).await?;
+        if note_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let notes = self.notes_info(&note_ids).await?;
+        Ok(notes.iter()
+            .filter_map(|note| note.fields.get("Front"))
+            .map(|field| strip_html(&field.value))
+            .filter(|front| !front.is_empty())
+            .collect())
+    }
 
-            println!("Wordcraft model created successfully.");
+    // Upload an audio file to Anki's media folder. Files are named after
+    // their content, so storing one that is already there changes nothing.
+    pub async fn store_media(&self, file: &MediaFile) -> Result<String, WordcraftError> {
+        self.store_media_file(&file.filename, &STANDARD.encode(&file.data)).await
+            .map_err(|err| err.context(format!("Error storing {}", file.filename)))
+    }
+
+    pub async fn check_connection(&self) -> Result<(), WordcraftError> {
+        self.version().await?;
+        Ok(())
+    }
+
+    pub async fn ensure_wordcraft_model_exists(&self) -> Result<(), WordcraftError> {
+        self.ensure_note_type_exists(NoteKind::Basic).await
+    }
+
+    // Create the Anki model of a Wordcraft note type unless it already
+    // exists, and migrate it when it was made by an older version
+    pub async fn ensure_note_type_exists(&self, kind: NoteKind) -> Result<(), WordcraftError> {
+        let note_type = kind.note_type();
+        let model_names = self.model_names().await
+            .map_err(|err| err.context("Failed to get model names"))?;
+
+        if !model_names.iter().any(|name| name == note_type.model_name) {
+            // Create the model if it doesn't exist
+            self.create_model(note_type.create_model()).await
+                .map_err(|err| err.context(format!("Error creating {} model", note_type.model_name)))?;
+
+            println!("{} model created successfully.", note_type.model_name);
+        } else if let Some(version) = self.migrate_note_type(kind).await? {
+            println!("{} model updated from version {} to {}.", note_type.model_name, version, WORDCRAFT_MODEL_VERSION);
         } else {
-            println!("Wordcraft model already exists.");
+            println!("{} model already exists.", note_type.model_name);
         }
 
         Ok(())
     }
+
+    // Bring an existing model up to `WORDCRAFT_MODEL_VERSION` without touching
+    // its notes: missing fields are added at their position (empty on old
+    // notes), then the templates and the styling are rewritten. Fields the
+    // model has beyond ours are left alone. Returns the version the model
+    // had, or None when it was already current.
+    pub async fn migrate_note_type(&self, kind: NoteKind) -> Result<Option<u32>, WordcraftError> {
+        let note_type = kind.note_type();
+        let model_name = note_type.model_name;
+        let migration_error = |err: WordcraftError| err.context(format!("Error migrating {} model", model_name));
+
+        let styling = self.model_styling(model_name).await.map_err(migration_error)?;
+        let version = model_version(&styling.css);
+        if version >= WORDCRAFT_MODEL_VERSION {
+            return Ok(None);
+        }
+
+        let mut fields = self.model_field_names(model_name).await.map_err(migration_error)?;
+        for (index, field) in note_type.fields.iter().enumerate() {
+            if fields.iter().any(|existing| existing == field) {
+                continue;
+            }
+            let index = index.min(fields.len());
+            self.model_field_add(model_name, field, index as u32).await.map_err(migration_error)?;
+            fields.insert(index, field.to_string());
+        }
+
+        self.update_model_templates(model_name, note_type.card_templates()).await.map_err(migration_error)?;
+        self.update_model_styling(model_name, &note_type.styling()).await.map_err(migration_error)?;
+
+        Ok(Some(version))
+    }
+
+    // Miscellaneous actions
+
+    pub async fn version(&self) -> Result<u32, WordcraftError> {
+        self.invoke(Version).await
+    }
+
+    pub async fn sync(&self) -> Result<(), WordcraftError> {
+        self.invoke(SyncCollection).await
+    }
+
+    // Deck actions
+
+    pub async fn deck_names(&self) -> Result<Vec<String>, WordcraftError> {
+        self.invoke(DeckNames).await
+    }
+
+    pub async fn deck_names_and_ids(&self) -> Result<HashMap<String, i64>, WordcraftError> {
+        self.invoke(DeckNamesAndIds).await
+    }
+
+    pub async fn delete_decks(&self, decks: &[&str], cards_too: bool) -> Result<(), WordcraftError> {
+        self.invoke(DeleteDecks {
+            decks: decks.iter().map(|deck| deck.to_string()).collect(),
+            cards_too,
+        }).await
+    }
+
+    pub async fn change_deck(&self, cards: &[i64], deck: &str) -> Result<(), WordcraftError> {
+        self.invoke(ChangeDeck { cards: cards.to_vec(), deck: deck.to_string() }).await
+    }
+
+    pub async fn get_deck_stats(&self, decks: &[&str]) -> Result<HashMap<String, DeckStats>, WordcraftError> {
+        self.invoke(GetDeckStats { decks: decks.iter().map(|deck| deck.to_string()).collect() }).await
+    }
+
+    // Note actions
+
+    pub async fn add_note(&self, note: Note) -> Result<i64, WordcraftError> {
+        self.invoke(AddNote { note }).await
+    }
+
+    pub async fn add_notes(&self, notes: Vec<Note>) -> Result<Vec<Option<i64>>, WordcraftError> {
+        self.invoke(AddNotes { notes }).await
+    }
+
+    pub async fn can_add_notes(&self, notes: Vec<Note>) -> Result<Vec<bool>, WordcraftError> {
+        self.invoke(CanAddNotes { notes }).await
+    }
+
+    pub async fn can_add_notes_with_error_detail(&self, notes: Vec<Note>) -> Result<Vec<CanAddResult>, WordcraftError> {
+        self.invoke(CanAddNotesWithErrorDetail { notes }).await
+    }
+
+    pub async fn update_note_fields(&self, id: i64, fields: BTreeMap<String, String>) -> Result<(), WordcraftError> {
+        self.invoke(UpdateNoteFields { note: NoteFieldsUpdate { id, fields } }).await
+    }
+
+    pub async fn find_notes(&self, query: &str) -> Result<Vec<i64>, WordcraftError> {
+        self.invoke(FindNotes { query: query.to_string() }).await
+    }
+
+    pub async fn notes_info(&self, notes: &[i64]) -> Result<Vec<NoteInfo>, WordcraftError> {
+        self.invoke(NotesInfo { notes: notes.to_vec() }).await
+    }
+
+    pub async fn delete_notes(&self, notes: &[i64]) -> Result<(), WordcraftError> {
+        self.invoke(DeleteNotes { notes: notes.to_vec() }).await
+    }
+
+    pub async fn add_tags(&self, notes: &[i64], tags: &[&str]) -> Result<(), WordcraftError> {
+        self.invoke(AddTags { notes: notes.to_vec(), tags: tags.join(" ") }).await
+    }
+
+    pub async fn remove_tags(&self, notes: &[i64], tags: &[&str]) -> Result<(), WordcraftError> {
+        self.invoke(RemoveTags { notes: notes.to_vec(), tags: tags.join(" ") }).await
+    }
+
+    pub async fn get_tags(&self) -> Result<Vec<String>, WordcraftError> {
+        self.invoke(GetTags).await
+    }
+
+    // Card actions
+
+    pub async fn find_cards(&self, query: &str) -> Result<Vec<i64>, WordcraftError> {
+        self.invoke(FindCards { query: query.to_string() }).await
+    }
+
+    pub async fn cards_info(&self, cards: &[i64]) -> Result<Vec<CardInfo>, WordcraftError> {
+        self.invoke(CardsInfo { cards: cards.to_vec() }).await
+    }
+
+    pub async fn cards_to_notes(&self, cards: &[i64]) -> Result<Vec<i64>, WordcraftError> {
+        self.invoke(CardsToNotes { cards: cards.to_vec() }).await
+    }
+
+    pub async fn suspend(&self, cards: &[i64]) -> Result<bool, WordcraftError> {
+        self.invoke(Suspend { cards: cards.to_vec() }).await
+    }
+
+    pub async fn unsuspend(&self, cards: &[i64]) -> Result<bool, WordcraftError> {
+        self.invoke(Unsuspend { cards: cards.to_vec() }).await
+    }
+
+    pub async fn are_suspended(&self, cards: &[i64]) -> Result<Vec<Option<bool>>, WordcraftError> {
+        self.invoke(AreSuspended { cards: cards.to_vec() }).await
+    }
+
+    pub async fn are_due(&self, cards: &[i64]) -> Result<Vec<bool>, WordcraftError> {
+        self.invoke(AreDue { cards: cards.to_vec() }).await
+    }
+
+    pub async fn get_ease_factors(&self, cards: &[i64]) -> Result<Vec<i64>, WordcraftError> {
+        self.invoke(GetEaseFactors { cards: cards.to_vec() }).await
+    }
+
+    pub async fn get_intervals(&self, cards: &[i64]) -> Result<Vec<i64>, WordcraftError> {
+        self.invoke(GetIntervals { cards: cards.to_vec() }).await
+    }
+
+    pub async fn get_reviews_of_cards(&self, cards: &[i64]) -> Result<HashMap<String, Vec<ReviewEntry>>, WordcraftError> {
+        self.invoke(GetReviewsOfCards { cards: cards.iter().map(|card| card.to_string()).collect() }).await
+    }
+
+    // Model actions
+
+    pub async fn model_names(&self) -> Result<Vec<String>, WordcraftError> {
+        self.invoke(ModelNames).await
+    }
+
+    pub async fn model_names_and_ids(&self) -> Result<HashMap<String, i64>, WordcraftError> {
+        self.invoke(ModelNamesAndIds).await
+    }
+
+    pub async fn model_field_names(&self, model_name: &str) -> Result<Vec<String>, WordcraftError> {
+        self.invoke(ModelFieldNames { model_name: model_name.to_string() }).await
+    }
+
+    pub async fn create_model(&self, model: CreateModel) -> Result<Value, WordcraftError> {
+        self.invoke(model).await
+    }
+
+    pub async fn model_templates(&self, model_name: &str) -> Result<HashMap<String, CardTemplate>, WordcraftError> {
+        self.invoke(ModelTemplates { model_name: model_name.to_string() }).await
+    }
+
+    pub async fn model_styling(&self, model_name: &str) -> Result<ModelCss, WordcraftError> {
+        self.invoke(ModelStyling { model_name: model_name.to_string() }).await
+    }
+
+    pub async fn update_model_templates(&self, name: &str, templates: BTreeMap<String, CardTemplate>) -> Result<(), WordcraftError> {
+        self.invoke(UpdateModelTemplates {
+            model: ModelTemplatesUpdate { name: name.to_string(), templates },
+        }).await
+    }
+
+    pub async fn update_model_styling(&self, name: &str, css: &str) -> Result<(), WordcraftError> {
+        self.invoke(UpdateModelStyling {
+            model: ModelStylingUpdate { name: name.to_string(), css: css.to_string() },
+        }).await
+    }
+
+    pub async fn model_field_add(&self, model_name: &str, field_name: &str, index: u32) -> Result<(), WordcraftError> {
+        self.invoke(ModelFieldAdd {
+            model_name: model_name.to_string(),
+            field_name: field_name.to_string(),
+            index,
+        }).await
+    }
+
+    // Media actions
+
+    pub async fn store_media_file(&self, filename: &str, base64_data: &str) -> Result<String, WordcraftError> {
+        self.invoke(StoreMediaFile {
+            filename: filename.to_string(),
+            data: Some(base64_data.to_string()),
+            path: None,
+            url: None,
+            delete_existing: None,
+        }).await
+    }
+
+    // Returns the base64 encoded file, or None if Anki has no file with that name
+    pub async fn retrieve_media_file(&self, filename: &str) -> Result<Option<String>, WordcraftError> {
+        let result = self.invoke(RetrieveMediaFile { filename: filename.to_string() }).await?;
+        Ok(result.as_str().map(|data| data.to_string()))
+    }
+
+    pub async fn get_media_files_names(&self, pattern: &str) -> Result<Vec<String>, WordcraftError> {
+        self.invoke(GetMediaFilesNames { pattern: pattern.to_string() }).await
+    }
+
+    pub async fn delete_media_file(&self, filename: &str) -> Result<(), WordcraftError> {
+        self.invoke(DeleteMediaFile { filename: filename.to_string() }).await
+    }
+
+    // GUI actions
+
+    pub async fn gui_browse(&self, query: &str) -> Result<Vec<i64>, WordcraftError> {
+        self.invoke(GuiBrowse { query: query.to_string() }).await
+    }
+
+    pub async fn gui_add_cards(&self, note: Note) -> Result<i64, WordcraftError> {
+        self.invoke(GuiAddCards { note }).await
+    }
+
+    pub async fn gui_current_card(&self) -> Result<Option<Value>, WordcraftError> {
+        self.invoke(GuiCurrentCard).await
+    }
+
+    pub async fn gui_deck_overview(&self, name: &str) -> Result<bool, WordcraftError> {
+        self.invoke(GuiDeckOverview { name: name.to_string() }).await
+    }
+
+    pub async fn gui_deck_browser(&self) -> Result<(), WordcraftError> {
+        self.invoke(GuiDeckBrowser).await
+    }
+}
+
+// Add cards to an existing deck, with their audio when TTS is configured.
+// Audio goes to the media folder first so the notes' [sound:] references resolve.
+pub async fn insert_cards(
+    adapter: &AnkiAdapter,
+    cards: &mut [Flashcard],
+    deck_name: &str,
+    note_kind: NoteKind,
+    tags: &[String],
+    target_language: &str,
+    tts: Option<&dyn TtsEngine>,
+) -> Result<BatchReport, WordcraftError> {
+    for file in synthesize_audio(tts, cards, target_language).await {
+        if let Err(err) = adapter.store_media(&file).await {
+            eprintln!("{}", err);
+        }
+    }
+
+    adapter.add_cards_tagged(deck_name, cards, note_kind, tags).await
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CardOutcome {
+    Added(i64),
+    Duplicate,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchEntry {
+    pub front: String,
+    pub outcome: CardOutcome,
+}
+
+// Per-card result of `AnkiAdapter::add_cards`, in the same order as the input cards
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchReport {
+    pub entries: Vec<BatchEntry>,
+}
+
+impl BatchReport {
+    pub fn added(&self) -> Vec<i64> {
+        self.entries.iter()
+            .filter_map(|entry| match entry.outcome {
+                CardOutcome::Added(note_id) => Some(note_id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn duplicates(&self) -> Vec<&str> {
+        self.entries.iter()
+            .filter(|entry| entry.outcome == CardOutcome::Duplicate)
+            .map(|entry| entry.front.as_str())
+            .collect()
+    }
+
+    pub fn failures(&self) -> Vec<(&str, &str)> {
+        self.entries.iter()
+            .filter_map(|entry| match &entry.outcome {
+                CardOutcome::Failed(reason) => Some((entry.front.as_str(), reason.as_str())),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+// Outcome of a note Anki refused, from its reason
+fn rejected(reason: String) -> CardOutcome {
+    if reason.contains("duplicate") {
+        CardOutcome::Duplicate
+    } else {
+        CardOutcome::Failed(reason)
+    }
+}
+
+// Anki search query matching every note in a deck
+pub fn deck_query(deck_name: &str) -> String {
+    format!("deck:\"{}\"", deck_name.replace('"', "\\\""))
+}
+
+// Remove HTML tags and the common entities Anki stores in field values
+pub(crate) fn strip_html(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut in_tag = false;
+    for c in value.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+// Build a note for the Wordcraft model from the four flashcard fields
+pub fn wordcraft_note(deck_name: &str, front: &str, back: &str, example: &str, example_translate: &str) -> Note {
+    let fields = WORDCRAFT_FIELDS.iter()
+        .zip([front, back, example, example_translate])
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+
+    Note {
+        deck_name: deck_name.to_string(),
+        model_name: WORDCRAFT_MODEL_NAME.to_string(),
+        fields,
+        options: NoteOptions { allow_duplicate: false, duplicate_scope: None },
+        tags: WORDCRAFT_TAGS.iter().map(|tag| tag.to_string()).collect(),
+    }
 }
@@ -0,0 +1,684 @@
+// Typed request/response layer for the AnkiConnect API (version 6).
+//
+// Every action is a plain struct holding the action's `params`. The
+// `AnkiAction` trait ties it to the AnkiConnect action name and to the type
+// the `result` field of the response deserializes into, so
+// `AnkiAdapter::invoke` can build the request and unwrap the
+// `{ "result": ..., "error": ... }` envelope in a single place. The structs
+// also deserialize, so `fake_anki` reads requests into the same types.
+use std::collections::{BTreeMap, HashMap};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub const ANKI_CONNECT_VERSION: u8 = 6;
+
+pub trait AnkiAction: Serialize {
+    /// Name of the AnkiConnect action, e.g. `"addNote"`.
+    const NAME: &'static str;
+    /// Type the `result` field of a successful response deserializes into.
+    type Output: DeserializeOwned;
+}
+
+// Envelope returned by AnkiConnect for every action
+#[derive(Debug, Deserialize)]
+pub struct AnkiResponse {
+    #[serde(default)]
+    pub result: Value,
+    #[serde(default)]
+    pub error: Option<Value>,
+}
+
+// ---------------------------------------------------------------------------
+// Shared types
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Note {
+    pub deck_name: String,
+    pub model_name: String,
+    pub fields: BTreeMap<String, String>,
+    #[serde(default)]
+    pub options: NoteOptions,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct NoteOptions {
+    pub allow_duplicate: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_scope: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NoteFieldsUpdate {
+    pub id: i64,
+    pub fields: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldValue {
+    pub value: String,
+    pub order: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteInfo {
+    pub note_id: i64,
+    pub model_name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub fields: HashMap<String, FieldValue>,
+    #[serde(default)]
+    pub cards: Vec<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardInfo {
+    pub card_id: i64,
+    pub note: i64,
+    pub deck_name: String,
+    pub model_name: String,
+    pub fields: HashMap<String, FieldValue>,
+    #[serde(default)]
+    pub question: String,
+    #[serde(default)]
+    pub answer: String,
+    /// Current interval in days (negative values are seconds for learning cards)
+    #[serde(default)]
+    pub interval: i64,
+    /// Ease factor in permille, e.g. 2500 for 250%
+    #[serde(default, rename = "factor")]
+    pub ease_factor: i64,
+    #[serde(default, rename = "type")]
+    pub card_type: i64,
+    #[serde(default)]
+    pub queue: i64,
+    #[serde(default)]
+    pub due: i64,
+    #[serde(default)]
+    pub reps: i64,
+    #[serde(default)]
+    pub lapses: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewEntry {
+    pub id: i64,
+    #[serde(default)]
+    pub usn: i64,
+    /// Answer button pressed: 1 (again) to 4 (easy)
+    pub ease: i64,
+    pub ivl: i64,
+    #[serde(default)]
+    pub last_ivl: i64,
+    #[serde(default)]
+    pub factor: i64,
+    #[serde(default)]
+    pub time: i64,
+    #[serde(default, rename = "type")]
+    pub review_type: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeckStats {
+    pub deck_id: i64,
+    pub name: String,
+    pub new_count: i64,
+    pub learn_count: i64,
+    pub review_count: i64,
+    pub total_in_deck: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CardTemplate {
+    #[serde(rename = "Name", skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "Front")]
+    pub front: String,
+    #[serde(rename = "Back")]
+    pub back: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelCss {
+    pub css: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelTemplatesUpdate {
+    pub name: String,
+    pub templates: BTreeMap<String, CardTemplate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelStylingUpdate {
+    pub name: String,
+    pub css: String,
+}
+
+// ---------------------------------------------------------------------------
+// Miscellaneous actions
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Version;
+
+impl AnkiAction for Version {
+    const NAME: &'static str = "version";
+    type Output = u32;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncCollection;
+
+impl AnkiAction for SyncCollection {
+    const NAME: &'static str = "sync";
+    type Output = ();
+}
+
+// ---------------------------------------------------------------------------
+// Deck actions
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeckNames;
+
+impl AnkiAction for DeckNames {
+    const NAME: &'static str = "deckNames";
+    type Output = Vec<String>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeckNamesAndIds;
+
+impl AnkiAction for DeckNamesAndIds {
+    const NAME: &'static str = "deckNamesAndIds";
+    type Output = HashMap<String, i64>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateDeck {
+    pub deck: String,
+}
+
+impl AnkiAction for CreateDeck {
+    const NAME: &'static str = "createDeck";
+    type Output = Option<i64>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteDecks {
+    pub decks: Vec<String>,
+    pub cards_too: bool,
+}
+
+impl AnkiAction for DeleteDecks {
+    const NAME: &'static str = "deleteDecks";
+    type Output = ();
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeDeck {
+    pub cards: Vec<i64>,
+    pub deck: String,
+}
+
+impl AnkiAction for ChangeDeck {
+    const NAME: &'static str = "changeDeck";
+    type Output = ();
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetDeckStats {
+    pub decks: Vec<String>,
+}
+
+impl AnkiAction for GetDeckStats {
+    const NAME: &'static str = "getDeckStats";
+    type Output = HashMap<String, DeckStats>;
+}
+
+// ---------------------------------------------------------------------------
+// Note actions
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddNote {
+    pub note: Note,
+}
+
+impl AnkiAction for AddNote {
+    const NAME: &'static str = "addNote";
+    type Output = i64;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddNotes {
+    pub notes: Vec<Note>,
+}
+
+impl AnkiAction for AddNotes {
+    const NAME: &'static str = "addNotes";
+    type Output = Vec<Option<i64>>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanAddNotes {
+    pub notes: Vec<Note>,
+}
+
+impl AnkiAction for CanAddNotes {
+    const NAME: &'static str = "canAddNotes";
+    type Output = Vec<bool>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanAddNotesWithErrorDetail {
+    pub notes: Vec<Note>,
+}
+
+impl AnkiAction for CanAddNotesWithErrorDetail {
+    const NAME: &'static str = "canAddNotesWithErrorDetail";
+    type Output = Vec<CanAddResult>;
+}
+
+// Whether a note can be added and, when not, Anki's reason
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CanAddResult {
+    pub can_add: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateNoteFields {
+    pub note: NoteFieldsUpdate,
+}
+
+impl AnkiAction for UpdateNoteFields {
+    const NAME: &'static str = "updateNoteFields";
+    type Output = ();
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FindNotes {
+    pub query: String,
+}
+
+impl AnkiAction for FindNotes {
+    const NAME: &'static str = "findNotes";
+    type Output = Vec<i64>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotesInfo {
+    pub notes: Vec<i64>,
+}
+
+impl AnkiAction for NotesInfo {
+    const NAME: &'static str = "notesInfo";
+    type Output = Vec<NoteInfo>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteNotes {
+    pub notes: Vec<i64>,
+}
+
+impl AnkiAction for DeleteNotes {
+    const NAME: &'static str = "deleteNotes";
+    type Output = ();
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddTags {
+    pub notes: Vec<i64>,
+    /// Space separated list of tags
+    pub tags: String,
+}
+
+impl AnkiAction for AddTags {
+    const NAME: &'static str = "addTags";
+    type Output = ();
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoveTags {
+    pub notes: Vec<i64>,
+    /// Space separated list of tags
+    pub tags: String,
+}
+
+impl AnkiAction for RemoveTags {
+    const NAME: &'static str = "removeTags";
+    type Output = ();
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetTags;
+
+impl AnkiAction for GetTags {
+    const NAME: &'static str = "getTags";
+    type Output = Vec<String>;
+}
+
+// ---------------------------------------------------------------------------
+// Card actions
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FindCards {
+    pub query: String,
+}
+
+impl AnkiAction for FindCards {
+    const NAME: &'static str = "findCards";
+    type Output = Vec<i64>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CardsInfo {
+    pub cards: Vec<i64>,
+}
+
+impl AnkiAction for CardsInfo {
+    const NAME: &'static str = "cardsInfo";
+    type Output = Vec<CardInfo>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CardsToNotes {
+    pub cards: Vec<i64>,
+}
+
+impl AnkiAction for CardsToNotes {
+    const NAME: &'static str = "cardsToNotes";
+    type Output = Vec<i64>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Suspend {
+    pub cards: Vec<i64>,
+}
+
+impl AnkiAction for Suspend {
+    const NAME: &'static str = "suspend";
+    type Output = bool;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Unsuspend {
+    pub cards: Vec<i64>,
+}
+
+impl AnkiAction for Unsuspend {
+    const NAME: &'static str = "unsuspend";
+    type Output = bool;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AreSuspended {
+    pub cards: Vec<i64>,
+}
+
+impl AnkiAction for AreSuspended {
+    const NAME: &'static str = "areSuspended";
+    type Output = Vec<Option<bool>>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AreDue {
+    pub cards: Vec<i64>,
+}
+
+impl AnkiAction for AreDue {
+    const NAME: &'static str = "areDue";
+    type Output = Vec<bool>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetEaseFactors {
+    pub cards: Vec<i64>,
+}
+
+impl AnkiAction for GetEaseFactors {
+    const NAME: &'static str = "getEaseFactors";
+    type Output = Vec<i64>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetIntervals {
+    pub cards: Vec<i64>,
+}
+
+impl AnkiAction for GetIntervals {
+    const NAME: &'static str = "getIntervals";
+    type Output = Vec<i64>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetReviewsOfCards {
+    pub cards: Vec<String>,
+}
+
+impl AnkiAction for GetReviewsOfCards {
+    const NAME: &'static str = "getReviewsOfCards";
+    type Output = HashMap<String, Vec<ReviewEntry>>;
+}
+
+// ---------------------------------------------------------------------------
+// Model actions
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelNames;
+
+impl AnkiAction for ModelNames {
+    const NAME: &'static str = "modelNames";
+    type Output = Vec<String>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelNamesAndIds;
+
+impl AnkiAction for ModelNamesAndIds {
+    const NAME: &'static str = "modelNamesAndIds";
+    type Output = HashMap<String, i64>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelFieldNames {
+    pub model_name: String,
+}
+
+impl AnkiAction for ModelFieldNames {
+    const NAME: &'static str = "modelFieldNames";
+    type Output = Vec<String>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateModel {
+    pub model_name: String,
+    pub in_order_fields: Vec<String>,
+    pub css: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_cloze: Option<bool>,
+    pub card_templates: Vec<CardTemplate>,
+}
+
+impl AnkiAction for CreateModel {
+    const NAME: &'static str = "createModel";
+    type Output = Value;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelTemplates {
+    pub model_name: String,
+}
+
+impl AnkiAction for ModelTemplates {
+    const NAME: &'static str = "modelTemplates";
+    type Output = HashMap<String, CardTemplate>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelStyling {
+    pub model_name: String,
+}
+
+impl AnkiAction for ModelStyling {
+    const NAME: &'static str = "modelStyling";
+    type Output = ModelCss;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateModelTemplates {
+    pub model: ModelTemplatesUpdate,
+}
+
+impl AnkiAction for UpdateModelTemplates {
+    const NAME: &'static str = "updateModelTemplates";
+    type Output = ();
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateModelStyling {
+    pub model: ModelStylingUpdate,
+}
+
+impl AnkiAction for UpdateModelStyling {
+    const NAME: &'static str = "updateModelStyling";
+    type Output = ();
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelFieldAdd {
+    pub model_name: String,
+    pub field_name: String,
+    pub index: u32,
+}
+
+impl AnkiAction for ModelFieldAdd {
+    const NAME: &'static str = "modelFieldAdd";
+    type Output = ();
+}
+
+// ---------------------------------------------------------------------------
+// Media actions
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreMediaFile {
+    pub filename: String,
+    /// Base64 encoded file contents
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delete_existing: Option<bool>,
+}
+
+impl AnkiAction for StoreMediaFile {
+    const NAME: &'static str = "storeMediaFile";
+    type Output = String;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrieveMediaFile {
+    pub filename: String,
+}
+
+impl AnkiAction for RetrieveMediaFile {
+    const NAME: &'static str = "retrieveMediaFile";
+    /// Base64 encoded contents, or `false` when the file does not exist
+    type Output = Value;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetMediaFilesNames {
+    pub pattern: String,
+}
+
+impl AnkiAction for GetMediaFilesNames {
+    const NAME: &'static str = "getMediaFilesNames";
+    type Output = Vec<String>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteMediaFile {
+    pub filename: String,
+}
+
+impl AnkiAction for DeleteMediaFile {
+    const NAME: &'static str = "deleteMediaFile";
+    type Output = ();
+}
+
+// ---------------------------------------------------------------------------
+// GUI actions
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuiBrowse {
+    pub query: String,
+}
+
+impl AnkiAction for GuiBrowse {
+    const NAME: &'static str = "guiBrowse";
+    type Output = Vec<i64>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuiAddCards {
+    pub note: Note,
+}
+
+impl AnkiAction for GuiAddCards {
+    const NAME: &'static str = "guiAddCards";
+    type Output = i64;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuiCurrentCard;
+
+impl AnkiAction for GuiCurrentCard {
+    const NAME: &'static str = "guiCurrentCard";
+    type Output = Option<Value>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuiDeckOverview {
+    pub name: String,
+}
+
+impl AnkiAction for GuiDeckOverview {
+    const NAME: &'static str = "guiDeckOverview";
+    type Output = bool;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuiDeckBrowser;
+
+impl AnkiAction for GuiDeckBrowser {
+    const NAME: &'static str = "guiDeckBrowser";
+    type Output = ();
+}
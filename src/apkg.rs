@@ -0,0 +1,421 @@
+// Offline deck export as an Anki package (.apkg).
+//
+// An .apkg is a zip archive holding a `collection.anki2` SQLite database in
+// the legacy (schema 11) layout that every Anki version can import, a `media`
+// manifest mapping numbered archive entries to file names, and the media
+// files themselves. The Wordcraft note type uses a fixed id and notes get a
+// guid derived from deck and front, so importing the same export twice
+// updates the notes instead of duplicating them.
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use sha1::{Digest, Sha1};
+use tempfile::TempDir;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::anki_adapter::strip_html;
+use crate::error::WordcraftError;
+use crate::generation::{Flashcard, FlashcardResponse};
+use crate::note_type::{NoteKind, NoteType, NOTE_KINDS, WORDCRAFT_TAGS};
+use crate::tts::MediaFile;
+
+pub const COLLECTION_FILE: &str = "collection.anki2";
+pub const MEDIA_FILE: &str = "media";
+
+// Stable id of the Wordcraft note type in exported collections; the other
+// note types follow it in `NOTE_KINDS` order
+pub const WORDCRAFT_MODEL_ID: i64 = 1_700_000_000_001;
+
+// Id of Anki's built-in "Default" deck and options group
+const DEFAULT_DECK_ID: i64 = 1;
+const DEFAULT_CONF_ID: i64 = 1;
+const SCHEMA_VERSION: i64 = 11;
+const FIELD_SEPARATOR: char = '\x1f';
+
+const SCHEMA: &str = "
+CREATE TABLE col (
+    id integer primary key, crt integer not null, mod integer not null, scm integer not null,
+    ver integer not null, dty integer not null, usn integer not null, ls integer not null,
+    conf text not null, models text not null, decks text not null, dconf text not null, tags text not null
+);
+CREATE TABLE notes (
+    id integer primary key, guid text not null, mid integer not null, mod integer not null,
+    usn integer not null, tags text not null, flds text not null, sfld integer not null,
+    csum integer not null, flags integer not null, data text not null
+);
+CREATE TABLE cards (
+    id integer primary key, nid integer not null, did integer not null, ord integer not null,
+    mod integer not null, usn integer not null, type integer not null, queue integer not null,
+    due integer not null, ivl integer not null, factor integer not null, reps integer not null,
+    lapses integer not null, left integer not null, odue integer not null, odid integer not null,
+    flags integer not null, data text not null
+);
+CREATE TABLE revlog (
+    id integer primary key, cid integer not null, usn integer not null, ease integer not null,
+    ivl integer not null, lastIvl integer not null, factor integer not null, time integer not null,
+    type integer not null
+);
+CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);
+CREATE INDEX ix_notes_usn on notes (usn);
+CREATE INDEX ix_cards_usn on cards (usn);
+CREATE INDEX ix_revlog_usn on revlog (usn);
+CREATE INDEX ix_cards_nid on cards (nid);
+CREATE INDEX ix_cards_sched on cards (did, queue, due);
+CREATE INDEX ix_revlog_cid on revlog (cid);
+CREATE INDEX ix_notes_csum on notes (csum);
+";
+
+// A Wordcraft note waiting to be written, fields in the note type's order
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApkgNote {
+    pub fields: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ApkgWriter {
+    deck_name: String,
+    note_type: NoteType,
+    notes: Vec<ApkgNote>,
+    media: BTreeMap<String, Vec<u8>>,
+}
+
+impl ApkgWriter {
+    pub fn new(deck_name: &str) -> Self {
+        ApkgWriter::with_note_type(deck_name, NoteKind::Basic)
+    }
+
+    pub fn with_note_type(deck_name: &str, kind: NoteKind) -> Self {
+        ApkgWriter {
+            deck_name: deck_name.to_string(),
+            note_type: kind.note_type(),
+            notes: Vec::new(),
+            media: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_flashcard(&mut self, card: &Flashcard) {
+        self.notes.push(ApkgNote {
+            fields: self.note_type.field_values(card),
+            tags: WORDCRAFT_TAGS.iter().map(|tag| tag.to_string()).collect(),
+        });
+    }
+
+    // Bundle a file referenced from a field, e.g. `[sound:word.mp3]`
+    pub fn add_media(&mut self, filename: &str, data: Vec<u8>) {
+        self.media.insert(filename.to_string(), data);
+    }
+
+    // Write the .apkg archive
+    pub fn write(&self, path: &Path) -> Result<(), WordcraftError> {
+        let workdir = scratch_dir()?;
+        let collection_path = workdir.path().join(COLLECTION_FILE);
+        self.write_collection(&collection_path)?;
+        let collection = std::fs::read(&collection_path)?;
+
+        let mut zip = ZipWriter::new(File::create(path)?);
+        let options = SimpleFileOptions::default();
+
+        zip.start_file(COLLECTION_FILE, options)?;
+        zip.write_all(&collection)?;
+
+        // Media entries are stored as "0", "1", ... and named by the manifest
+        let mut manifest = serde_json::Map::new();
+        for (index, (filename, data)) in self.media.iter().enumerate() {
+            manifest.insert(index.to_string(), Value::String(filename.clone()));
+            zip.start_file(index.to_string(), options)?;
+            zip.write_all(data)?;
+        }
+        zip.start_file(MEDIA_FILE, options)?;
+        zip.write_all(Value::Object(manifest).to_string().as_bytes())?;
+
+        zip.finish()?;
+        Ok(())
+    }
+
+    // Write only the SQLite collection
+    pub fn write_collection(&self, path: &Path) -> Result<(), WordcraftError> {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+
+        let now_ms = now_millis();
+        let deck_id = deck_id(&self.deck_name);
+        let model_id = model_id(self.note_type.kind);
+        let mut connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO col VALUES (1, ?1, ?2, ?2, ?3, 0, 0, 0, ?4, ?5, ?6, ?7, '{}')",
+            params![
+                now_ms / 1000,
+                now_ms,
+                SCHEMA_VERSION,
+                collection_conf(deck_id, model_id).to_string(),
+                models(&self.note_type, model_id, deck_id, now_ms).to_string(),
+                decks(deck_id, &self.deck_name, now_ms).to_string(),
+                deck_confs().to_string(),
+            ],
+        )?;
+
+        let mut card_id = now_ms;
+        for (index, note) in self.notes.iter().enumerate() {
+            // Note and card ids only have to be unique within the package
+            let note_id = now_ms + index as i64;
+            let sort_field = note.fields.first().map(|field| strip_html(field)).unwrap_or_default();
+            let tags = if note.tags.is_empty() { String::new() } else { format!(" {} ", note.tags.join(" ")) };
+
+            transaction.execute(
+                "INSERT INTO notes VALUES (?1, ?2, ?3, ?4, -1, ?5, ?6, ?7, ?8, 0, '')",
+                params![
+                    note_id,
+                    note_guid(&self.deck_name, &sort_field),
+                    model_id,
+                    now_ms / 1000,
+                    tags,
+                    note.fields.join(&FIELD_SEPARATOR.to_string()),
+                    sort_field,
+                    field_checksum(&sort_field),
+                ],
+            )?;
+            // New cards: type 0, queue 0, due is the position in the new queue
+            for ord in card_ordinals(&self.note_type, note) {
+                transaction.execute(
+                    "INSERT INTO cards VALUES (?1, ?2, ?3, ?4, ?5, -1, 0, 0, ?6, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+                    params![card_id, note_id, deck_id, ord, now_ms / 1000, index as i64 + 1],
+                )?;
+                card_id += 1;
+            }
+        }
+
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+// Save a generated deck as an .apkg file
+pub fn export_apkg(response: &FlashcardResponse, path: &Path) -> Result<(), WordcraftError> {
+    export_apkg_as(response, NoteKind::Basic, &[], path)
+}
+
+// Save a deck with the given note type, bundling the cards' audio files
+pub fn export_apkg_as(
+    response: &FlashcardResponse,
+    kind: NoteKind,
+    media: &[MediaFile],
+    path: &Path,
+) -> Result<(), WordcraftError> {
+    let mut writer = ApkgWriter::with_note_type(&response.deck_name, kind);
+    response.cards.iter().for_each(|card| writer.add_flashcard(card));
+    media.iter().for_each(|file| writer.add_media(&file.filename, file.data.clone()));
+    writer.write(path)
+}
+
+pub fn model_id(kind: NoteKind) -> i64 {
+    let position = NOTE_KINDS.iter().position(|other| *other == kind).unwrap_or_default();
+    WORDCRAFT_MODEL_ID + position as i64
+}
+
+// Template ordinals of the cards a note produces: one per template, or one
+// per cloze number for cloze notes
+fn card_ordinals(note_type: &NoteType, note: &ApkgNote) -> Vec<i64> {
+    if !note_type.is_cloze {
+        return (0..note_type.templates.len() as i64).collect();
+    }
+
+    let text = note.fields.first().map(String::as_str).unwrap_or_default();
+    let mut ordinals: Vec<i64> = text.split("{{c")
+        .skip(1)
+        .filter_map(|rest| rest.split_once("::"))
+        .filter_map(|(number, _)| number.parse::<i64>().ok())
+        .filter(|number| *number > 0)
+        .map(|number| number - 1)
+        .collect();
+    ordinals.sort_unstable();
+    ordinals.dedup();
+
+    if ordinals.is_empty() { vec![0] } else { ordinals }
+}
+
+// File name for a deck export: the deck name with path separators and other
+// unsafe characters replaced
+pub fn apkg_file_name(deck_name: &str) -> String {
+    let name: String = deck_name.trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == ' ' || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    let name = if name.is_empty() { "wordcraft".to_string() } else { name };
+
+    format!("{}.apkg", name)
+}
+
+// Anki's duplicate check compares the first 8 hex digits of the SHA-1 of the stripped sort field
+pub fn field_checksum(sort_field: &str) -> i64 {
+    let digest = Sha1::digest(sort_field.as_bytes());
+    i64::from(u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]))
+}
+
+fn note_guid(deck_name: &str, sort_field: &str) -> String {
+    let digest = Sha1::digest(format!("wordcraft\x1f{}\x1f{}", deck_name, sort_field).as_bytes());
+    digest.iter().take(8).map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Deck ids derived from the name keep repeated exports in the same deck.
+// Kept below 2^53 so the id survives Anki's JSON handling.
+fn deck_id(deck_name: &str) -> i64 {
+    let digest = Sha1::digest(deck_name.as_bytes());
+    let value = u64::from_be_bytes([digest[0], digest[1], digest[2], digest[3], digest[4], digest[5], digest[6], digest[7]]);
+    (value % (1 << 52)) as i64 + (1 << 40)
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_millis() as i64).unwrap_or_default()
+}
+
+// A new directory for the SQLite collection, removed when dropped
+pub(crate) fn scratch_dir() -> Result<TempDir, WordcraftError> {
+    Ok(tempfile::Builder::new().prefix("wordcraft-apkg-").tempdir()?)
+}
+
+fn collection_conf(deck_id: i64, model_id: i64) -> Value {
+    json!({
+        "nextPos": 1,
+        "estTimes": true,
+        "activeDecks": [deck_id],
+        "sortType": "noteFld",
+        "timeLim": 0,
+        "sortBackwards": false,
+        "addToCur": true,
+        "curDeck": deck_id,
+        "newSpread": 0,
+        "dueCounts": true,
+        "curModel": model_id.to_string(),
+        "collapseTime": 1200
+    })
+}
+
+fn models(note_type: &NoteType, model_id: i64, deck_id: i64, now_ms: i64) -> Value {
+    let fields: Vec<Value> = note_type.fields.iter()
+        .enumerate()
+        .map(|(ord, name)| json!({
+            "name": name,
+            "ord": ord,
+            "sticky": false,
+            "rtl": false,
+            "font": "Arial",
+            "size": 20,
+            "media": []
+        }))
+        .collect();
+    let templates: Vec<Value> = note_type.templates.iter()
+        .enumerate()
+        .map(|(ord, template)| json!({
+            "name": template.name,
+            "ord": ord,
+            "qfmt": template.front,
+            "afmt": template.back,
+            "did": null,
+            "bqfmt": "",
+            "bafmt": ""
+        }))
+        .collect();
+    // Card requirements: each template needs its first field filled in
+    let requirements: Vec<Value> = if note_type.is_cloze {
+        Vec::new()
+    } else {
+        (0..note_type.templates.len()).map(|ord| json!([ord, "any", [0]])).collect()
+    };
+
+    json!({
+        model_id.to_string(): {
+            "id": model_id,
+            "name": note_type.model_name,
+            "type": if note_type.is_cloze { 1 } else { 0 },
+            "mod": now_ms / 1000,
+            "usn": -1,
+            "sortf": 0,
+            "did": deck_id,
+            "tmpls": templates,
+            "flds": fields,
+            "css": note_type.styling(),
+            "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+            "latexPost": "\\end{document}",
+            "latexsvg": false,
+            "req": requirements,
+            "tags": [],
+            "vers": []
+        }
+    })
+}
+
+fn deck(id: i64, name: &str, now_ms: i64) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "mod": now_ms / 1000,
+        "usn": -1,
+        "lrnToday": [0, 0],
+        "revToday": [0, 0],
+        "newToday": [0, 0],
+        "timeToday": [0, 0],
+        "collapsed": false,
+        "browserCollapsed": false,
+        "desc": "",
+        "dyn": 0,
+        "conf": DEFAULT_CONF_ID,
+        "extendNew": 0,
+        "extendRev": 0
+    })
+}
+
+fn decks(deck_id: i64, deck_name: &str, now_ms: i64) -> Value {
+    json!({
+        DEFAULT_DECK_ID.to_string(): deck(DEFAULT_DECK_ID, "Default", now_ms),
+        deck_id.to_string(): deck(deck_id, deck_name, now_ms),
+    })
+}
+
+fn deck_confs() -> Value {
+    json!({
+        DEFAULT_CONF_ID.to_string(): {
+            "id": DEFAULT_CONF_ID,
+            "name": "Default",
+            "mod": 0,
+            "usn": 0,
+            "maxTaken": 60,
+            "autoplay": true,
+            "timer": 0,
+            "replayq": true,
+            "dyn": false,
+            "new": {
+                "delays": [1.0, 10.0],
+                "ints": [1, 4, 0],
+                "initialFactor": 2500,
+                "order": 1,
+                "perDay": 20,
+                "bury": false
+            },
+            "lapse": {
+                "delays": [10.0],
+                "mult": 0.0,
+                "minInt": 1,
+                "leechFails": 8,
+                "leechAction": 1
+            },
+            "rev": {
+                "perDay": 200,
+                "ease4": 1.3,
+                "ivlFct": 1.0,
+                "maxIvl": 36500,
+                "bury": false,
+                "hardFactor": 1.2
+            }
+        }
+    })
+}
@@ -0,0 +1,250 @@
+// Reading notes out of Anki packages (.apkg) and collection backups (.colpkg).
+//
+// Both are zip archives around an SQLite collection. Older exports contain
+// `collection.anki2`; newer ones add `collection.anki21`, which is preferred
+// when present (the anki2 file next to it is then only a placeholder). The
+// newest compress it as `collection.anki21b`, which is not read. The note
+// type and deck names live either as JSON in the `col` table (schema 11) or
+// in the `notetypes`, `fields` and `decks` tables (schema 18).
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::anki_adapter::strip_html;
+use crate::apkg::{scratch_dir, MEDIA_FILE};
+use crate::error::WordcraftError;
+use crate::generation::Flashcard;
+
+const COLLECTION_FILES: [&str; 2] = ["collection.anki21", "collection.anki2"];
+const ZSTD_COLLECTION_FILE: &str = "collection.anki21b";
+
+// Field names used by common community decks, most specific first
+const FRONT_FIELDS: [&str; 6] = ["Front", "Word", "Expression", "Vocabulary", "Vocab", "Kanji"];
+const BACK_FIELDS: [&str; 5] = ["Back", "Meaning", "Definition", "English", "Translation"];
+const EXAMPLE_FIELDS: [&str; 3] = ["Example", "Sentence", "Example Sentence"];
+const EXAMPLE_TRANSLATION_FIELDS: [&str; 4] = ["ExampleTranslation", "Sentence Translation", "Sentence-English", "Example Translation"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedNote {
+    pub note_id: i64,
+    pub model_name: String,
+    // Decks holding the note's cards, usually just one
+    pub deck_names: Vec<String>,
+    // Field names and values in the note type's order
+    pub fields: Vec<(String, String)>,
+    pub tags: Vec<String>,
+}
+
+impl ImportedNote {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.iter()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // The note as a Wordcraft card: fields are matched by their usual names,
+    // falling back to the first and second field for front and back
+    pub fn to_flashcard(&self) -> Flashcard {
+        let pick = |names: &[&str], position: Option<usize>| {
+            names.iter()
+                .find_map(|name| self.field(name))
+                .or_else(|| position.and_then(|index| self.fields.get(index)).map(|(_, value)| value.as_str()))
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        Flashcard {
+            front: pick(&FRONT_FIELDS, Some(0)),
+            back: pick(&BACK_FIELDS, Some(1)),
+            example: pick(&EXAMPLE_FIELDS, None),
+            example_translate: pick(&EXAMPLE_TRANSLATION_FIELDS, None),
+            ..Default::default()
+        }
+    }
+
+    // Front text without HTML, as used for known-word exclusion
+    pub fn word(&self) -> String {
+        strip_html(&self.to_flashcard().front)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportedCollection {
+    pub notes: Vec<ImportedNote>,
+    // Archive entry name to media file name, empty for packages without media
+    pub media: BTreeMap<String, String>,
+}
+
+impl ImportedCollection {
+    pub fn words(&self) -> Vec<String> {
+        self.notes.iter()
+            .map(ImportedNote::word)
+            .filter(|word| !word.is_empty())
+            .collect()
+    }
+
+    pub fn flashcards(&self) -> Vec<Flashcard> {
+        self.notes.iter().map(ImportedNote::to_flashcard).collect()
+    }
+}
+
+// Read every note of an .apkg or .colpkg file
+pub fn read_apkg(path: &Path) -> Result<ImportedCollection, WordcraftError> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)
+        .map_err(|err| WordcraftError::Package(format!("{} is not an Anki package: {}", path.display(), err)))?;
+
+    // Checked first: the anki2 file next to it only holds a note asking to
+    // update Anki
+    if archive.index_for_name(ZSTD_COLLECTION_FILE).is_some() {
+        return Err(WordcraftError::Package(format!(
+            "{} uses the compressed {} format; export it again with \"Support older Anki versions\" enabled",
+            path.display(),
+            ZSTD_COLLECTION_FILE
+        )));
+    }
+    let Some(entry_name) = COLLECTION_FILES.iter().find(|name| archive.index_for_name(name).is_some()) else {
+        return Err(WordcraftError::Package(format!("{} contains no Anki collection", path.display())));
+    };
+
+    let mut collection = Vec::new();
+    archive.by_name(entry_name)?.read_to_end(&mut collection)?;
+
+    let media = match archive.by_name(MEDIA_FILE) {
+        Ok(mut entry) => {
+            let mut manifest = Vec::new();
+            entry.read_to_end(&mut manifest)?;
+            serde_json::from_slice(&manifest).unwrap_or_default()
+        }
+        Err(_) => BTreeMap::new(),
+    };
+
+    let workdir = scratch_dir()?;
+    let collection_path = workdir.path().join(entry_name);
+    std::fs::write(&collection_path, collection)?;
+    let notes = read_collection(&collection_path)?;
+
+    Ok(ImportedCollection { notes, media })
+}
+
+// Read every note of an unpacked collection file
+pub fn read_collection(path: &Path) -> Result<Vec<ImportedNote>, WordcraftError> {
+    let connection = Connection::open(path)?;
+
+    let has_notetypes_table = connection
+        .query_row("SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'notetypes'", [], |row| row.get::<_, String>(0))
+        .optional()?
+        .is_some();
+    let (models, decks) = if has_notetypes_table {
+        (read_notetypes(&connection)?, read_decks(&connection)?)
+    } else {
+        read_legacy_models_and_decks(&connection)?
+    };
+
+    let mut note_decks: HashMap<i64, Vec<String>> = HashMap::new();
+    let mut statement = connection.prepare("SELECT nid, did FROM cards ORDER BY nid, ord")?;
+    let cards = statement.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?;
+    for card in cards {
+        let (note_id, deck_id) = card?;
+        let deck_name = decks.get(&deck_id).cloned().unwrap_or_else(|| deck_id.to_string());
+        let names = note_decks.entry(note_id).or_default();
+        if !names.contains(&deck_name) {
+            names.push(deck_name);
+        }
+    }
+
+    let mut statement = connection.prepare("SELECT id, mid, tags, flds FROM notes ORDER BY id")?;
+    let rows = statement.query_map([], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?))
+    })?;
+
+    let mut notes = Vec::new();
+    for row in rows {
+        let (note_id, model_id, tags, fields) = row?;
+        let (model_name, field_names) = models.get(&model_id)
+            .cloned()
+            .unwrap_or_else(|| (model_id.to_string(), Vec::new()));
+
+        let fields = fields.split('\x1f')
+            .enumerate()
+            .map(|(index, value)| {
+                let name = field_names.get(index).cloned().unwrap_or_else(|| format!("Field {}", index + 1));
+                (name, value.to_string())
+            })
+            .collect();
+
+        notes.push(ImportedNote {
+            note_id,
+            model_name,
+            deck_names: note_decks.remove(&note_id).unwrap_or_default(),
+            fields,
+            tags: tags.split_whitespace().map(str::to_string).collect(),
+        });
+    }
+
+    Ok(notes)
+}
+
+type Models = HashMap<i64, (String, Vec<String>)>;
+type Decks = HashMap<i64, String>;
+
+// Schema 11: note types and decks are JSON objects keyed by id in `col`
+fn read_legacy_models_and_decks(connection: &Connection) -> Result<(Models, Decks), WordcraftError> {
+    let (models, decks): (String, String) =
+        connection.query_row("SELECT models, decks FROM col", [], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    let models: HashMap<String, Value> = serde_json::from_str(&models)?;
+    let models = models.into_iter()
+        .filter_map(|(id, model)| {
+            let id = id.parse::<i64>().ok()?;
+            let name = model["name"].as_str().unwrap_or_default().to_string();
+            let mut fields: Vec<(i64, String)> = model["flds"].as_array()
+                .map(|fields| fields.iter()
+                    .map(|field| (field["ord"].as_i64().unwrap_or_default(), field["name"].as_str().unwrap_or_default().to_string()))
+                    .collect())
+                .unwrap_or_default();
+            fields.sort_by_key(|(ord, _)| *ord);
+            Some((id, (name, fields.into_iter().map(|(_, name)| name).collect())))
+        })
+        .collect();
+
+    let decks: HashMap<String, Value> = serde_json::from_str(&decks)?;
+    let decks = decks.into_iter()
+        .filter_map(|(id, deck)| Some((id.parse::<i64>().ok()?, deck["name"].as_str()?.to_string())))
+        .collect();
+
+    Ok((models, decks))
+}
+
+// Schema 18: note types, their fields and decks have tables of their own
+fn read_notetypes(connection: &Connection) -> Result<Models, WordcraftError> {
+    let mut models: Models = HashMap::new();
+
+    let mut statement = connection.prepare("SELECT id, name FROM notetypes")?;
+    for row in statement.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))? {
+        let (id, name) = row?;
+        models.insert(id, (name, Vec::new()));
+    }
+
+    let mut statement = connection.prepare("SELECT ntid, name FROM fields ORDER BY ntid, ord")?;
+    for row in statement.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))? {
+        let (model_id, name) = row?;
+        if let Some((_, fields)) = models.get_mut(&model_id) {
+            fields.push(name);
+        }
+    }
+
+    Ok(models)
+}
+
+fn read_decks(connection: &Connection) -> Result<Decks, WordcraftError> {
+    let mut statement = connection.prepare("SELECT id, name FROM decks")?;
+    let decks = statement
+        // Nested deck names are stored with \x1f instead of "::"
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?.replace('\x1f', "::"))))?
+        .collect::<Result<_, _>>()?;
+
+    Ok(decks)
+}
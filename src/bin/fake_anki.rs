@@ -0,0 +1,38 @@
+// An in-memory AnkiConnect to try wordcraft without a desktop Anki:
+//
+//   cargo run --bin fake-anki
+//   wordcraft generate --topic Food
+//
+// Everything is lost when it stops.
+use clap::Parser;
+use std::process;
+use tokio::net::TcpListener;
+
+use autoflashcard::fake_anki::FakeAnki;
+
+#[derive(Debug, Parser)]
+#[command(name = "fake-anki", version, about = "In-memory AnkiConnect for development and tests")]
+struct Args {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8765")]
+    listen: String,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+
+    let listener = match TcpListener::bind(&args.listen).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("Could not listen on {}: {}", args.listen, err);
+            process::exit(1);
+        }
+    };
+    println!("Fake AnkiConnect listening on http://{}", args.listen);
+
+    if let Err(err) = FakeAnki::new().serve(listener).await {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
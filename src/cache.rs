@@ -0,0 +1,248 @@
+// On-disk cache of generated decks.
+//
+// An entry is keyed by the engine, the model, the prompt template, the note
+// type, count and level, and the user's request, and is written as soon as a
+// deck is generated, before the cards are reviewed or added. Words from Anki
+// are left out of the key, since they change with every review; the cards the
+// learner has since added are dropped from a cached deck instead. Running the same request again, e.g. after
+// Anki failed, reuses the deck instead of paying for another completion.
+// Entries older than the TTL are ignored and removed by `wordcraft cache prune`.
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::error::WordcraftError;
+use crate::generator::FlashcardGenerator;
+use crate::generation::{
+    build_system_message, generate_flashcards, remove_known_cards, FlashcardResponse, GenerationOptions, GenerationOutcome,
+};
+
+pub const DEFAULT_CACHE_TTL_HOURS: u64 = 24;
+
+// cache.dir / WORDCRAFT_CACHE_DIR moves the cache, cache.ttl_hours /
+// CACHE_TTL_HOURS sets how long an entry is used; 0 turns the cache off
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    pub dir: Option<String>,
+    pub ttl_hours: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig { dir: None, ttl_hours: DEFAULT_CACHE_TTL_HOURS }
+    }
+}
+
+// A generated deck with the replies it was parsed from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedGeneration {
+    // Seconds since the Unix epoch
+    pub created_at: u64,
+    pub engine: String,
+    pub model: String,
+    pub system_prompt_sha1: String,
+    pub user_input: String,
+    // Raw text of every reply, the first request followed by the repairs
+    pub replies: Vec<String>,
+    pub salvaged: bool,
+    pub response: FlashcardResponse,
+}
+
+impl CachedGeneration {
+    pub fn new(
+        generator: &dyn FlashcardGenerator,
+        user_input: &str,
+        options: &GenerationOptions,
+        outcome: &GenerationOutcome,
+    ) -> Self {
+        CachedGeneration {
+            created_at: now(),
+            engine: generator.engine().to_string(),
+            model: generator.model().to_string(),
+            system_prompt_sha1: sha1_hex(&build_system_message(options)),
+            user_input: user_input.to_string(),
+            replies: outcome.attempts.iter().map(|attempt| attempt.reply.clone()).collect(),
+            salvaged: outcome.salvaged,
+            response: outcome.response.clone(),
+        }
+    }
+
+    pub fn age(&self) -> Duration {
+        Duration::from_secs(now().saturating_sub(self.created_at))
+    }
+}
+
+// Key of a generation request: whom it is sent to and the settings that shape
+// the deck, but not the exclusion list or learner profile
+pub fn cache_key(generator: &dyn FlashcardGenerator, user_input: &str, options: &GenerationOptions) -> String {
+    let template = sha1_hex(&options.system_template);
+    let card_count = options.card_count.map(|count| count.to_string()).unwrap_or_default();
+    let level = options.level.map(|level| level.to_string()).unwrap_or_default();
+    sha1_hex(&[
+        generator.engine(),
+        generator.model(),
+        &template,
+        options.note_kind.name(),
+        &card_count,
+        &level,
+        user_input,
+    ].join("\0"))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    pub removed: usize,
+    pub kept: usize,
+}
+
+pub struct ResponseCache {
+    pub dir: PathBuf,
+    pub ttl: Duration,
+}
+
+impl ResponseCache {
+    pub fn new(dir: impl Into<PathBuf>, ttl: Duration) -> Self {
+        ResponseCache { dir: dir.into(), ttl }
+    }
+
+    pub fn from_config(config: &CacheConfig) -> Self {
+        let dir = config.dir.as_ref().map(PathBuf::from).unwrap_or_else(default_cache_dir);
+        ResponseCache::new(dir, Duration::from_secs(config.ttl_hours.saturating_mul(60 * 60)))
+    }
+
+    // The cache for a run, or None when it is turned off. Recording and
+    // replaying always talk to the generator, so they are not cached.
+    pub fn for_run(config: &Config) -> Option<Self> {
+        let fixtures = config.fixtures.record.is_some() || config.fixtures.replay.is_some();
+        if fixtures || config.cache.ttl_hours == 0 {
+            return None;
+        }
+        Some(ResponseCache::from_config(&config.cache))
+    }
+
+    // The deck generated recently for the same request, if there is one
+    pub fn lookup(
+        &self,
+        generator: &dyn FlashcardGenerator,
+        user_input: &str,
+        options: &GenerationOptions,
+    ) -> Option<CachedGeneration> {
+        self.get(&cache_key(generator, user_input, options))
+    }
+
+    // Keep a new deck for the next run with the same request. The deck is
+    // usable without the cache, so an entry that cannot be written only warns.
+    pub fn store(
+        &self,
+        generator: &dyn FlashcardGenerator,
+        user_input: &str,
+        options: &GenerationOptions,
+        outcome: &GenerationOutcome,
+    ) {
+        let entry = CachedGeneration::new(generator, user_input, options, outcome);
+        if let Err(err) = self.put(&cache_key(generator, user_input, options), &entry) {
+            eprintln!("Could not cache the generated cards in {}: {}", self.dir.display(), err);
+        }
+    }
+
+    pub fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    // None when there is no entry, it has expired or it cannot be read
+    pub fn get(&self, key: &str) -> Option<CachedGeneration> {
+        let text = std::fs::read_to_string(self.path(key)).ok()?;
+        let entry: CachedGeneration = serde_json::from_str(&text).ok()?;
+        (!self.is_expired(&entry)).then_some(entry)
+    }
+
+    // Written to a temporary file first, so an interrupted run never leaves
+    // half an entry behind
+    pub fn put(&self, key: &str, entry: &CachedGeneration) -> Result<PathBuf, WordcraftError> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.path(key);
+        let partial = path.with_extension("json.partial");
+        std::fs::write(&partial, serde_json::to_string_pretty(entry)?)?;
+        std::fs::rename(&partial, &path)?;
+        Ok(path)
+    }
+
+    pub fn is_expired(&self, entry: &CachedGeneration) -> bool {
+        entry.age() >= self.ttl
+    }
+
+    // Remove expired and unreadable entries, or every entry with `all`.
+    // A missing cache directory is an empty cache.
+    pub fn prune(&self, all: bool) -> Result<PruneReport, WordcraftError> {
+        let mut report = PruneReport::default();
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(report),
+            Err(err) => return Err(err.into()),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            if !is_cache_file(&path) {
+                continue;
+            }
+            let keep = !all && std::fs::read_to_string(&path).ok()
+                .and_then(|text| serde_json::from_str::<CachedGeneration>(&text).ok())
+                .is_some_and(|entry| !self.is_expired(&entry));
+            if keep {
+                report.kept += 1;
+            } else {
+                std::fs::remove_file(&path)?;
+                report.removed += 1;
+            }
+        }
+        Ok(report)
+    }
+}
+
+// `generate_flashcards` through `cache`: a recent deck for the same request is
+// reused without the cards now known, and a new one is stored before it is
+// returned
+pub async fn generate_flashcards_cached(
+    generator: &dyn FlashcardGenerator,
+    cache: Option<&ResponseCache>,
+    user_input: &str,
+    options: &GenerationOptions,
+) -> Result<FlashcardResponse, WordcraftError> {
+    if let Some(entry) = cache.and_then(|cache| cache.lookup(generator, user_input, options)) {
+        let mut response = entry.response;
+        remove_known_cards(&mut response, &options.exclude);
+        return Ok(response);
+    }
+    let outcome = generate_flashcards(generator, user_input, options).await?;
+    if let Some(cache) = cache {
+        cache.store(generator, user_input, options, &outcome);
+    }
+    Ok(outcome.response)
+}
+
+// Entries and the temporary files of interrupted writes
+fn is_cache_file(path: &Path) -> bool {
+    let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    name.ends_with(".json") || name.ends_with(".json.partial")
+}
+
+// $XDG_CACHE_HOME/wordcraft/responses or ~/.cache/wordcraft/responses
+pub fn default_cache_dir() -> PathBuf {
+    let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir);
+
+    cache_dir.join("wordcraft").join("responses")
+}
+
+fn sha1_hex(text: &str) -> String {
+    Sha1::digest(text.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default()
+}
@@ -0,0 +1,805 @@
+// Command line interface of the wordcraft binary.
+//
+// Every setting can be passed as a flag. The interactive prompts are only a
+// fallback for a bare `wordcraft generate` in a terminal, so runs can be
+// scripted and piped.
+use clap::{Args, Parser, Subcommand};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::time::{timeout, Duration};
+
+use crate::anki_adapter::{deck_query, insert_cards, AnkiAdapter};
+use crate::apkg::{apkg_file_name, export_apkg_as};
+use crate::apkg_reader::read_apkg;
+use crate::cache::ResponseCache;
+use crate::config::{Config, ConfigLayer, ConfigSources};
+use crate::error::WordcraftError;
+use crate::generator::{build_generator, FlashcardGenerator};
+use crate::jobs::{state_path, JobFile, JobRunner, DEFAULT_JOB_CONCURRENCY};
+use crate::level::ProficiencyLevel;
+use crate::generation::{
+    generate_flashcards_streaming, generation_prompt, remove_known_cards, Flashcard, FlashcardResponse, GenerationOptions,
+};
+use crate::note_type::{model_version, NoteKind, NOTE_KINDS, WORDCRAFT_MODEL_VERSION};
+use crate::prompt::{ask_for_confirmation, FlashcardSettings, SettingsOverrides};
+use crate::prompt_pack::PromptLibrary;
+use crate::rag::{build_index, default_index_path, SelectionStrategy, VocabularyIndex, WORDCRAFT_NOTES_QUERY};
+use crate::review::{review_cards, GeneratorSource};
+use crate::tts::{synthesize_audio, CommandTts, TtsEngine};
+
+#[derive(Debug, Parser)]
+#[command(name = "wordcraft", version, about = "Generate Anki flashcards with an LLM")]
+pub struct Cli {
+    /// Config file [default: ~/.config/wordcraft/config.toml]
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// Profile of the config file to use [default: $WORDCRAFT_PROFILE]
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+    /// Save every LLM request and reply as a fixture in this directory [default: $LLM_RECORD_DIR]
+    #[arg(long, global = true, value_name = "DIR", conflicts_with = "replay")]
+    pub record: Option<String>,
+    /// Answer LLM requests from the fixtures in this directory instead of a model [default: $LLM_REPLAY_DIR]
+    #[arg(long, global = true, value_name = "DIR")]
+    pub replay: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Generate cards and add them to Anki, or save an .apkg when Anki is not running
+    Generate(GenerateArgs),
+    /// Add cards from a JSON file written by `generate --save`
+    Add(AddArgs),
+    /// Save cards as an .apkg file without touching Anki
+    Export(ExportArgs),
+    /// Generate and add every deck listed in a job file
+    Run(RunArgs),
+    /// List the decks in Anki
+    Decks,
+    /// Check the LLM engine, AnkiConnect, the Wordcraft note types and TTS
+    Doctor,
+    /// Manage the cache of generated decks
+    Cache(CacheArgs),
+}
+
+// What to generate and with which model
+#[derive(Debug, Clone, Default, Args)]
+pub struct GenerationArgs {
+    /// Your native language [default: English]
+    #[arg(long)]
+    pub native: Option<String>,
+    /// The language to learn [default: Japanese]
+    #[arg(long)]
+    pub target: Option<String>,
+    /// What the cards should be about
+    #[arg(long)]
+    pub topic: Option<String>,
+    /// Existing deck to add to; its words are skipped
+    #[arg(long)]
+    pub deck: Option<String>,
+    /// Exact number of cards; a short deck is topped up [default: at least 15]
+    #[arg(long)]
+    pub count: Option<usize>,
+    /// Level to aim at: CEFR A1-C2, JLPT N5-N1, HSK 1-9 or TOPIK 1-6
+    #[arg(long)]
+    pub level: Option<ProficiencyLevel>,
+    /// LLM engine: openai, openai-compatible, ollama or fake [default: from config or $ENGINE]
+    #[arg(long)]
+    pub engine: Option<String>,
+    /// Model of the engine [default: from config, $OPENAI_MODEL or $OLLAMA_MODEL]
+    #[arg(long)]
+    pub model: Option<String>,
+    /// basic, bidirectional, type-answer or cloze [default: basic]
+    #[arg(long = "note-type")]
+    pub note_type: Option<NoteKind>,
+    /// Ask the model again even when the same request was answered recently
+    #[arg(long)]
+    pub no_cache: bool,
+}
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct GenerateArgs {
+    #[command(flatten)]
+    pub generation: GenerationArgs,
+    /// Add the cards without asking for confirmation
+    #[arg(long, short = 'y')]
+    pub yes: bool,
+    /// Only show the generated cards; nothing is added to Anki or exported
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Also write the generated deck as JSON, for a later `wordcraft add`
+    #[arg(long, value_name = "FILE")]
+    pub save: Option<PathBuf>,
+    /// Ask a single yes/no question instead of reviewing the cards one by one
+    #[arg(long)]
+    pub no_review: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct AddArgs {
+    /// JSON file with a deck_name and cards, as written by `generate --save`
+    pub file: PathBuf,
+    /// Deck to add to instead of the file's deck_name
+    #[arg(long)]
+    pub deck: Option<String>,
+    /// basic, bidirectional, type-answer or cloze [default: basic]
+    #[arg(long = "note-type")]
+    pub note_type: Option<NoteKind>,
+    /// The language of the cards, needed for audio when TTS_COMMAND is set
+    #[arg(long)]
+    pub target: Option<String>,
+    /// Add the cards without asking for confirmation
+    #[arg(long, short = 'y')]
+    pub yes: bool,
+    /// Only show the cards that would be added
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub generation: GenerationArgs,
+    /// Export the cards of a JSON file instead of generating new ones
+    #[arg(long, value_name = "FILE")]
+    pub input: Option<PathBuf>,
+    /// Where to write the package [default: <deck name>.apkg]
+    #[arg(long, short = 'o', value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct RunArgs {
+    /// TOML or JSON job file
+    pub file: PathBuf,
+    /// How many decks to generate at once [default: the file's concurrency, or 2]
+    #[arg(long)]
+    pub concurrency: Option<usize>,
+    /// Forget the progress of earlier runs and do every job again
+    #[arg(long)]
+    pub restart: bool,
+    /// Also write the summary as JSON
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<PathBuf>,
+    /// Ask the model again even when the same job was answered recently
+    #[arg(long)]
+    pub no_cache: bool,
+    /// LLM engine for every job [default: from config or $ENGINE]
+    #[arg(long)]
+    pub engine: Option<String>,
+    /// Model of the engine
+    #[arg(long)]
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub command: CacheCommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum CacheCommand {
+    /// Remove expired and unreadable entries
+    Prune {
+        /// Remove every entry, expired or not
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+// Run a command. A bare `wordcraft` is `wordcraft generate`.
+// Returns false when the command ran but reported a problem (doctor).
+pub async fn run(cli: Cli) -> Result<bool, WordcraftError> {
+    let command = cli.command.unwrap_or_else(|| Command::Generate(GenerateArgs::default()));
+    let mut overrides = command_overrides(&command);
+    overrides.fixtures.record = cli.record;
+    overrides.fixtures.replay = cli.replay;
+    let sources = ConfigSources {
+        path: cli.config,
+        profile: cli.profile,
+        overrides,
+    };
+
+    // The doctor reports a broken configuration instead of stopping at it
+    let config = match (Config::load(sources), &command) {
+        (Ok(config), _) => config,
+        (Err(err), Command::Doctor) => {
+            report(Check::Fail, &format!("Configuration: {}", err));
+            return Ok(false);
+        }
+        (Err(err), _) => return Err(err.context("Invalid configuration")),
+    };
+
+    match command {
+        Command::Generate(args) => run_generate(&config, args).await.map(|_| true),
+        Command::Add(args) => run_add(&config, args).await.map(|_| true),
+        Command::Export(args) => run_export(&config, args).await.map(|_| true),
+        Command::Run(args) => run_jobs(&config, args).await,
+        Command::Decks => run_decks(&config).await.map(|_| true),
+        Command::Doctor => run_doctor(&config).await,
+        Command::Cache(args) => run_cache(&config, args).map(|_| true),
+    }
+}
+
+// The --engine and --model flags of a command, as the top config layer.
+// --model applies to whichever engine ends up selected.
+pub fn command_overrides(command: &Command) -> ConfigLayer {
+    let (engine, model) = match command {
+        Command::Generate(args) => (&args.generation.engine, &args.generation.model),
+        Command::Export(args) => (&args.generation.engine, &args.generation.model),
+        Command::Run(args) => (&args.engine, &args.model),
+        Command::Add(_) | Command::Decks | Command::Doctor | Command::Cache(_) => (&None, &None),
+    };
+
+    let mut layer = ConfigLayer { engine: engine.clone(), ..Default::default() };
+    layer.openai.model = model.clone();
+    layer.ollama.model = model.clone();
+    layer
+}
+
+pub async fn run_generate(config: &Config, args: GenerateArgs) -> Result<(), WordcraftError> {
+    let settings = resolve_settings(&args.generation)?;
+
+    let adapter = AnkiAdapter::from_config(config);
+    let online = connect(&adapter).await;
+    if !online && !args.dry_run {
+        eprintln!("Anki is not reachable. The cards will be saved to an .apkg file you can import later.");
+    }
+
+    // Ensure the Wordcraft model for the chosen note type exists
+    if online && !args.dry_run {
+        adapter.ensure_note_type_exists(settings.note_kind).await?;
+    }
+
+    let generator = build_generator(config)?;
+    let options = generation_options(config, &settings, &adapter, online).await?;
+    let cache = response_cache(config, &args.generation);
+    let mut response = generate(generator.as_ref(), cache.as_ref(), &settings, &options).await?;
+    if response.cards.is_empty() {
+        println!("No flashcards to add.");
+        return Ok(());
+    }
+
+    if let Some(path) = &args.save {
+        std::fs::write(path, serde_json::to_string_pretty(&response)?)?;
+        println!("Saved {} cards to {}.", response.cards.len(), path.display());
+    }
+    if args.dry_run {
+        println!("Dry run: {} cards were not added.", response.cards.len());
+        return Ok(());
+    }
+
+    // Only the cards accepted in the review are added
+    let review = !args.yes && !args.no_review && std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+    if review {
+        let source = GeneratorSource {
+            generator: generator.as_ref(),
+            prompt: generation_prompt(&FlashcardSettings { card_count: None, ..settings.clone() }),
+            count: settings.card_count,
+            options,
+        };
+        match review_cards(response.cards, settings.note_kind, &source).await? {
+            Some(cards) if !cards.is_empty() => response.cards = cards,
+            Some(_) => {
+                println!("No cards were accepted.");
+                return Ok(());
+            }
+            None => {
+                println!("Exiting without adding flashcards.");
+                return Ok(());
+            }
+        }
+    }
+
+    if !online {
+        if let Some(deck_name) = &settings.deck_name {
+            response.deck_name = deck_name.clone();
+        }
+        let path = PathBuf::from(apkg_file_name(&response.deck_name));
+        return save_apkg(config, response, settings.note_kind, &settings.target_language, &path).await;
+    }
+
+    add_to_anki(config, &adapter, response, settings.deck_name.as_deref(), settings.note_kind, &settings.target_language, args.yes || review).await
+}
+
+pub async fn run_add(config: &Config, args: AddArgs) -> Result<(), WordcraftError> {
+    let response = read_deck_file(&args.file)?;
+    let note_kind = args.note_type.unwrap_or_default();
+    let target_language = deck_file_language(config, args.target)?;
+
+    response.cards.iter().for_each(print_card);
+    if args.dry_run {
+        let deck_name = args.deck.as_deref().unwrap_or(&response.deck_name);
+        println!("Dry run: {} cards would be added to deck '{}'.", response.cards.len(), deck_name);
+        return Ok(());
+    }
+
+    let adapter = AnkiAdapter::from_config(config);
+    if !connect(&adapter).await {
+        return Err(WordcraftError::AnkiUnavailable {
+            message: format!("Anki is not reachable at {}. Use `wordcraft export --input {}` instead.", adapter.url, args.file.display()),
+            url: adapter.url,
+        });
+    }
+    adapter.ensure_note_type_exists(note_kind).await?;
+
+    add_to_anki(config, &adapter, response, args.deck.as_deref(), note_kind, &target_language, args.yes).await
+}
+
+pub async fn run_export(config: &Config, args: ExportArgs) -> Result<(), WordcraftError> {
+    let (mut response, note_kind, target_language) = match &args.input {
+        Some(path) => (
+            read_deck_file(path)?,
+            args.generation.note_type.unwrap_or_default(),
+            deck_file_language(config, args.generation.target.clone())?,
+        ),
+        None => {
+            let settings = resolve_settings(&args.generation)?;
+            // Anki is only read from, to skip the words the learner already has
+            let adapter = AnkiAdapter::from_config(config);
+            let online = connect(&adapter).await;
+            let generator = build_generator(config)?;
+            let options = generation_options(config, &settings, &adapter, online).await?;
+            let cache = response_cache(config, &args.generation);
+            let response = generate(generator.as_ref(), cache.as_ref(), &settings, &options).await?;
+            (response, settings.note_kind, settings.target_language)
+        }
+    };
+
+    if response.cards.is_empty() {
+        println!("No flashcards to export.");
+        return Ok(());
+    }
+    if let Some(deck_name) = &args.generation.deck {
+        response.deck_name = deck_name.clone();
+    }
+
+    let path = args.output.clone().unwrap_or_else(|| PathBuf::from(apkg_file_name(&response.deck_name)));
+    save_apkg(config, response, note_kind, &target_language, &path).await
+}
+
+// A deck file does not say which language its cards are in, and only audio needs it
+fn deck_file_language(config: &Config, target: Option<String>) -> Result<String, WordcraftError> {
+    match target {
+        Some(target) => Ok(target),
+        None if CommandTts::from_config(&config.tts)?.is_some() => Err(WordcraftError::Config(
+            "TTS_COMMAND is set, so pass --target with the language of the cards for their audio.".to_string(),
+        )),
+        None => Ok(String::new()),
+    }
+}
+
+// Returns false when a job failed; rerunning picks up from there
+pub async fn run_jobs(config: &Config, args: RunArgs) -> Result<bool, WordcraftError> {
+    let job_file = JobFile::load(&args.file)?;
+    let jobs = job_file.jobs()?;
+    let state_path = state_path(&args.file);
+    if args.restart && state_path.exists() {
+        std::fs::remove_file(&state_path)?;
+    }
+
+    let generator = build_generator(config)?;
+
+    let adapter = AnkiAdapter::from_config(config);
+    if !connect(&adapter).await {
+        return Err(WordcraftError::AnkiUnavailable {
+            message: format!("Anki is not reachable at {}. Job files add their decks to Anki.", adapter.url),
+            url: adapter.url,
+        });
+    }
+
+    let mut exclude = Vec::new();
+    for path in &config.known_words_apkg {
+        match read_apkg(Path::new(path)) {
+            Ok(collection) => exclude.extend(collection.words()),
+            Err(err) => eprintln!("Could not read {}: {}", path, err),
+        }
+    }
+
+    let runner = JobRunner {
+        generator: Arc::from(generator),
+        prompts: Arc::new(PromptLibrary::from_config(&config.prompts)?),
+        adapter: Arc::new(adapter),
+        concurrency: args.concurrency.or(job_file.concurrency).unwrap_or(DEFAULT_JOB_CONCURRENCY),
+        exclude,
+        tts: CommandTts::from_config(&config.tts)?.map(|tts| Arc::new(tts) as Arc<dyn TtsEngine>),
+        cache: ResponseCache::for_run(config).filter(|_| !args.no_cache).map(Arc::new),
+    };
+    println!("Running {} jobs from {}, {} at a time.", jobs.len(), args.file.display(), runner.concurrency);
+    let summary = runner.run(jobs, &state_path).await?;
+
+    summary.print();
+    if let Some(path) = &args.report {
+        std::fs::write(path, serde_json::to_string_pretty(&summary)?)?;
+    }
+
+    Ok(summary.failed() == 0)
+}
+
+pub async fn run_decks(config: &Config) -> Result<(), WordcraftError> {
+    let adapter = AnkiAdapter::from_config(config);
+    let mut names = adapter.deck_names().await
+        .map_err(|err| err.context(format!("Could not list decks from {}", adapter.url)))?;
+    names.sort();
+
+    let stats = adapter.get_deck_stats(&names.iter().map(String::as_str).collect::<Vec<_>>()).await.unwrap_or_default();
+    for name in &names {
+        match stats.values().find(|deck| &deck.name == name) {
+            Some(deck) => println!("{} ({} cards)", name, deck.total_in_deck),
+            None => println!("{}", name),
+        }
+    }
+
+    Ok(())
+}
+
+pub fn run_cache(config: &Config, args: CacheArgs) -> Result<(), WordcraftError> {
+    let cache = ResponseCache::from_config(&config.cache);
+    match args.command {
+        CacheCommand::Prune { all } => {
+            let report = cache.prune(all)?;
+            println!("Removed {} cached decks from {}, kept {}.", report.removed, cache.dir.display(), report.kept);
+        }
+    }
+    Ok(())
+}
+
+// Check everything a run depends on. Returns false when something is broken.
+pub async fn run_doctor(config: &Config) -> Result<bool, WordcraftError> {
+    let mut healthy = true;
+
+    match &config.profile {
+        Some(profile) => report(Check::Ok, &format!("Configuration: profile {}", profile)),
+        None => report(Check::Ok, "Configuration: no profile"),
+    }
+
+    match build_generator(config) {
+        Ok(generator) => report(Check::Ok, &format!("LLM engine: {} ({})", generator.engine(), generator.model())),
+        Err(err) => {
+            healthy = false;
+            report(Check::Fail, &format!("LLM engine: {}", err));
+        }
+    }
+
+    let adapter = AnkiAdapter::from_config(config);
+    match timeout(Duration::from_secs(2), adapter.version()).await {
+        Ok(Ok(version)) => {
+            report(Check::Ok, &format!("AnkiConnect at {} (API version {})", adapter.url, version));
+            check_note_types(&adapter).await;
+        }
+        Ok(Err(err)) => {
+            healthy = false;
+            report(Check::Fail, &format!("AnkiConnect at {}: {}", adapter.url, err));
+        }
+        Err(_) => {
+            healthy = false;
+            report(Check::Fail, &format!("AnkiConnect at {}: timed out", adapter.url));
+        }
+    }
+
+    match CommandTts::from_config(&config.tts) {
+        Ok(None) => report(Check::Warn, "Text-to-speech: no TTS command is configured, cards get no audio"),
+        Ok(Some(tts)) => match tts.synthesize("test", "en").await {
+            Ok(_) => report(Check::Ok, &format!("Text-to-speech: {}", tts.name())),
+            Err(err) => {
+                healthy = false;
+                report(Check::Fail, &format!("Text-to-speech: {}", err));
+            }
+        },
+        Err(err) => {
+            healthy = false;
+            report(Check::Fail, &format!("Text-to-speech: {}", err));
+        }
+    }
+
+    for path in &config.known_words_apkg {
+        match read_apkg(Path::new(path)) {
+            Ok(collection) => report(Check::Ok, &format!("Known words: {} ({} words)", path, collection.words().len())),
+            Err(err) => {
+                healthy = false;
+                report(Check::Fail, &format!("Known words: {}", err));
+            }
+        }
+    }
+
+    match PromptLibrary::from_config(&config.prompts) {
+        Ok(library) if library.overrides.is_empty() => report(Check::Ok, "Prompt templates: built in"),
+        Ok(library) => {
+            let files: Vec<String> = library.overrides.iter().map(|path| path.display().to_string()).collect();
+            report(Check::Ok, &format!("Prompt templates: built in, overridden by {}", files.join(", ")));
+        }
+        Err(err) => {
+            healthy = false;
+            report(Check::Fail, &format!("Prompt templates: {}", err));
+        }
+    }
+
+    Ok(healthy)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Check {
+    Ok,
+    Warn,
+    Fail,
+}
+
+fn report(check: Check, message: &str) {
+    match check {
+        Check::Ok => println!("[ok]   {}", message),
+        Check::Warn => println!("[warn] {}", message),
+        Check::Fail => println!("[fail] {}", message),
+    }
+}
+
+// Missing or outdated models are only a warning: the next run fixes them
+async fn check_note_types(adapter: &AnkiAdapter) {
+    let model_names = adapter.model_names().await.unwrap_or_default();
+
+    for kind in NOTE_KINDS {
+        let model_name = kind.note_type().model_name;
+        if !model_names.iter().any(|name| name == model_name) {
+            report(Check::Warn, &format!("Note type {}: missing, it is created on first use", model_name));
+            continue;
+        }
+        match adapter.model_styling(model_name).await {
+            Ok(styling) if model_version(&styling.css) >= WORDCRAFT_MODEL_VERSION => {
+                report(Check::Ok, &format!("Note type {}: version {}", model_name, WORDCRAFT_MODEL_VERSION));
+            }
+            Ok(styling) => report(Check::Warn, &format!(
+                "Note type {}: version {}, it is updated to {} on next use",
+                model_name,
+                model_version(&styling.css),
+                WORDCRAFT_MODEL_VERSION
+            )),
+            Err(err) => report(Check::Warn, &format!("Note type {}: {}", model_name, err)),
+        }
+    }
+}
+
+// The settings from the flags, asking for the rest when attached to a terminal
+pub fn resolve_settings(args: &GenerationArgs) -> Result<FlashcardSettings, WordcraftError> {
+    let given = SettingsOverrides {
+        native_language: args.native.clone(),
+        target_language: args.target.clone(),
+        topic: args.topic.clone(),
+        deck_name: args.deck.clone(),
+        note_kind: args.note_type,
+        card_count: args.count,
+        level: args.level,
+    };
+    FlashcardSettings::resolve(given, std::io::stdin().is_terminal()).map_err(WordcraftError::Config)
+}
+
+// Check if Anki Connect is available with a timeout
+async fn connect(adapter: &AnkiAdapter) -> bool {
+    println!("Checking if Anki Connect is available...");
+    match timeout(Duration::from_secs(2), adapter.check_connection()).await {
+        Ok(Ok(_)) => {
+            println!("Successfully connected to Anki.");
+            true
+        }
+        Ok(Err(err)) => {
+            eprintln!("Error connecting to Anki: {}", err);
+            false
+        }
+        Err(_) => {
+            eprintln!("Timeout while connecting to Anki.");
+            false
+        }
+    }
+}
+
+// The words to skip and the learner's profile, from Anki, the known-words
+// packages and the stored vocabulary index
+async fn generation_options(
+    config: &Config,
+    settings: &FlashcardSettings,
+    adapter: &AnkiAdapter,
+    online: bool,
+) -> Result<GenerationOptions, WordcraftError> {
+    let mut options = GenerationOptions {
+        note_kind: settings.note_kind,
+        card_count: settings.card_count,
+        level: settings.level,
+        system_template: PromptLibrary::from_config(&config.prompts)?.template(&settings.target_language),
+        ..GenerationOptions::default()
+    };
+    // A replay answers from the recorded replies, whatever this machine's
+    // Anki and vocabulary index hold
+    if config.fixtures.replay.is_some() {
+        return Ok(options);
+    }
+    if let (true, Some(deck_name)) = (online, &settings.deck_name) {
+        options.exclude = adapter.fetch_deck_fronts(deck_name).await?;
+        println!("Found {} existing words in deck '{}'. They will be skipped.", options.exclude.len(), deck_name);
+    }
+
+    // Community decks the learner studies outside of AnkiConnect
+    for path in &config.known_words_apkg {
+        match read_apkg(Path::new(path)) {
+            Ok(collection) => {
+                let words = collection.words();
+                println!("Found {} known words in {}.", words.len(), path);
+                options.exclude.extend(words);
+            }
+            Err(err) => eprintln!("Could not read {}: {}", path, err),
+        }
+    }
+
+    // Refresh the learner's vocabulary index, falling back to the last stored one
+    let index_query = match &settings.deck_name {
+        Some(deck_name) => deck_query(deck_name),
+        None => WORDCRAFT_NOTES_QUERY.to_string(),
+    };
+    let index_path = default_index_path(&index_query);
+    let built = if online { Some(build_index(adapter, &index_query).await) } else { None };
+    let index = match built {
+        Some(Ok(index)) => {
+            if let Err(err) = index.save(&index_path) {
+                eprintln!("Could not save vocabulary index to {}: {}", index_path.display(), err);
+            }
+            index
+        }
+        Some(Err(err)) => {
+            eprintln!("Could not read review history from Anki: {}", err);
+            VocabularyIndex::load_for_query(&index_path, &index_query).unwrap_or_default()
+        }
+        None => VocabularyIndex::load_for_query(&index_path, &index_query).unwrap_or_default(),
+    };
+    let strategy = SelectionStrategy::default();
+    options.learner_profile = strategy.summarize(&index);
+    options.learner_profile_words = strategy.select(&index).words();
+    options.exclude.extend(index.words());
+    Ok(options)
+}
+
+// The cache for a generate or export run
+fn response_cache(config: &Config, args: &GenerationArgs) -> Option<ResponseCache> {
+    ResponseCache::for_run(config).filter(|_| !args.no_cache)
+}
+
+// Generate a deck, skipping the words the learner already has. Cards are
+// printed as they arrive; Ctrl-C drops the request but keeps them. A deck
+// generated recently for the same request is taken from the cache, and a new
+// one is cached before it is reviewed or added.
+async fn generate(
+    generator: &dyn FlashcardGenerator,
+    cache: Option<&ResponseCache>,
+    settings: &FlashcardSettings,
+    options: &GenerationOptions,
+) -> Result<FlashcardResponse, WordcraftError> {
+    let complete_prompt = generation_prompt(settings);
+
+    if let Some(entry) = cache.and_then(|cache| cache.lookup(generator, &complete_prompt, options)) {
+        println!(
+            "Using the cards generated {} minutes ago for this request (--no-cache to ask the model again).\n",
+            entry.age().as_secs() / 60
+        );
+        let mut response = entry.response;
+        remove_known_cards(&mut response, &options.exclude);
+        response.cards.iter().for_each(print_card);
+        return Ok(response);
+    }
+
+    println!("Generating flashcards for:\n{}", &complete_prompt);
+
+    println!("Press Ctrl-C to stop early and keep the cards received so far.\n");
+
+    let mut previewed: Vec<Flashcard> = Vec::new();
+    let outcome = {
+        let generation = generate_flashcards_streaming(generator, &complete_prompt, options, |card| {
+            print_card(card);
+            previewed.push(card.clone());
+        });
+        tokio::select! {
+            outcome = generation => Some(outcome?),
+            _ = tokio::signal::ctrl_c() => None,
+        }
+    };
+
+    Ok(match outcome {
+        Some(outcome) => {
+            for (number, attempt) in outcome.attempts.iter().enumerate() {
+                if let Some(error) = &attempt.error {
+                    eprintln!("Reply {} could not be parsed: {}", number + 1, error);
+                }
+            }
+            if outcome.salvaged {
+                eprintln!("Recovered {} cards from the model's malformed replies.", outcome.response.cards.len());
+            }
+            if let Some(card_count) = settings.card_count.filter(|count| outcome.response.cards.len() < *count) {
+                eprintln!("The model made {} of the {} cards asked for.", outcome.response.cards.len(), card_count);
+            }
+            // The preview came from a reply that was replaced, topped up, cut or only partly usable
+            if outcome.attempts.len() > 1 || outcome.salvaged || outcome.response.cards.len() != previewed.len() {
+                println!("Final cards:\n");
+                outcome.response.cards.iter().for_each(print_card);
+            }
+            if let Some(cache) = cache {
+                cache.store(generator, &complete_prompt, options, &outcome);
+            }
+            outcome.response
+        }
+        None => {
+            println!("\nGeneration cancelled, keeping {} cards.", previewed.len());
+            FlashcardResponse {
+                deck_name: format!("{} in {}", settings.topic, settings.target_language),
+                cards: previewed,
+            }
+        }
+    })
+}
+
+// Confirm, create the deck when none was given, upload audio and add the cards
+async fn add_to_anki(
+    config: &Config,
+    adapter: &AnkiAdapter,
+    mut response: FlashcardResponse,
+    deck_name: Option<&str>,
+    note_kind: NoteKind,
+    target_language: &str,
+    yes: bool,
+) -> Result<(), WordcraftError> {
+    if yes || ask_for_confirmation("Would you like to add these flashcards? (y/n)")? {
+        println!("Adding flashcards to Anki...");
+    } else {
+        println!("Exiting without adding flashcards.");
+        return Ok(());
+    }
+
+    let deck_name = match deck_name {
+        Some(name) => {
+            println!("Adding to existing deck: {}", name);
+            name.to_string()
+        }
+        None => {
+            println!("No existing deck provided. Creating new deck.");
+            adapter.create_deck(&response.deck_name).await?;
+            response.deck_name.clone()
+        }
+    };
+
+    let tts = CommandTts::from_config(&config.tts)?;
+    println!("Inserting cards into deck: {}", deck_name);
+    let report = insert_cards(adapter, &mut response.cards, &deck_name, note_kind, &[], target_language, tts.as_ref().map(|tts| tts as &dyn TtsEngine)).await?;
+
+    println!("Added {} of {} cards.", report.added().len(), report.entries.len());
+    for front in report.duplicates() {
+        println!("Skipped duplicate: '{}'", front);
+    }
+    for (front, reason) in report.failures() {
+        eprintln!("Failed to add '{}': {}", front, reason);
+    }
+
+    Ok(())
+}
+
+async fn save_apkg(
+    config: &Config,
+    mut response: FlashcardResponse,
+    note_kind: NoteKind,
+    target_language: &str,
+    path: &Path,
+) -> Result<(), WordcraftError> {
+    let tts = CommandTts::from_config(&config.tts)?;
+    let media = synthesize_audio(tts.as_ref().map(|tts| tts as &dyn TtsEngine), &mut response.cards, target_language).await;
+    export_apkg_as(&response, note_kind, &media, path)?;
+    println!("Saved {} cards to {}. Import it in Anki with File > Import.", response.cards.len(), path.display());
+    Ok(())
+}
+
+fn read_deck_file(path: &Path) -> Result<FlashcardResponse, WordcraftError> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|err| WordcraftError::Config(format!("Could not read {}: {}", path.display(), err)))?;
+    serde_json::from_str(&text)
+        .map_err(|err| WordcraftError::Config(format!("{} is not a Wordcraft deck: {}", path.display(), err)))
+}
+
+pub fn print_card(card: &Flashcard) {
+    println!("Front: {}\nBack: {}\nExample: {}\nExample Translation: {}\n", card.front, card.back, card.example, card.example_translate);
+}
@@ -0,0 +1,423 @@
+// Settings for a run, collected in one place.
+//
+// Values are layered, each layer overriding the one before:
+//
+//   built-in defaults
+//   ~/.config/wordcraft/config.toml
+//   environment variables (and .env)
+//   the selected profile of the config file
+//   command line flags
+//
+// A profile is picked on purpose, so it wins over the environment. Modules get
+// a `Config` (or the part of it they need) instead of reading the environment.
+//
+//   engine = "ollama"
+//   profile = "work-ollama"
+//
+//   [ollama]
+//   model = "gemma2"
+//
+//   [profiles.work-ollama.ollama]
+//   base_url = "http://gpu-box:11434"
+//
+//   [profiles.home-openai]
+//   engine = "openai"
+//   openai = { model = "gpt-4o-mini", api_key = "sk-..." }
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::cache::CacheConfig;
+use crate::error::WordcraftError;
+use crate::generator::{EngineConfig, DEFAULT_OLLAMA_MODEL, DEFAULT_OPENAI_MODEL};
+use crate::ollama::{OllamaConfig, DEFAULT_OLLAMA_BASE_URL};
+use crate::openai::{parse_headers, OpenAIConfig, DEFAULT_OPENAI_BASE_URL};
+use crate::prompt_pack::PromptConfig;
+use crate::recording::FixturesConfig;
+use crate::tts::{TtsConfig, DEFAULT_TTS_EXTENSION};
+
+pub const DEFAULT_ANKI_CONNECT_URL: &str = "http://localhost:8765";
+pub const DEFAULT_ENGINE: &str = "openai";
+
+// Resolved settings, ready to use
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    // Profile the settings came from, if any
+    pub profile: Option<String>,
+    pub anki_connect_url: String,
+    // openai, openai-compatible, ollama or fake
+    pub engine: String,
+    pub openai: OpenAIConfig,
+    pub ollama: OllamaConfig,
+    pub tts: TtsConfig,
+    // .apkg/.colpkg files whose words should not be generated again
+    pub known_words_apkg: Vec<String>,
+    pub fixtures: FixturesConfig,
+    pub cache: CacheConfig,
+    pub prompts: PromptConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            profile: None,
+            anki_connect_url: DEFAULT_ANKI_CONNECT_URL.to_string(),
+            engine: DEFAULT_ENGINE.to_string(),
+            openai: OpenAIConfig {
+                base_url: DEFAULT_OPENAI_BASE_URL.to_string(),
+                model: DEFAULT_OPENAI_MODEL.to_string(),
+                api_key: None,
+                headers: Vec::new(),
+                structured_output: true,
+            },
+            ollama: OllamaConfig {
+                base_url: DEFAULT_OLLAMA_BASE_URL.to_string(),
+                model: DEFAULT_OLLAMA_MODEL.to_string(),
+            },
+            tts: TtsConfig {
+                command: None,
+                extension: DEFAULT_TTS_EXTENSION.to_string(),
+            },
+            known_words_apkg: Vec::new(),
+            fixtures: FixturesConfig::default(),
+            cache: CacheConfig::default(),
+            prompts: PromptConfig::default(),
+        }
+    }
+}
+
+// One layer of settings; unset values fall through to the layer below
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigLayer {
+    pub anki_connect_url: Option<String>,
+    pub engine: Option<String>,
+    pub known_words_apkg: Option<Vec<String>>,
+    #[serde(default)]
+    pub openai: OpenAILayer,
+    #[serde(default)]
+    pub ollama: OllamaLayer,
+    #[serde(default)]
+    pub tts: TtsLayer,
+    #[serde(default)]
+    pub fixtures: FixturesLayer,
+    #[serde(default)]
+    pub cache: CacheLayer,
+    #[serde(default)]
+    pub prompts: PromptsLayer,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OpenAILayer {
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+    pub api_key: Option<String>,
+    pub headers: Option<BTreeMap<String, String>>,
+    pub structured_output: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OllamaLayer {
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TtsLayer {
+    pub command: Option<String>,
+    pub extension: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FixturesLayer {
+    pub record: Option<String>,
+    pub replay: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheLayer {
+    pub dir: Option<String>,
+    pub ttl_hours: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PromptsLayer {
+    pub dir: Option<String>,
+}
+
+impl ConfigLayer {
+    // The variables from .env.example
+    pub fn from_env() -> Result<ConfigLayer, WordcraftError> {
+        ConfigLayer::from_lookup(|name| env::var(name).ok())
+    }
+
+    // Like `from_env`, reading variables through `lookup`. Empty values count
+    // as unset, as in a copied .env.example.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<ConfigLayer, WordcraftError> {
+        let var = |name: &str| lookup(name).map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+
+        let headers = match var("OPENAI_HEADERS") {
+            Some(raw) => Some(parse_headers(&raw).map_err(|err| err.context("OPENAI_HEADERS"))?.into_iter().collect()),
+            None => None,
+        };
+        let ttl_hours = match var("CACHE_TTL_HOURS") {
+            Some(raw) => Some(raw.parse().map_err(|_| {
+                WordcraftError::Config(format!("CACHE_TTL_HOURS must be a whole number of hours, got '{}'", raw))
+            })?),
+            None => None,
+        };
+
+        Ok(ConfigLayer {
+            anki_connect_url: var("ANKI_CONNECT_URL"),
+            engine: var("ENGINE"),
+            known_words_apkg: var("KNOWN_WORDS_APKG").map(|paths| {
+                paths.split(',')
+                    .map(str::trim)
+                    .filter(|path| !path.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }),
+            openai: OpenAILayer {
+                base_url: var("OPENAI_BASE_URL"),
+                model: var("OPENAI_MODEL"),
+                api_key: var("OPEN_API_KEY"),
+                headers,
+                structured_output: var("OPENAI_STRUCTURED_OUTPUT")
+                    .map(|value| !matches!(value.to_lowercase().as_str(), "false" | "0" | "no")),
+            },
+            ollama: OllamaLayer {
+                base_url: var("OLLAMA_BASE_URL"),
+                model: var("OLLAMA_MODEL"),
+            },
+            tts: TtsLayer {
+                command: var("TTS_COMMAND"),
+                extension: var("TTS_EXTENSION"),
+            },
+            fixtures: FixturesLayer {
+                record: var("LLM_RECORD_DIR"),
+                replay: var("LLM_REPLAY_DIR"),
+            },
+            cache: CacheLayer {
+                dir: var("WORDCRAFT_CACHE_DIR"),
+                ttl_hours,
+            },
+            prompts: PromptsLayer {
+                dir: var("WORDCRAFT_PROMPT_DIR"),
+            },
+        })
+    }
+
+    // Apply this layer on top of `config`
+    pub fn apply(&self, config: &mut Config) {
+        fn set<T: Clone>(target: &mut T, value: &Option<T>) {
+            if let Some(value) = value {
+                *target = value.clone();
+            }
+        }
+
+        set(&mut config.anki_connect_url, &self.anki_connect_url);
+        set(&mut config.engine, &self.engine);
+        set(&mut config.known_words_apkg, &self.known_words_apkg);
+
+        set(&mut config.openai.base_url, &self.openai.base_url);
+        set(&mut config.openai.model, &self.openai.model);
+        if let Some(api_key) = &self.openai.api_key {
+            config.openai.api_key = Some(api_key.clone());
+        }
+        if let Some(headers) = &self.openai.headers {
+            config.openai.headers = headers.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+        }
+        set(&mut config.openai.structured_output, &self.openai.structured_output);
+
+        set(&mut config.ollama.base_url, &self.ollama.base_url);
+        set(&mut config.ollama.model, &self.ollama.model);
+
+        if let Some(command) = &self.tts.command {
+            config.tts.command = Some(command.clone());
+        }
+        set(&mut config.tts.extension, &self.tts.extension);
+
+        if let Some(record) = &self.fixtures.record {
+            config.fixtures.record = Some(record.clone());
+        }
+        if let Some(replay) = &self.fixtures.replay {
+            config.fixtures.replay = Some(replay.clone());
+        }
+
+        if let Some(dir) = &self.cache.dir {
+            config.cache.dir = Some(dir.clone());
+        }
+        set(&mut config.cache.ttl_hours, &self.cache.ttl_hours);
+
+        if let Some(dir) = &self.prompts.dir {
+            config.prompts.dir = Some(dir.clone());
+        }
+    }
+}
+
+// The config file: a base layer, named profiles and the profile to use when
+// none is asked for
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigFile {
+    pub profile: Option<String>,
+    pub base: ConfigLayer,
+    pub profiles: BTreeMap<String, ConfigLayer>,
+}
+
+impl ConfigFile {
+    // A missing file is an empty one
+    pub fn load(path: &Path) -> Result<ConfigFile, WordcraftError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => ConfigFile::parse(&text)
+                .map_err(|err| WordcraftError::Config(format!("Invalid config file {}: {}", path.display(), err))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(ConfigFile::default()),
+            Err(err) => Err(WordcraftError::Config(format!("Could not read config file {}: {}", path.display(), err))),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<ConfigFile, String> {
+        let mut table: toml::Table = toml::from_str(text).map_err(|err| err.to_string())?;
+
+        let profile = match table.remove("profile") {
+            Some(toml::Value::String(name)) => Some(name),
+            Some(_) => return Err("profile must be the name of a profile".to_string()),
+            None => None,
+        };
+        let profiles = match table.remove("profiles") {
+            Some(toml::Value::Table(profiles)) => profiles.into_iter()
+                .map(|(name, layer)| {
+                    layer.try_into()
+                        .map(|layer| (name.clone(), layer))
+                        .map_err(|err: toml::de::Error| format!("profile '{}': {}", name, err.message()))
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err("profiles must be a table of profiles".to_string()),
+            None => BTreeMap::new(),
+        };
+        let base = toml::Value::Table(table).try_into()
+            .map_err(|err: toml::de::Error| err.message().to_string())?;
+
+        Ok(ConfigFile { profile, base, profiles })
+    }
+}
+
+// Where to look and what to put on top, usually from the command line
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSources {
+    // Config file to read instead of the default one; it must exist
+    pub path: Option<PathBuf>,
+    pub profile: Option<String>,
+    pub overrides: ConfigLayer,
+}
+
+impl Config {
+    // Layer the config file, the environment, the profile and the overrides
+    // and check the result
+    pub fn load(sources: ConfigSources) -> Result<Config, WordcraftError> {
+        let explicit = sources.path.clone()
+            .or_else(|| env::var_os("WORDCRAFT_CONFIG").filter(|path| !path.is_empty()).map(PathBuf::from));
+        let path = match explicit {
+            Some(path) if !path.exists() => {
+                return Err(WordcraftError::Config(format!("Config file {} does not exist", path.display())));
+            }
+            Some(path) => path,
+            None => default_config_path(),
+        };
+        let file = ConfigFile::load(&path)?;
+        let profile = sources.profile.clone()
+            .or_else(|| env::var("WORDCRAFT_PROFILE").ok().filter(|name| !name.trim().is_empty()));
+
+        Config::layered(&file, &ConfigLayer::from_env()?, profile.as_deref(), &sources.overrides).map_err(|err| {
+            // The file is only named when it is wrong on its own, not when
+            // the environment or a flag is
+            let empty = ConfigLayer::default();
+            match path.exists() && Config::layered(&file, &empty, profile.as_deref(), &empty).is_err() {
+                true => WordcraftError::Config(format!("{} ({})", err, path.display())),
+                false => WordcraftError::Config(err),
+            }
+        })
+    }
+
+    // Build a config from already loaded layers. `profile` defaults to the
+    // file's `profile`.
+    pub fn layered(
+        file: &ConfigFile,
+        env: &ConfigLayer,
+        profile: Option<&str>,
+        overrides: &ConfigLayer,
+    ) -> Result<Config, String> {
+        let mut config = Config::default();
+        file.base.apply(&mut config);
+        env.apply(&mut config);
+
+        if let Some(name) = profile.or(file.profile.as_deref()) {
+            let layer = file.profiles.get(name).ok_or_else(|| {
+                let known: Vec<&str> = file.profiles.keys().map(String::as_str).collect();
+                match known.is_empty() {
+                    true => format!("Unknown profile '{}': the config file defines no profiles", name),
+                    false => format!("Unknown profile '{}'. Profiles: {}", name, known.join(", ")),
+                }
+            })?;
+            layer.apply(&mut config);
+            config.profile = Some(name.to_string());
+        }
+
+        overrides.apply(&mut config);
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Catch mistakes before anything talks to Anki or the model. The engine
+    // is checked by `build_generator`, as only some commands use one.
+    pub fn validate(&self) -> Result<(), String> {
+        check_url("anki_connect_url (ANKI_CONNECT_URL)", &self.anki_connect_url)?;
+        check_url("openai.base_url (OPENAI_BASE_URL)", &self.openai.base_url)?;
+        check_url("ollama.base_url (OLLAMA_BASE_URL)", &self.ollama.base_url)?;
+        if self.tts.extension.trim_start_matches('.').is_empty() {
+            return Err("tts.extension (TTS_EXTENSION) must not be empty".to_string());
+        }
+        if self.fixtures.record.is_some() && self.fixtures.replay.is_some() {
+            return Err("fixtures.record (LLM_RECORD_DIR) and fixtures.replay (LLM_REPLAY_DIR) cannot both be set".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn engine_config(&self) -> Result<EngineConfig, WordcraftError> {
+        EngineConfig::from_config(self)
+    }
+}
+
+fn check_url(name: &str, url: &str) -> Result<(), String> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        Ok(())
+    } else {
+        Err(format!("{} must be an http:// or https:// URL, got '{}'", name, url))
+    }
+}
+
+// $XDG_CONFIG_HOME/wordcraft/config.toml or ~/.config/wordcraft/config.toml.
+// WORDCRAFT_CONFIG or --config point elsewhere.
+pub fn default_config_path() -> PathBuf {
+    wordcraft_config_dir().join("config.toml")
+}
+
+// User prompt templates, next to the config file
+pub fn default_prompt_dir() -> PathBuf {
+    wordcraft_config_dir().join("prompts")
+}
+
+fn wordcraft_config_dir() -> PathBuf {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(env::temp_dir);
+
+    config_dir.join("wordcraft")
+}
@@ -1,60 +1,19 @@
-pub const SYSTEM_MESSAGE: &str = r#"You are a language teacher. You generate flashcards for students based on their request.
-Flashcards are made of front and back. The request will contain two languages: their native language and their target language.
+pub const EXCLUSION_INSTRUCTION: &str = "The student already knows the following words. Do not create flashcards for any of them:";
 
-User's default native language is English.
-Default target language is Japanese.
+pub const MAX_EXCLUDED_WORDS_IN_PROMPT: usize = 300;
 
-You only respond with this type of answer:
-- Generate flashcard deck
-Return JSON for the user to insert into a Flashcard application
-Result should contains at least 15 flashcards.
-Front of the flashcard should be in the target language. If the word is in Kanji, add readings in Hiragana and Romaji after the Kanji.
-Back of the flashcard should be in the user's native language.
-Example should be in the target language.
-Example translation should be in the user's native language.
+pub const REPAIR_INSTRUCTION: &str = "Your previous reply could not be used as a flashcard deck. Reply again with only the corrected JSON, in the same format as before, with no other text. The problem was:";
 
-Example JSON format:
-{
-    "deck_name":"Places in Japanese",
-    "cards":[
-      {
-        "front":"家 (いえ) (ie)",
-        "back":"Home",
-        "example":"私は家にいます (わたしはいえにいます) Watashi wa ie ni imasu",
-        "example_translate":"I am home."
-      }
-    ]
-}",
-        "Make a personalized greeting for Joe"
+// Times a broken reply is sent back to the model before giving up
+pub const DEFAULT_MAX_REPAIR_ATTEMPTS: usize = 2;
 
-    )
+// Deck name used when a reply carries cards without a readable deck_name
+pub const DEFAULT_DECK_NAME: &str = "Generated flashcards";
 
-    // Create a chain with the system and user messages
-    let chain = Chain::new(model)
-        .system_message(r#"You are a language teacher. You generate flashcards for students based on their request.
-Flashcards are made of front and back. The request will contain two languages: their native language and their target language.
+// Card count the prompt asks for when none is given, as a minimum
+pub const DEFAULT_CARD_COUNT: usize = 15;
 
-User's default native language is English.
-Default target language is Japanese.
+pub const TOP_UP_INSTRUCTION: &str = "The deck has fewer flashcards than were asked for. Reply with only a JSON deck, in the same format as before, of new flashcards on the same topic and at the same level. Do not repeat a word that is already in the deck. Number of flashcards needed:";
 
-You only respond with this type of answer:
-- Generate flashcard deck
-Return JSON for the user to insert into a Flashcard application
-Result should contains at least 15 flashcards.
-Front of the flashcard should be in the target language. If the word is in Kanji, add readings in Hiragana and Romaji after the Kanji.
-Back of the flashcard should be in the user's native language.
-Example should be in the target language.
-Example translation should be in the user's native language.
-
-Example JSON format:
-{
-    "deck_name":"Places in Japanese",
-    "cards":[
-      {
-        "front":"家 (いえ) (ie)",
-        "back":"Home",
-        "example":"私は家にいます (わたしはいえにいます) Watashi wa ie ni imasu",
-        "example_translate":"I am home."
-      }
-    ]
-}"#;
\ No newline at end of file
+// Times a short deck is sent back to the model for more cards
+pub const DEFAULT_MAX_TOP_UPS: usize = 2;
@@ -0,0 +1,96 @@
+// Errors returned by the library.
+//
+// Each variant is a kind of failure a caller may want to handle differently:
+// start Anki, skip a duplicate, retry the model, fix a setting. The messages
+// are the ones the wordcraft binary prints.
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum WordcraftError {
+    /// AnkiConnect could not be reached: Anki is closed, the add-on is not
+    /// installed or the URL is wrong.
+    #[error("{message}")]
+    AnkiUnavailable { url: String, message: String },
+
+    /// AnkiConnect answered, but the action failed. `message` is the full
+    /// description, including what AnkiConnect said.
+    #[error("{message}")]
+    AnkiActionFailed { action: String, message: String },
+
+    /// The note is already in the collection.
+    #[error("Error adding card: '{front}' is already in the collection")]
+    DuplicateNote { front: String },
+
+    /// The model could not be reached or answered with an error.
+    #[error("{message}")]
+    LlmTransport { message: String },
+
+    /// The model answered, but no usable flashcards could be read from
+    /// `raw`, its last reply.
+    #[error("{message}")]
+    LlmMalformedOutput { raw: String, message: String },
+
+    /// A setting, flag or input file is invalid.
+    #[error("{0}")]
+    Config(String),
+
+    /// The text-to-speech command failed.
+    #[error("{0}")]
+    Tts(String),
+
+    /// An Anki package could not be read or written.
+    #[error("{0}")]
+    Package(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+impl WordcraftError {
+    // AnkiConnect's error for `action`, as "AnkiConnect error (action): message"
+    pub fn anki_action(action: &str, message: impl std::fmt::Display) -> Self {
+        WordcraftError::AnkiActionFailed {
+            action: action.to_string(),
+            message: format!("AnkiConnect error ({}): {}", action, message),
+        }
+    }
+
+    pub fn llm_transport(message: impl std::fmt::Display) -> Self {
+        WordcraftError::LlmTransport { message: message.to_string() }
+    }
+
+    // Say what was being done, e.g. "Error creating Wordcraft model: ...",
+    // keeping the kind of failure
+    pub fn context(self, context: impl std::fmt::Display) -> Self {
+        let prefix = |message: String| format!("{}: {}", context, message);
+        match self {
+            WordcraftError::AnkiUnavailable { url, message } => WordcraftError::AnkiUnavailable { url, message: prefix(message) },
+            WordcraftError::AnkiActionFailed { action, message } => {
+                WordcraftError::AnkiActionFailed { action, message: prefix(message) }
+            }
+            WordcraftError::LlmTransport { message } => WordcraftError::LlmTransport { message: prefix(message) },
+            WordcraftError::LlmMalformedOutput { raw, message } => {
+                WordcraftError::LlmMalformedOutput { raw, message: prefix(message) }
+            }
+            WordcraftError::Config(message) => WordcraftError::Config(prefix(message)),
+            WordcraftError::Tts(message) => WordcraftError::Tts(prefix(message)),
+            WordcraftError::Package(message) => WordcraftError::Package(prefix(message)),
+            other => other,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for WordcraftError {
+    fn from(err: rusqlite::Error) -> Self {
+        WordcraftError::Package(err.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for WordcraftError {
+    fn from(err: zip::result::ZipError) -> Self {
+        WordcraftError::Package(err.to_string())
+    }
+}
@@ -0,0 +1,884 @@
+// In-memory AnkiConnect for local development and tests.
+//
+// `Collection` keeps decks, note types, notes, cards, tags and media the way
+// an Anki collection does and answers the API version 6 actions Wordcraft
+// sends, with Anki's rules for empty and duplicate notes. `FakeAnki` shares a
+// collection between requests and serves it over HTTP; the `fake-anki` binary
+// runs it on AnkiConnect's port so `wordcraft` works against it unchanged.
+//
+// Searches support the terms Wordcraft uses: deck:, note:, tag:, is:, nid:,
+// cid:, field:value, bare text and `-` for negation.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use crate::anki_adapter::strip_html;
+use crate::anki_connect::*;
+
+// Anki ids are creation times in milliseconds; start near a realistic one
+const FIRST_ID: i64 = 1_700_000_000_000;
+
+pub const DEFAULT_DECK: &str = "Default";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FakeModel {
+    pub id: i64,
+    pub fields: Vec<String>,
+    // Card templates in order; a card's `ord` indexes this list
+    pub templates: Vec<(String, CardTemplate)>,
+    pub css: String,
+    pub is_cloze: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FakeNote {
+    pub id: i64,
+    pub model: String,
+    // Values in the order of the model's fields
+    pub fields: Vec<(String, String)>,
+    pub tags: Vec<String>,
+}
+
+impl FakeNote {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.iter().find(|(field, _)| field == name).map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FakeCard {
+    pub id: i64,
+    pub note: i64,
+    pub deck: String,
+    pub ord: usize,
+    // 0 new, 1 learning, 2 review
+    pub card_type: i64,
+    pub suspended: bool,
+    // For review cards, days until the card is due; 0 or less is due
+    pub due: i64,
+    pub interval: i64,
+    pub ease_factor: i64,
+    pub reps: i64,
+    pub lapses: i64,
+    pub reviews: Vec<ReviewEntry>,
+}
+
+impl FakeCard {
+    fn is_due(&self) -> bool {
+        !self.suspended && self.card_type == 2 && self.due <= 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Collection {
+    // Deck name to id
+    pub decks: BTreeMap<String, i64>,
+    pub models: BTreeMap<String, FakeModel>,
+    pub notes: BTreeMap<i64, FakeNote>,
+    pub cards: BTreeMap<i64, FakeCard>,
+    pub media: BTreeMap<String, Vec<u8>>,
+    next_id: i64,
+}
+
+// A new profile: the Default deck and the Basic and Cloze note types
+impl Default for Collection {
+    fn default() -> Self {
+        let mut collection = Collection {
+            decks: BTreeMap::new(),
+            models: BTreeMap::new(),
+            notes: BTreeMap::new(),
+            cards: BTreeMap::new(),
+            media: BTreeMap::new(),
+            next_id: FIRST_ID,
+        };
+        collection.decks.insert(DEFAULT_DECK.to_string(), 1);
+
+        let basic = CardTemplate {
+            name: None,
+            front: "{{Front}}".to_string(),
+            back: "{{FrontSide}}<hr id=answer>{{Back}}".to_string(),
+        };
+        let cloze = CardTemplate {
+            name: None,
+            front: "{{cloze:Text}}".to_string(),
+            back: "{{cloze:Text}}<br>{{Back Extra}}".to_string(),
+        };
+        for (name, fields, template, is_cloze) in [
+            ("Basic", ["Front", "Back"], basic, false),
+            ("Cloze", ["Text", "Back Extra"], cloze, true),
+        ] {
+            let id = collection.new_id();
+            collection.models.insert(name.to_string(), FakeModel {
+                id,
+                fields: fields.iter().map(|field| field.to_string()).collect(),
+                templates: vec![(if is_cloze { "Cloze" } else { "Card 1" }.to_string(), template)],
+                css: ".card { font-family: arial; }".to_string(),
+                is_cloze,
+            });
+        }
+        collection
+    }
+}
+
+impl Collection {
+    fn new_id(&mut self) -> i64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    // Answer an AnkiConnect request with its `{result, error}` envelope
+    pub fn handle(&mut self, request: &Value) -> Value {
+        let action = request.get("action").and_then(Value::as_str).unwrap_or_default();
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        match self.dispatch(action, params) {
+            Ok(result) => json!({ "result": result, "error": null }),
+            Err(error) => json!({ "result": null, "error": error }),
+        }
+    }
+
+    fn dispatch(&mut self, action: &str, params: Value) -> Result<Value, String> {
+        match action {
+            Version::NAME => to_result(ANKI_CONNECT_VERSION),
+            SyncCollection::NAME => to_result(()),
+
+            DeckNames::NAME => to_result(self.decks.keys().collect::<Vec<_>>()),
+            DeckNamesAndIds::NAME => to_result(&self.decks),
+            CreateDeck::NAME => {
+                let params: CreateDeck = from_params(params)?;
+                to_result(self.create_deck(&params.deck))
+            }
+            DeleteDecks::NAME => {
+                let params: DeleteDecks = from_params(params)?;
+                self.delete_decks(&params.decks);
+                to_result(())
+            }
+            ChangeDeck::NAME => {
+                let params: ChangeDeck = from_params(params)?;
+                self.create_deck(&params.deck);
+                for id in &params.cards {
+                    if let Some(card) = self.cards.get_mut(id) {
+                        card.deck = params.deck.clone();
+                    }
+                }
+                to_result(())
+            }
+            GetDeckStats::NAME => {
+                let params: GetDeckStats = from_params(params)?;
+                to_result(self.deck_stats(&params.decks))
+            }
+
+            AddNote::NAME => {
+                let params: AddNote = from_params(params)?;
+                to_result(self.add_note(&params.note)?)
+            }
+            AddNotes::NAME => {
+                let params: AddNotes = from_params(params)?;
+                to_result(params.notes.iter().map(|note| self.add_note(note).ok()).collect::<Vec<_>>())
+            }
+            CanAddNotes::NAME => {
+                let params: CanAddNotes = from_params(params)?;
+                to_result(params.notes.iter().map(|note| self.check_note(note).is_ok()).collect::<Vec<_>>())
+            }
+            CanAddNotesWithErrorDetail::NAME => {
+                let params: CanAddNotesWithErrorDetail = from_params(params)?;
+                to_result(params.notes.iter()
+                    .map(|note| match self.check_note(note) {
+                        Ok(()) => CanAddResult { can_add: true, error: None },
+                        Err(error) => CanAddResult { can_add: false, error: Some(error) },
+                    })
+                    .collect::<Vec<_>>())
+            }
+            UpdateNoteFields::NAME => {
+                let params: UpdateNoteFields = from_params(params)?;
+                let note = self.notes.get_mut(&params.note.id)
+                    .ok_or_else(|| format!("note was not found: {}", params.note.id))?;
+                for (name, value) in note.fields.iter_mut() {
+                    if let Some(new_value) = params.note.fields.get(name) {
+                        *value = new_value.clone();
+                    }
+                }
+                to_result(())
+            }
+            FindNotes::NAME => {
+                let params: FindNotes = from_params(params)?;
+                let mut notes: Vec<i64> = self.search(&params.query)?.iter().map(|id| self.cards[id].note).collect();
+                notes.sort();
+                notes.dedup();
+                to_result(notes)
+            }
+            NotesInfo::NAME => {
+                let params: NotesInfo = from_params(params)?;
+                to_result(params.notes.iter().map(|id| self.note_info(*id)).collect::<Vec<_>>())
+            }
+            DeleteNotes::NAME => {
+                let params: DeleteNotes = from_params(params)?;
+                self.delete_notes(&params.notes);
+                to_result(())
+            }
+            AddTags::NAME => {
+                let params: AddTags = from_params(params)?;
+                for note in self.notes.values_mut().filter(|note| params.notes.contains(&note.id)) {
+                    for tag in params.tags.split_whitespace() {
+                        if !note.tags.iter().any(|existing| existing.eq_ignore_ascii_case(tag)) {
+                            note.tags.push(tag.to_string());
+                        }
+                    }
+                }
+                to_result(())
+            }
+            RemoveTags::NAME => {
+                let params: RemoveTags = from_params(params)?;
+                let removed: Vec<&str> = params.tags.split_whitespace().collect();
+                for note in self.notes.values_mut().filter(|note| params.notes.contains(&note.id)) {
+                    note.tags.retain(|tag| !removed.iter().any(|removed| tag.eq_ignore_ascii_case(removed)));
+                }
+                to_result(())
+            }
+            GetTags::NAME => {
+                let mut tags: Vec<&String> = self.notes.values().flat_map(|note| &note.tags).collect();
+                tags.sort();
+                tags.dedup();
+                to_result(tags)
+            }
+
+            FindCards::NAME => {
+                let params: FindCards = from_params(params)?;
+                to_result(self.search(&params.query)?)
+            }
+            CardsInfo::NAME => {
+                let params: CardsInfo = from_params(params)?;
+                to_result(params.cards.iter().map(|id| self.card_info(*id)).collect::<Vec<_>>())
+            }
+            CardsToNotes::NAME => {
+                let params: CardsToNotes = from_params(params)?;
+                let mut notes: Vec<i64> = Vec::new();
+                for note in params.cards.iter().filter_map(|id| self.cards.get(id)).map(|card| card.note) {
+                    if !notes.contains(&note) {
+                        notes.push(note);
+                    }
+                }
+                to_result(notes)
+            }
+            Suspend::NAME => {
+                let params: Suspend = from_params(params)?;
+                to_result(self.set_suspended(&params.cards, true))
+            }
+            Unsuspend::NAME => {
+                let params: Unsuspend = from_params(params)?;
+                to_result(self.set_suspended(&params.cards, false))
+            }
+            AreSuspended::NAME => {
+                let params: AreSuspended = from_params(params)?;
+                to_result(params.cards.iter().map(|id| self.cards.get(id).map(|card| card.suspended)).collect::<Vec<_>>())
+            }
+            AreDue::NAME => {
+                let params: AreDue = from_params(params)?;
+                to_result(params.cards.iter().map(|id| self.cards.get(id).is_some_and(FakeCard::is_due)).collect::<Vec<_>>())
+            }
+            GetEaseFactors::NAME => {
+                let params: GetEaseFactors = from_params(params)?;
+                to_result(params.cards.iter().map(|id| self.cards.get(id).map_or(0, |card| card.ease_factor)).collect::<Vec<_>>())
+            }
+            GetIntervals::NAME => {
+                let params: GetIntervals = from_params(params)?;
+                to_result(params.cards.iter().map(|id| self.cards.get(id).map_or(0, |card| card.interval)).collect::<Vec<_>>())
+            }
+            GetReviewsOfCards::NAME => {
+                let params: GetReviewsOfCards = from_params(params)?;
+                let reviews: HashMap<&String, Vec<ReviewEntry>> = params.cards.iter()
+                    .map(|id| {
+                        let card = id.parse::<i64>().ok().and_then(|id| self.cards.get(&id));
+                        (id, card.map(|card| card.reviews.clone()).unwrap_or_default())
+                    })
+                    .collect();
+                to_result(reviews)
+            }
+
+            ModelNames::NAME => to_result(self.models.keys().collect::<Vec<_>>()),
+            ModelNamesAndIds::NAME => {
+                to_result(self.models.iter().map(|(name, model)| (name, model.id)).collect::<BTreeMap<_, _>>())
+            }
+            ModelFieldNames::NAME => {
+                let params: ModelFieldNames = from_params(params)?;
+                to_result(&self.model(&params.model_name)?.fields)
+            }
+            CreateModel::NAME => {
+                let params: CreateModel = from_params(params)?;
+                to_result(self.create_model(params)?)
+            }
+            ModelTemplates::NAME => {
+                let params: ModelTemplates = from_params(params)?;
+                to_result(self.model(&params.model_name)?.templates.iter().cloned().collect::<BTreeMap<_, _>>())
+            }
+            ModelStyling::NAME => {
+                let params: ModelStyling = from_params(params)?;
+                to_result(ModelCss { css: self.model(&params.model_name)?.css.clone() })
+            }
+            UpdateModelTemplates::NAME => {
+                let params: UpdateModelTemplates = from_params(params)?;
+                let model = self.model_mut(&params.model.name)?;
+                // Like AnkiConnect, templates the model does not have are ignored
+                for (name, template) in model.templates.iter_mut() {
+                    if let Some(update) = params.model.templates.get(name) {
+                        template.front = update.front.clone();
+                        template.back = update.back.clone();
+                    }
+                }
+                to_result(())
+            }
+            UpdateModelStyling::NAME => {
+                let params: UpdateModelStyling = from_params(params)?;
+                self.model_mut(&params.model.name)?.css = params.model.css;
+                to_result(())
+            }
+            ModelFieldAdd::NAME => {
+                let params: ModelFieldAdd = from_params(params)?;
+                self.add_field(&params.model_name, &params.field_name, params.index as usize)?;
+                to_result(())
+            }
+
+            StoreMediaFile::NAME => {
+                let params: StoreMediaFile = from_params(params)?;
+                let data = params.data
+                    .ok_or("only media sent as base64 data can be stored")?;
+                let bytes = STANDARD.decode(data).map_err(|err| format!("invalid base64 data: {}", err))?;
+                self.media.insert(params.filename.clone(), bytes);
+                to_result(params.filename)
+            }
+            RetrieveMediaFile::NAME => {
+                let params: RetrieveMediaFile = from_params(params)?;
+                match self.media.get(&params.filename) {
+                    Some(bytes) => to_result(STANDARD.encode(bytes)),
+                    None => to_result(false),
+                }
+            }
+            GetMediaFilesNames::NAME => {
+                let params: GetMediaFilesNames = from_params(params)?;
+                let pattern = glob(&params.pattern)?;
+                to_result(self.media.keys().filter(|name| pattern.is_match(name)).collect::<Vec<_>>())
+            }
+            DeleteMediaFile::NAME => {
+                let params: DeleteMediaFile = from_params(params)?;
+                self.media.remove(&params.filename);
+                to_result(())
+            }
+
+            GuiBrowse::NAME => {
+                let params: GuiBrowse = from_params(params)?;
+                to_result(self.search(&params.query)?)
+            }
+            GuiDeckOverview::NAME => {
+                let params: GuiDeckOverview = from_params(params)?;
+                to_result(self.decks.contains_key(&params.name))
+            }
+            GuiDeckBrowser::NAME | GuiCurrentCard::NAME => to_result(()),
+
+            _ => Err("unsupported action".to_string()),
+        }
+    }
+
+    fn model(&self, name: &str) -> Result<&FakeModel, String> {
+        self.models.get(name).ok_or_else(|| format!("model was not found: {}", name))
+    }
+
+    fn model_mut(&mut self, name: &str) -> Result<&mut FakeModel, String> {
+        self.models.get_mut(name).ok_or_else(|| format!("model was not found: {}", name))
+    }
+
+    // Create the deck and its parents, returning the deck's id
+    pub fn create_deck(&mut self, name: &str) -> i64 {
+        let mut path = String::new();
+        for part in name.split("::") {
+            if !path.is_empty() {
+                path.push_str("::");
+            }
+            path.push_str(part);
+            if !self.decks.contains_key(&path) {
+                let id = self.new_id();
+                self.decks.insert(path.clone(), id);
+            }
+        }
+        self.decks[name]
+    }
+
+    // Remove the decks, their subdecks and their cards. The Default deck stays.
+    fn delete_decks(&mut self, names: &[String]) {
+        let deleted: Vec<String> = self.decks.keys()
+            .filter(|deck| names.iter().any(|name| in_deck(deck, name)))
+            .filter(|deck| deck.as_str() != DEFAULT_DECK)
+            .cloned()
+            .collect();
+        for deck in &deleted {
+            self.decks.remove(deck);
+        }
+
+        self.cards.retain(|_, card| !deleted.contains(&card.deck));
+        let cards = &self.cards;
+        self.notes.retain(|id, _| cards.values().any(|card| card.note == *id));
+    }
+
+    fn deck_stats(&self, names: &[String]) -> HashMap<String, DeckStats> {
+        names.iter()
+            .filter_map(|name| self.decks.get(name).map(|id| (name, *id)))
+            .map(|(name, id)| {
+                let cards: Vec<&FakeCard> = self.cards.values()
+                    .filter(|card| in_deck(&card.deck, name))
+                    .collect();
+                let count = |card_type: i64| cards.iter().filter(|card| !card.suspended && card.card_type == card_type).count() as i64;
+                (id.to_string(), DeckStats {
+                    deck_id: id,
+                    name: name.clone(),
+                    new_count: count(0),
+                    learn_count: count(1),
+                    review_count: cards.iter().filter(|card| card.is_due()).count() as i64,
+                    total_in_deck: cards.len() as i64,
+                })
+            })
+            .collect()
+    }
+
+    fn create_model(&mut self, params: CreateModel) -> Result<Value, String> {
+        if self.models.contains_key(&params.model_name) {
+            return Err("Model name already exists".to_string());
+        }
+        if params.in_order_fields.is_empty() {
+            return Err("Must provide at least one field for inOrderFields".to_string());
+        }
+        if params.card_templates.is_empty() {
+            return Err("Must provide at least one card for cardTemplates".to_string());
+        }
+
+        let id = self.new_id();
+        let templates = params.card_templates.into_iter()
+            .enumerate()
+            .map(|(index, template)| {
+                let name = template.name.clone().unwrap_or_else(|| format!("Card {}", index + 1));
+                (name, CardTemplate { name: None, ..template })
+            })
+            .collect();
+        let is_cloze = params.is_cloze.unwrap_or(false);
+        self.models.insert(params.model_name.clone(), FakeModel {
+            id,
+            fields: params.in_order_fields.clone(),
+            templates,
+            css: params.css,
+            is_cloze,
+        });
+
+        Ok(json!({
+            "id": id,
+            "name": params.model_name,
+            "type": if is_cloze { 1 } else { 0 },
+            "flds": params.in_order_fields.iter().enumerate()
+                .map(|(ord, name)| json!({ "name": name, "ord": ord }))
+                .collect::<Vec<_>>(),
+        }))
+    }
+
+    // Insert a field at `index`; existing notes get it empty
+    fn add_field(&mut self, model_name: &str, field: &str, index: usize) -> Result<(), String> {
+        let model = self.model_mut(model_name)?;
+        if model.fields.iter().any(|existing| existing == field) {
+            return Err(format!("field already exists: {}", field));
+        }
+        let index = index.min(model.fields.len());
+        model.fields.insert(index, field.to_string());
+
+        for note in self.notes.values_mut().filter(|note| note.model == model_name) {
+            note.fields.insert(index.min(note.fields.len()), (field.to_string(), String::new()));
+        }
+        Ok(())
+    }
+
+    // Why Anki would refuse to add the note, in AnkiConnect's words
+    fn check_note(&self, note: &Note) -> Result<(), String> {
+        let model = self.model(&note.model_name)?;
+        if !self.decks.contains_key(&note.deck_name) {
+            return Err(format!("deck was not found: {}", note.deck_name));
+        }
+
+        let first_field = model.fields.first()
+            .and_then(|field| note.fields.get(field))
+            .map(|value| strip_html(value))
+            .unwrap_or_default();
+        if first_field.is_empty() {
+            return Err("cannot create note because it is empty".to_string());
+        }
+        if model.is_cloze && cloze_numbers(note.fields.values()).is_empty() {
+            return Err("cannot create note because it has no cloze deletions".to_string());
+        }
+
+        // Anki compares the first field of notes of the same type, optionally
+        // only within the deck
+        if !note.options.allow_duplicate {
+            let same_deck_only = note.options.duplicate_scope.as_deref() == Some("deck");
+            let duplicate = self.notes.values()
+                .filter(|existing| existing.model == note.model_name)
+                .filter(|existing| existing.fields.first().is_some_and(|(_, value)| strip_html(value) == first_field))
+                .any(|existing| {
+                    !same_deck_only
+                        || self.cards.values().any(|card| card.note == existing.id && card.deck == note.deck_name)
+                });
+            if duplicate {
+                return Err("cannot create note because it is a duplicate".to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    // Add the note and its cards: one per template, or one per cloze number
+    pub fn add_note(&mut self, note: &Note) -> Result<i64, String> {
+        self.check_note(note)?;
+        let model = self.model(&note.model_name)?.clone();
+
+        let note_id = self.new_id();
+        let mut tags: Vec<String> = Vec::new();
+        for tag in &note.tags {
+            if !tags.iter().any(|existing| existing.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.clone());
+            }
+        }
+        self.notes.insert(note_id, FakeNote {
+            id: note_id,
+            model: note.model_name.clone(),
+            fields: model.fields.iter()
+                .map(|field| (field.clone(), note.fields.get(field).cloned().unwrap_or_default()))
+                .collect(),
+            tags,
+        });
+
+        let ords: Vec<usize> = if model.is_cloze {
+            cloze_numbers(note.fields.values()).into_iter().map(|number| number - 1).collect()
+        } else {
+            (0..model.templates.len()).collect()
+        };
+        for ord in ords {
+            let card_id = self.new_id();
+            let position = self.cards.len() as i64 + 1;
+            self.cards.insert(card_id, FakeCard {
+                id: card_id,
+                note: note_id,
+                deck: note.deck_name.clone(),
+                ord,
+                card_type: 0,
+                suspended: false,
+                due: position,
+                interval: 0,
+                ease_factor: 0,
+                reps: 0,
+                lapses: 0,
+                reviews: Vec::new(),
+            });
+        }
+
+        Ok(note_id)
+    }
+
+    fn delete_notes(&mut self, ids: &[i64]) {
+        for id in ids {
+            self.notes.remove(id);
+        }
+        self.cards.retain(|_, card| !ids.contains(&card.note));
+    }
+
+    // Returns false when no card changed, like AnkiConnect
+    fn set_suspended(&mut self, ids: &[i64], suspended: bool) -> bool {
+        let mut changed = false;
+        for card in self.cards.values_mut().filter(|card| ids.contains(&card.id)) {
+            changed |= card.suspended != suspended;
+            card.suspended = suspended;
+        }
+        changed
+    }
+
+    // AnkiConnect answers `{}` for an id that does not exist
+    fn note_info(&self, id: i64) -> Value {
+        let Some(note) = self.notes.get(&id) else {
+            return json!({});
+        };
+        let info = NoteInfo {
+            note_id: note.id,
+            model_name: note.model.clone(),
+            tags: note.tags.clone(),
+            fields: field_values(note),
+            cards: self.cards.values().filter(|card| card.note == id).map(|card| card.id).collect(),
+        };
+        serde_json::to_value(info).unwrap_or_default()
+    }
+
+    fn card_info(&self, id: i64) -> Value {
+        let Some((card, note)) = self.cards.get(&id).and_then(|card| Some((card, self.notes.get(&card.note)?))) else {
+            return json!({});
+        };
+        let template = self.models.get(&note.model)
+            .and_then(|model| model.templates.get(card.ord).or(model.templates.first()))
+            .map(|(_, template)| template.clone());
+        let (question, answer) = match template {
+            Some(template) => {
+                let question = render(&template.front, note, "");
+                let answer = render(&template.back, note, &question);
+                (question, answer)
+            }
+            None => (String::new(), String::new()),
+        };
+
+        let info = CardInfo {
+            card_id: card.id,
+            note: note.id,
+            deck_name: card.deck.clone(),
+            model_name: note.model.clone(),
+            fields: field_values(note),
+            question,
+            answer,
+            interval: card.interval,
+            ease_factor: card.ease_factor,
+            card_type: card.card_type,
+            queue: if card.suspended { -1 } else { card.card_type },
+            due: card.due,
+            reps: card.reps,
+            lapses: card.lapses,
+        };
+        serde_json::to_value(info).unwrap_or_default()
+    }
+
+    // Ids of the cards matching an Anki search, in creation order
+    pub fn search(&self, query: &str) -> Result<Vec<i64>, String> {
+        let terms = search_terms(query)?
+            .into_iter()
+            .map(|term| SearchTerm::parse(&term))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(self.cards.values()
+            .filter(|card| {
+                let Some(note) = self.notes.get(&card.note) else {
+                    return false;
+                };
+                terms.iter().all(|term| term.negated != term.matches(card, note))
+            })
+            .map(|card| card.id)
+            .collect())
+    }
+}
+
+// A deck is in `parent` when it is that deck or one of its subdecks
+fn in_deck(deck: &str, parent: &str) -> bool {
+    deck.eq_ignore_ascii_case(parent)
+        || deck.get(..parent.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(parent))
+            && deck[parent.len()..].strip_prefix("::").is_some_and(|child| !child.is_empty())
+}
+
+fn field_values(note: &FakeNote) -> HashMap<String, FieldValue> {
+    note.fields.iter()
+        .enumerate()
+        .map(|(order, (name, value))| (name.clone(), FieldValue { value: value.clone(), order: order as u32 }))
+        .collect()
+}
+
+// Fill in {{Field}} and {{FrontSide}}; other template syntax is left as is
+fn render(template: &str, note: &FakeNote, front_side: &str) -> String {
+    let mut text = template.replace("{{FrontSide}}", front_side);
+    for (name, value) in &note.fields {
+        text = text.replace(&format!("{{{{{}}}}}", name), value);
+    }
+    text
+}
+
+// The distinct N of the {{cN::...}} deletions in the fields, in order
+fn cloze_numbers<'a>(fields: impl Iterator<Item = &'a String>) -> Vec<usize> {
+    let pattern = Regex::new(r"\{\{c(\d+)::").expect("valid cloze pattern");
+    let mut numbers: Vec<usize> = fields
+        .flat_map(|value| pattern.captures_iter(value).filter_map(|captures| captures[1].parse().ok()).collect::<Vec<_>>())
+        .filter(|number| *number > 0)
+        .collect();
+    numbers.sort();
+    numbers.dedup();
+    numbers
+}
+
+// A case-insensitive matcher for an Anki pattern where `*` is any text
+fn glob(pattern: &str) -> Result<Regex, String> {
+    let pattern = regex::escape(pattern).replace(r"\*", ".*");
+    Regex::new(&format!("(?is)^{}$", pattern)).map_err(|err| err.to_string())
+}
+
+// Split a search into terms. Quotes group words and are dropped, a backslash
+// escapes the next character.
+fn search_terms(query: &str) -> Result<Vec<String>, String> {
+    let mut terms = Vec::new();
+    let mut term = String::new();
+    let mut quoted = false;
+    let mut chars = query.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => term.extend(chars.next()),
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !term.is_empty() {
+                    terms.push(std::mem::take(&mut term));
+                }
+            }
+            c => term.push(c),
+        }
+    }
+    if quoted {
+        return Err(format!("unterminated quote in search: {}", query));
+    }
+    if !term.is_empty() {
+        terms.push(term);
+    }
+    Ok(terms)
+}
+
+struct SearchTerm {
+    negated: bool,
+    kind: TermKind,
+}
+
+enum TermKind {
+    Deck(String),
+    Note(Regex),
+    Tag(Regex),
+    Is(String),
+    NoteIds(Vec<i64>),
+    CardIds(Vec<i64>),
+    Field(String, Regex),
+    Text(String),
+}
+
+impl SearchTerm {
+    fn parse(term: &str) -> Result<SearchTerm, String> {
+        let (negated, term) = match term.strip_prefix('-') {
+            Some(rest) if !rest.is_empty() => (true, rest),
+            _ => (false, term),
+        };
+        if term.eq_ignore_ascii_case("or") || term.starts_with('(') || term.ends_with(')') {
+            return Err(format!("the fake AnkiConnect does not support '{}' in searches", term));
+        }
+
+        let ids = |list: &str| {
+            list.split(',')
+                .map(|id| id.trim().parse::<i64>().map_err(|_| format!("invalid id in search: {}", id)))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let kind = match term.split_once(':') {
+            None => TermKind::Text(term.to_lowercase()),
+            Some((key, value)) => match key.to_lowercase().as_str() {
+                "deck" => TermKind::Deck(value.to_string()),
+                "note" => TermKind::Note(glob(value)?),
+                "tag" => TermKind::Tag(glob(value)?),
+                "is" => match value {
+                    "new" | "learn" | "review" | "due" | "suspended" => TermKind::Is(value.to_string()),
+                    _ => return Err(format!("unsupported search: is:{}", value)),
+                },
+                "nid" => TermKind::NoteIds(ids(value)?),
+                "cid" => TermKind::CardIds(ids(value)?),
+                _ => TermKind::Field(key.to_string(), glob(value)?),
+            },
+        };
+        Ok(SearchTerm { negated, kind })
+    }
+
+    fn matches(&self, card: &FakeCard, note: &FakeNote) -> bool {
+        match &self.kind {
+            TermKind::Deck(deck) if deck.contains('*') => glob(deck).is_ok_and(|pattern| pattern.is_match(&card.deck)),
+            TermKind::Deck(deck) => in_deck(&card.deck, deck),
+            TermKind::Note(pattern) => pattern.is_match(&note.model),
+            TermKind::Tag(pattern) => note.tags.iter().any(|tag| pattern.is_match(tag)),
+            TermKind::Is(state) => match state.as_str() {
+                "new" => card.card_type == 0,
+                "learn" => card.card_type == 1,
+                "review" => card.card_type == 2,
+                "due" => card.is_due(),
+                _ => card.suspended,
+            },
+            TermKind::NoteIds(ids) => ids.contains(&note.id),
+            TermKind::CardIds(ids) => ids.contains(&card.id),
+            TermKind::Field(name, pattern) => note.fields.iter()
+                .any(|(field, value)| field.eq_ignore_ascii_case(name) && pattern.is_match(value)),
+            TermKind::Text(text) => note.fields.iter()
+                .any(|(_, value)| strip_html(value).to_lowercase().contains(text.as_str())),
+        }
+    }
+}
+
+fn from_params<T: DeserializeOwned>(params: Value) -> Result<T, String> {
+    serde_json::from_value(params).map_err(|err| format!("invalid params: {}", err))
+}
+
+fn to_result<T: Serialize>(value: T) -> Result<Value, String> {
+    serde_json::to_value(value).map_err(|err| err.to_string())
+}
+
+// A collection shared by every request, served over HTTP like AnkiConnect
+#[derive(Debug, Clone, Default)]
+pub struct FakeAnki {
+    collection: Arc<Mutex<Collection>>,
+}
+
+impl FakeAnki {
+    pub fn new() -> Self {
+        FakeAnki::default()
+    }
+
+    // The collection, to seed or inspect it
+    pub fn collection(&self) -> MutexGuard<'_, Collection> {
+        self.collection.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn handle(&self, request: &Value) -> Value {
+        self.collection().handle(request)
+    }
+
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/", get(|| async { "AnkiConnect v.6" }).post(answer))
+            .with_state(self.clone())
+    }
+
+    pub async fn serve(&self, listener: TcpListener) -> std::io::Result<()> {
+        axum::serve(listener, self.router()).await
+    }
+
+    // Serve on a free local port until the returned server is dropped
+    pub async fn start(&self) -> std::io::Result<FakeAnkiServer> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let anki = self.clone();
+        let task = tokio::spawn(async move {
+            if let Err(err) = anki.serve(listener).await {
+                eprintln!("Fake AnkiConnect stopped: {}", err);
+            }
+        });
+        Ok(FakeAnkiServer { url, task })
+    }
+}
+
+// Any body is read as JSON, whatever its content type, like AnkiConnect
+async fn answer(State(anki): State<FakeAnki>, body: String) -> Json<Value> {
+    Json(match serde_json::from_str::<Value>(&body) {
+        Ok(request) => anki.handle(&request),
+        Err(err) => json!({ "result": null, "error": format!("invalid request: {}", err) }),
+    })
+}
+
+pub struct FakeAnkiServer {
+    pub url: String,
+    task: JoinHandle<()>,
+}
+
+impl Drop for FakeAnkiServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,466 @@
+// Turning a request into a deck of flashcards.
+//
+// `generate_flashcards` builds the system prompt, asks a `FlashcardGenerator`
+// for the deck and parses the reply against the flashcard schema. Unparsable
+// replies are sent back for repair, short decks are topped up and cards for
+// words the learner already knows are dropped.
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use regex::Regex;
+
+use crate::constant::{
+    DEFAULT_MAX_REPAIR_ATTEMPTS, EXCLUSION_INSTRUCTION, MAX_EXCLUDED_WORDS_IN_PROMPT, REPAIR_INSTRUCTION,
+    DEFAULT_CARD_COUNT, DEFAULT_DECK_NAME, DEFAULT_MAX_TOP_UPS, TOP_UP_INSTRUCTION,
+};
+use crate::error::WordcraftError;
+use crate::generator::{ChatMessage, CompletionRequest, FlashcardGenerator};
+use crate::json_extract::{balanced_objects, candidates, StreamingObjects};
+use crate::level::ProficiencyLevel;
+use crate::note_type::{plain_word, NoteKind};
+use crate::prompt::FlashcardSettings;
+use crate::prompt_pack::default_template;
+use crate::schema::{flashcard_response_schema, flashcard_response_schema_for, validate};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct FlashcardResponse {
+    pub deck_name: String,
+    pub cards: Vec<Flashcard>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Flashcard {
+    pub front: String,
+    pub back: String,
+    pub example: String,
+    pub example_translate: String,
+    // The word as it should be typed, asked for by the type-answer note type
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer: Option<String>,
+    // The example with the word as a {{c1::...}} deletion, asked for by the cloze note type
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloze: Option<String>,
+    // Media file names of the spoken front and example, see `tts::add_audio`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub front_audio: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub example_audio: Option<String>,
+}
+
+// Extra context for a generation run on top of the user's request
+#[derive(Debug, Clone)]
+pub struct GenerationOptions {
+    // Words the learner already has in Anki; the model is asked to skip them
+    // and any card whose normalized front matches one is dropped
+    pub exclude: Vec<String>,
+    // Summary of the learner's Anki history, see `rag::SelectionStrategy::summarize`
+    pub learner_profile: Option<String>,
+    // Words the learner profile names; they are left out of the list of
+    // excluded words in the prompt, which would only repeat them
+    pub learner_profile_words: Vec<String>,
+    // How many times an unparsable reply is sent back to the model for fixing
+    pub max_repair_attempts: usize,
+    // Note type the cards are made for; decides which card fields are asked for
+    pub note_kind: NoteKind,
+    // Exact number of cards; a longer deck is cut and a shorter one topped up.
+    // Without it the model is asked for at least DEFAULT_CARD_COUNT.
+    pub card_count: Option<usize>,
+    pub level: Option<ProficiencyLevel>,
+    // How many times a short deck is sent back to the model for more cards
+    pub max_top_ups: usize,
+    // System prompt of the target language, see `prompt_pack::PromptLibrary::template`
+    pub system_template: String,
+}
+
+impl Default for GenerationOptions {
+    fn default() -> Self {
+        GenerationOptions {
+            exclude: Vec::new(),
+            learner_profile: None,
+            learner_profile_words: Vec::new(),
+            max_repair_attempts: DEFAULT_MAX_REPAIR_ATTEMPTS,
+            note_kind: NoteKind::default(),
+            card_count: None,
+            level: None,
+            max_top_ups: DEFAULT_MAX_TOP_UPS,
+            system_template: default_template(),
+        }
+    }
+}
+
+// One round trip to the model
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerationAttempt {
+    pub reply: String,
+    // Why the reply could not be parsed, None when it was accepted
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct GenerationOutcome {
+    pub response: FlashcardResponse,
+    // Every reply in order: the first request, the repair requests, then the top-ups
+    pub attempts: Vec<GenerationAttempt>,
+    // True when no reply parsed and the cards were recovered one by one
+    pub salvaged: bool,
+}
+
+// Generate a deck, asking the model to fix replies that do not parse.
+// After `max_repair_attempts` failed repairs, well-formed card objects are
+// salvaged from the reply that yields the most of them. With a `card_count`,
+// a short deck is then topped up and a long one cut to size.
+pub async fn generate_flashcards(
+    generator: &dyn FlashcardGenerator,
+    user_input: &str,
+    options: &GenerationOptions,
+) -> Result<GenerationOutcome, WordcraftError> {
+    let request = initial_request(user_input, options);
+    let text = generator.complete(&request).await
+        .map_err(|err| invocation_error(generator, err))?;
+
+    finish_generation(generator, request, text, options).await
+}
+
+// Streaming variant of `generate_flashcards`: `on_card` sees every card as
+// soon as its object closes in the reply, before the reply is complete.
+// Cards the learner already knows are not passed on. The returned outcome is
+// the same as `generate_flashcards` gives, repairs included.
+pub async fn generate_flashcards_streaming<F>(
+    generator: &dyn FlashcardGenerator,
+    user_input: &str,
+    options: &GenerationOptions,
+    mut on_card: F,
+) -> Result<GenerationOutcome, WordcraftError>
+where
+    F: FnMut(&Flashcard) + Send,
+{
+    let request = initial_request(user_input, options);
+    let known: HashSet<String> = options.exclude.iter().map(|word| normalize_front(word)).collect();
+    let mut objects = StreamingObjects::new();
+
+    let mut on_chunk = |chunk: &str| {
+        for object in objects.push(chunk) {
+            if let Ok(card) = serde_json::from_str::<Flashcard>(&object) {
+                if !known.contains(&normalize_front(&card.front)) {
+                    on_card(&card);
+                }
+            }
+        }
+    };
+    let text = generator.complete_stream(&request, &mut on_chunk).await
+        .map_err(|err| invocation_error(generator, err))?;
+
+    finish_generation(generator, request, text, options).await
+}
+
+fn initial_request(user_input: &str, options: &GenerationOptions) -> CompletionRequest {
+    CompletionRequest {
+        messages: vec![
+            ChatMessage::system(build_system_message(options)),
+            ChatMessage::user(user_input),
+        ],
+        response_schema: Some(flashcard_response_schema_for(options.note_kind)),
+    }
+}
+
+fn invocation_error(generator: &dyn FlashcardGenerator, err: WordcraftError) -> WordcraftError {
+    err.context(format!("Error invoking {} ({})", generator.engine(), generator.model()))
+}
+
+// Parse the first reply, running the repair loop and the salvage fallback as
+// needed, then bring the deck to the requested size
+async fn finish_generation(
+    generator: &dyn FlashcardGenerator,
+    request: CompletionRequest,
+    first_reply: String,
+    options: &GenerationOptions,
+) -> Result<GenerationOutcome, WordcraftError> {
+    let mut outcome = repair_reply(generator, request.clone(), first_reply, options).await?;
+    if let Some(card_count) = options.card_count {
+        top_up(generator, &request, card_count, &mut outcome, options).await?;
+        outcome.response.cards.truncate(card_count);
+    }
+    Ok(outcome)
+}
+
+async fn repair_reply(
+    generator: &dyn FlashcardGenerator,
+    mut request: CompletionRequest,
+    first_reply: String,
+    options: &GenerationOptions,
+) -> Result<GenerationOutcome, WordcraftError> {
+    let schema = request.response_schema.clone().unwrap_or_else(flashcard_response_schema);
+    let mut attempts: Vec<GenerationAttempt> = Vec::new();
+    let mut text = first_reply;
+
+    loop {
+        match parse_flashcard_response(&text, &schema) {
+            Ok(mut response) => {
+                attempts.push(GenerationAttempt { reply: text, error: None });
+                remove_known_cards(&mut response, &options.exclude);
+                return Ok(GenerationOutcome { response, attempts, salvaged: false });
+            }
+            Err(err) => {
+                let error = err.to_string();
+                attempts.push(GenerationAttempt { reply: text.clone(), error: Some(error.clone()) });
+
+                if attempts.len() > options.max_repair_attempts {
+                    break;
+                }
+                request.messages.push(ChatMessage::assistant(text));
+                request.messages.push(ChatMessage::user(format!("{}\n{}", REPAIR_INSTRUCTION, error)));
+            }
+        }
+
+        text = generator.complete(&request).await
+            .map_err(|err| invocation_error(generator, err))?;
+    }
+
+    let salvaged = attempts.iter()
+        .filter_map(|attempt| salvage_flashcards(&attempt.reply))
+        .max_by_key(|response| response.cards.len());
+
+    match salvaged {
+        Some(mut response) => {
+            remove_known_cards(&mut response, &options.exclude);
+            Ok(GenerationOutcome { response, attempts, salvaged: true })
+        }
+        None => {
+            let last = attempts.last();
+            let last_error = last.and_then(|attempt| attempt.error.clone()).unwrap_or_default();
+            Err(WordcraftError::LlmMalformedOutput {
+                raw: last.map(|attempt| attempt.reply.clone()).unwrap_or_default(),
+                message: format!("No usable flashcards after {} attempts: {}", attempts.len(), last_error),
+            })
+        }
+    }
+}
+
+// Ask for the cards a deck is short of, showing the model the deck so far.
+// New cards are added when their front is neither known nor in the deck yet.
+async fn top_up(
+    generator: &dyn FlashcardGenerator,
+    request: &CompletionRequest,
+    card_count: usize,
+    outcome: &mut GenerationOutcome,
+    options: &GenerationOptions,
+) -> Result<(), WordcraftError> {
+    let schema = request.response_schema.clone().unwrap_or_else(flashcard_response_schema);
+    let mut seen: HashSet<String> = options.exclude.iter().map(|word| normalize_front(word)).collect();
+    outcome.response.cards.retain(|card| seen.insert(normalize_front(&card.front)));
+
+    for _ in 0..options.max_top_ups {
+        let missing = card_count.saturating_sub(outcome.response.cards.len());
+        if missing == 0 {
+            break;
+        }
+
+        let mut follow_up = request.clone();
+        follow_up.messages.push(ChatMessage::assistant(serde_json::to_string(&outcome.response)?));
+        follow_up.messages.push(ChatMessage::user(format!("{} {}", TOP_UP_INSTRUCTION, missing)));
+        let text = generator.complete(&follow_up).await
+            .map_err(|err| invocation_error(generator, err))?;
+
+        let more = match parse_flashcard_response(&text, &schema) {
+            Ok(more) => {
+                outcome.attempts.push(GenerationAttempt { reply: text, error: None });
+                Some(more)
+            }
+            Err(err) => {
+                let more = salvage_flashcards(&text);
+                outcome.attempts.push(GenerationAttempt { reply: text, error: Some(err.to_string()) });
+                more
+            }
+        };
+        let cards = more.map(|more| more.cards).unwrap_or_default();
+        outcome.response.cards.extend(cards.into_iter().filter(|card| seen.insert(normalize_front(&card.front))));
+    }
+    Ok(())
+}
+
+// Recover the well-formed card objects from a reply that does not parse as a
+// whole, e.g. a deck with one truncated or mistyped card. Returns None when
+// not a single card could be read.
+pub fn salvage_flashcards(text: &str) -> Option<FlashcardResponse> {
+    let cards: Vec<Flashcard> = balanced_objects(text)
+        .into_iter()
+        .filter_map(|object| serde_json::from_str::<Flashcard>(object).ok())
+        .collect();
+    if cards.is_empty() {
+        return None;
+    }
+
+    let deck_name = Regex::new(r#""deck_name"\s*:\s*("(?:[^"\\]|\\.)*")"#).ok()
+        .and_then(|re| re.captures(text))
+        .and_then(|captures| serde_json::from_str::<String>(&captures[1]).ok())
+        .unwrap_or_else(|| DEFAULT_DECK_NAME.to_string());
+
+    Some(FlashcardResponse { deck_name, cards })
+}
+
+// Try every JSON candidate in the reply (see `json_extract::candidates`) and
+// return the first one that matches the reply schema. When none does, the
+// error of the most promising candidate is returned: a schema violation over
+// a syntax error, so a repair prompt can point at the actual problem.
+pub fn parse_flashcard_response(text: &str, schema: &Value) -> Result<FlashcardResponse, WordcraftError> {
+    let mut schema_error: Option<String> = None;
+    let mut syntax_error: Option<String> = None;
+
+    for candidate in candidates(text) {
+        let value = match candidate_value(candidate) {
+            Ok(value) => value,
+            Err(err) => {
+                syntax_error.get_or_insert(err.to_string());
+                continue;
+            }
+        };
+
+        if let Err(errors) = validate(&value, schema) {
+            schema_error.get_or_insert(format!("Reply does not match the flashcard schema: {}", errors.join("; ")));
+            continue;
+        }
+
+        match serde_json::from_value(value) {
+            Ok(response) => return Ok(response),
+            Err(err) => {
+                schema_error.get_or_insert(err.to_string());
+            }
+        }
+    }
+
+    Err(WordcraftError::LlmMalformedOutput {
+        raw: text.to_string(),
+        message: schema_error.or(syntax_error).unwrap_or_else(|| NO_JSON_ERROR.to_string()),
+    })
+}
+
+const NO_JSON_ERROR: &str = "No JSON found in assistant's reply";
+
+// Parse a candidate span; a bare array of cards becomes a deck with the default name
+fn candidate_value(candidate: &str) -> Result<Value, serde_json::Error> {
+    let value: Value = serde_json::from_str(candidate)?;
+
+    Ok(match value {
+        Value::Array(cards) => serde_json::json!({ "deck_name": DEFAULT_DECK_NAME, "cards": cards }),
+        other => other,
+    })
+}
+
+// The user message describing what to generate
+pub fn generation_prompt(settings: &FlashcardSettings) -> String {
+    let mut prompt = format!(
+        "Native Language: {}\nTarget Language: {}\nTopic: {}\n",
+        settings.native_language,
+        settings.target_language,
+        settings.topic
+    );
+    if let Some(count) = settings.card_count {
+        prompt.push_str(&format!("Number of cards: {}\n", count));
+    }
+    if let Some(level) = settings.level {
+        prompt.push_str(&format!("Level: {}\n", level));
+    }
+    prompt
+}
+
+// Fill in the card count and level of the system prompt, then append the note
+// type's field instruction, the learner profile and the exclusion list. The
+// exclusion list is capped to keep the prompt small.
+pub fn build_system_message(options: &GenerationOptions) -> String {
+    let card_count = match options.card_count {
+        Some(count) => format!("exactly {}", count),
+        None => format!("at least {}", DEFAULT_CARD_COUNT),
+    };
+    let level = match options.level {
+        Some(level) => format!(
+            "The student's level is {} ({}). Choose words and write examples that suit that level.",
+            level,
+            level.description()
+        ),
+        None => String::new(),
+    };
+    let mut message = render_template(&options.system_template, &[("card_count", &card_count), ("level", &level)]);
+
+    if let Some(instruction) = options.note_kind.note_type().prompt_instruction {
+        message.push_str("\n\n");
+        message.push_str(instruction);
+    }
+
+    if let Some(profile) = &options.learner_profile {
+        message.push_str("\n\n");
+        message.push_str(profile);
+    }
+
+    // Each word once, so the same word from the deck and the vocabulary index
+    // does not use up the limit twice
+    let mut listed: HashSet<String> = match options.learner_profile {
+        Some(_) => options.learner_profile_words.iter().map(|word| normalize_front(word)).collect(),
+        None => HashSet::new(),
+    };
+    let words: Vec<&str> = options.exclude.iter()
+        .filter(|word| listed.insert(normalize_front(word)))
+        .take(MAX_EXCLUDED_WORDS_IN_PROMPT)
+        .map(|word| word.as_str())
+        .collect();
+    if !words.is_empty() {
+        message.push_str("\n\n");
+        message.push_str(EXCLUSION_INSTRUCTION);
+        message.push('\n');
+        message.push_str(&words.join("\n"));
+    }
+
+    message
+}
+
+// Replace every {name} in `template`. A line that was only a placeholder and
+// renders empty is dropped, so optional parts leave no blank line behind.
+pub fn render_template(template: &str, values: &[(&str, &str)]) -> String {
+    template.split('\n')
+        .filter_map(|line| {
+            let mut rendered = line.to_string();
+            for (name, value) in values {
+                rendered = rendered.replace(&format!("{{{}}}", name), value);
+            }
+            (rendered.trim().is_empty() == line.trim().is_empty()).then_some(rendered)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Drop cards whose front the learner already has
+pub fn remove_known_cards(response: &mut FlashcardResponse, known: &[String]) {
+    if known.is_empty() {
+        return;
+    }
+
+    let known: HashSet<String> = known.iter().map(|word| normalize_front(word)).collect();
+    response.cards.retain(|card| !known.contains(&normalize_front(&card.front)));
+}
+
+// Normalize a card front for comparison: drop readings in parentheses,
+// collapse whitespace and lowercase, so "家 (いえ) (ie)" matches "家"
+pub fn normalize_front(front: &str) -> String {
+    plain_word(front).to_lowercase()
+}
+
+// Return the JSON of the first candidate in the reply that deserializes into
+// a `FlashcardResponse`, falling back to the first candidate at all so the
+// caller can report why it does not parse
+pub fn extract_json(text: &str) -> Result<String, WordcraftError> {
+    let candidates = candidates(text);
+
+    let deck = candidates.iter().find_map(|candidate| {
+        let value = candidate_value(candidate).ok()?;
+        serde_json::from_value::<FlashcardResponse>(value.clone()).ok()?;
+        Some(value.to_string())
+    });
+
+    match (deck, candidates.first()) {
+        (Some(deck), _) => Ok(deck),
+        (None, Some(candidate)) => Ok(candidate.to_string()),
+        (None, None) => Err(WordcraftError::LlmMalformedOutput { raw: text.to_string(), message: NO_JSON_ERROR.to_string() }),
+    }
+}
@@ -0,0 +1,292 @@
+// LLM backends behind a single `FlashcardGenerator` trait.
+//
+// A generator only turns a conversation into the model's raw reply; prompt
+// building and parsing stay in `generation::generate_flashcards`, so every
+// backend (including ones supplied by library users) gets the same handling.
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+use crate::config::Config;
+use crate::error::WordcraftError;
+use crate::generation::{Flashcard, FlashcardResponse};
+use crate::ollama::{OllamaConfig, OllamaGenerator};
+use crate::openai::{OpenAIConfig, OpenAIGenerator};
+use crate::recording::{RecordingGenerator, ReplayGenerator};
+
+pub const DEFAULT_OPENAI_MODEL: &str = "gpt-4o-mini";
+pub const DEFAULT_OLLAMA_MODEL: &str = "gemma2";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn system(content: impl Into<String>) -> Self {
+        ChatMessage { role: Role::System, content: content.into() }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        ChatMessage { role: Role::User, content: content.into() }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        ChatMessage { role: Role::Assistant, content: content.into() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompletionRequest {
+    pub messages: Vec<ChatMessage>,
+    // JSON schema the reply must follow, for backends with structured output
+    pub response_schema: Option<Value>,
+}
+
+impl CompletionRequest {
+    pub fn new(messages: Vec<ChatMessage>) -> Self {
+        CompletionRequest { messages, response_schema: None }
+    }
+}
+
+// Receives the pieces of a streamed reply in order
+pub type ChunkHandler<'a> = dyn FnMut(&str) + Send + 'a;
+
+#[async_trait]
+pub trait FlashcardGenerator: Send + Sync {
+    /// Short identifier of the backend, e.g. `"openai"`.
+    fn engine(&self) -> &str;
+
+    /// Model the backend talks to.
+    fn model(&self) -> &str;
+
+    /// Send the conversation to the model and return its raw reply.
+    async fn complete(&self, request: &CompletionRequest) -> Result<String, WordcraftError>;
+
+    /// Like `complete`, but hands each piece of the reply to `on_chunk` as it
+    /// arrives. Backends without streaming deliver the whole reply as one chunk.
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+        on_chunk: &mut ChunkHandler<'_>,
+    ) -> Result<String, WordcraftError> {
+        let reply = self.complete(request).await?;
+        on_chunk(&reply);
+        Ok(reply)
+    }
+}
+
+// Read a streamed HTTP reply one line at a time. `decode_line` turns a
+// non-empty line into the piece of the reply it carries, or None for lines
+// that carry none; each piece goes to `on_chunk` and the joined reply is
+// returned. Lines are split on bytes, so a character cut in two by the
+// network is decoded once both halves have arrived.
+pub(crate) async fn read_line_stream<F>(
+    mut response: reqwest::Response,
+    on_chunk: &mut ChunkHandler<'_>,
+    mut decode_line: F,
+) -> Result<String, WordcraftError>
+where
+    F: FnMut(&str) -> Result<Option<String>, WordcraftError> + Send,
+{
+    let mut reply = String::new();
+    let mut pending: Vec<u8> = Vec::new();
+
+    let mut handle_line = |line: &[u8], reply: &mut String| -> Result<(), WordcraftError> {
+        let line = std::str::from_utf8(line).map_err(WordcraftError::llm_transport)?.trim();
+        if line.is_empty() {
+            return Ok(());
+        }
+        if let Some(piece) = decode_line(line)?.filter(|piece| !piece.is_empty()) {
+            on_chunk(&piece);
+            reply.push_str(&piece);
+        }
+        Ok(())
+    };
+
+    while let Some(bytes) = response.chunk().await.map_err(WordcraftError::llm_transport)? {
+        pending.extend_from_slice(&bytes);
+        while let Some(newline) = pending.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = pending.drain(..=newline).collect();
+            handle_line(&line, &mut reply)?;
+        }
+    }
+    handle_line(&pending, &mut reply)?;
+
+    Ok(reply)
+}
+
+// Deterministic in-process generator for tests and offline runs.
+//
+// By default it answers every request with a deck built from the "Topic:" line
+// of the last user message. Scripted replies are returned in order instead,
+// with the last one repeated once the script runs out.
+#[derive(Debug, Clone, Default)]
+pub struct FakeGenerator {
+    replies: Vec<String>,
+    calls: Arc<Mutex<Vec<Vec<ChatMessage>>>>,
+}
+
+pub const FAKE_CARD_COUNT: usize = 15;
+
+// Size in characters of the pieces a streamed fake reply is cut into
+const FAKE_CHUNK_CHARS: usize = 7;
+
+impl FakeGenerator {
+    pub fn new() -> Self {
+        FakeGenerator::default()
+    }
+
+    pub fn with_replies<S: Into<String>>(replies: Vec<S>) -> Self {
+        FakeGenerator {
+            replies: replies.into_iter().map(Into::into).collect(),
+            ..Default::default()
+        }
+    }
+
+    // Every conversation the generator has been asked to complete
+    pub fn calls(&self) -> Vec<Vec<ChatMessage>> {
+        self.calls.lock().map(|calls| calls.clone()).unwrap_or_default()
+    }
+
+    fn canned_reply(messages: &[ChatMessage]) -> String {
+        let input = messages.iter()
+            .rev()
+            .find(|message| message.role == Role::User)
+            .map(|message| message.content.as_str())
+            .unwrap_or_default();
+        let field = |name: &str, default: &str| {
+            input.lines()
+                .find_map(|line| line.strip_prefix(name))
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+                .unwrap_or_else(|| default.to_string())
+        };
+        let topic = field("Topic:", "Vocabulary");
+        let target_language = field("Target Language:", "Japanese");
+
+        let response = FlashcardResponse {
+            deck_name: format!("{} in {}", topic, target_language),
+            cards: (1..=FAKE_CARD_COUNT)
+                .map(|i| Flashcard {
+                    front: format!("{} {} {}", target_language, topic, i),
+                    back: format!("{} {}", topic, i),
+                    example: format!("Example {} about {}", i, topic),
+                    example_translate: format!("Example translation {} about {}", i, topic),
+                    ..Default::default()
+                })
+                .collect(),
+        };
+
+        serde_json::to_string_pretty(&response).unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl FlashcardGenerator for FakeGenerator {
+    fn engine(&self) -> &str {
+        "fake"
+    }
+
+    fn model(&self) -> &str {
+        "fake"
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<String, WordcraftError> {
+        let messages = &request.messages;
+        let call_index = {
+            let mut calls = self.calls.lock()
+                .map_err(|_| WordcraftError::llm_transport("FakeGenerator call log is poisoned"))?;
+            calls.push(messages.clone());
+            calls.len() - 1
+        };
+
+        match self.replies.get(call_index).or(self.replies.last()) {
+            Some(reply) => Ok(reply.clone()),
+            None => Ok(FakeGenerator::canned_reply(messages)),
+        }
+    }
+
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+        on_chunk: &mut ChunkHandler<'_>,
+    ) -> Result<String, WordcraftError> {
+        let reply = self.complete(request).await?;
+        let chars: Vec<char> = reply.chars().collect();
+        for chunk in chars.chunks(FAKE_CHUNK_CHARS) {
+            on_chunk(&chunk.iter().collect::<String>());
+        }
+        Ok(reply)
+    }
+}
+
+// Which backend to use and how to reach it
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineConfig {
+    OpenAI(OpenAIConfig),
+    Ollama(OllamaConfig),
+    Fake,
+}
+
+impl EngineConfig {
+    // The configured engine (openai, openai-compatible, ollama or fake) and
+    // the backend's settings
+    pub fn from_config(config: &Config) -> Result<EngineConfig, WordcraftError> {
+        match config.engine.trim().to_lowercase().as_str() {
+            "openai" | "openai-compatible" => {
+                let openai = OpenAIConfig {
+                    api_key: config.openai.api_key.clone().filter(|key| !key.trim().is_empty()),
+                    ..config.openai.clone()
+                };
+                if openai.api_key.is_none() && openai.is_default_endpoint() {
+                    return Err(WordcraftError::Config(
+                        "OPEN_API_KEY must be set when using the OpenAI API (or set OPENAI_BASE_URL for a local server)".to_string(),
+                    ));
+                }
+                Ok(EngineConfig::OpenAI(openai))
+            }
+            "ollama" => Ok(EngineConfig::Ollama(config.ollama.clone())),
+            "fake" => Ok(EngineConfig::Fake),
+            other => Err(WordcraftError::Config(format!(
+                "Unsupported engine '{}'. Expected one of: openai, openai-compatible, ollama, fake",
+                other
+            ))),
+        }
+    }
+
+    pub fn build(&self) -> Result<Box<dyn FlashcardGenerator>, WordcraftError> {
+        Ok(match self {
+            EngineConfig::OpenAI(config) => Box::new(OpenAIGenerator::new(config.clone())?),
+            EngineConfig::Ollama(config) => Box::new(OllamaGenerator::new(config.clone())),
+            EngineConfig::Fake => Box::new(FakeGenerator::new()),
+        })
+    }
+}
+
+// The generator for a run: the configured engine, with its completions
+// recorded when fixtures.record is set, or the recorded completions instead
+// of a model when fixtures.replay is
+pub fn build_generator(config: &Config) -> Result<Box<dyn FlashcardGenerator>, WordcraftError> {
+    if let Some(dir) = &config.fixtures.replay {
+        return Ok(Box::new(ReplayGenerator::load(dir)?));
+    }
+
+    let generator = config.engine_config()
+        .and_then(|engine| engine.build())
+        .map_err(|err| err.context("Invalid engine configuration"))?;
+    Ok(match &config.fixtures.record {
+        Some(dir) => Box::new(RecordingGenerator::new(generator, dir)),
+        None => generator,
+    })
+}
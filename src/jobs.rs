@@ -0,0 +1,380 @@
+// Batch generation from a job file.
+//
+// A job file lists the decks to generate, as TOML or as the same structure
+// in JSON:
+//
+//   concurrency = 3
+//
+//   [defaults]
+//   native = "English"
+//   target = "Japanese"
+//   count = 20
+//   level = "N4"
+//   tags = ["onboarding"]
+//
+//   [[job]]
+//   topic = "Food"
+//   deck = "Japanese::Food"
+//   note_type = "cloze"
+//
+// Finished jobs are recorded in a state file next to the job file, so a
+// rerun after a failure only does the jobs that did not finish.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::anki_adapter::{insert_cards, AnkiAdapter};
+use crate::cache::{generate_flashcards_cached, ResponseCache};
+use crate::error::WordcraftError;
+use crate::generator::FlashcardGenerator;
+use crate::generation::{generation_prompt, GenerationOptions};
+use crate::level::ProficiencyLevel;
+use crate::note_type::NoteKind;
+use crate::prompt::{FlashcardSettings, SettingsOverrides};
+use crate::prompt_pack::PromptLibrary;
+use crate::tts::TtsEngine;
+
+pub const DEFAULT_JOB_CONCURRENCY: usize = 2;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobFile {
+    // How many jobs run at once
+    pub concurrency: Option<usize>,
+    // Values for every job that does not set its own
+    #[serde(default)]
+    pub defaults: JobEntry,
+    #[serde(default, rename = "job")]
+    pub jobs: Vec<JobEntry>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobEntry {
+    pub topic: Option<String>,
+    pub native: Option<String>,
+    pub target: Option<String>,
+    // Defaults to "<topic> in <target>"
+    pub deck: Option<String>,
+    pub count: Option<usize>,
+    pub level: Option<ProficiencyLevel>,
+    pub note_type: Option<NoteKind>,
+    // Added to the defaults' tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+// A job with the defaults applied
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub settings: FlashcardSettings,
+    pub deck_name: String,
+    pub tags: Vec<String>,
+}
+
+impl Job {
+    // Identifies the job in the state file: everything that changes the
+    // cards it makes
+    pub fn key(&self) -> String {
+        let settings = &self.settings;
+        format!(
+            "{}|{}|{}|{}|{}|{}",
+            self.deck_name,
+            settings.target_language,
+            settings.topic,
+            settings.note_kind,
+            settings.card_count.map(|count| count.to_string()).unwrap_or_default(),
+            settings.level.map(|level| level.to_string()).unwrap_or_default(),
+        )
+    }
+}
+
+impl JobFile {
+    // .json files are read as JSON, anything else as TOML
+    pub fn load(path: &Path) -> Result<JobFile, WordcraftError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| WordcraftError::Config(format!("Could not read job file {}: {}", path.display(), err)))?;
+        let is_json = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+
+        let parsed = if is_json {
+            serde_json::from_str(&text).map_err(|err| err.to_string())
+        } else {
+            toml::from_str(&text).map_err(|err| err.to_string())
+        };
+        parsed.map_err(|err| WordcraftError::Config(format!("Invalid job file {}: {}", path.display(), err)))
+    }
+
+    pub fn jobs(&self) -> Result<Vec<Job>, WordcraftError> {
+        self.jobs.iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let topic = entry.topic.clone()
+                    .or_else(|| self.defaults.topic.clone())
+                    .filter(|topic| !topic.trim().is_empty())
+                    .ok_or_else(|| WordcraftError::Config(format!("Job {} has no topic", index + 1)))?;
+                let given = SettingsOverrides {
+                    native_language: entry.native.clone().or_else(|| self.defaults.native.clone()),
+                    target_language: entry.target.clone().or_else(|| self.defaults.target.clone()),
+                    topic: Some(topic),
+                    deck_name: None,
+                    note_kind: entry.note_type.or(self.defaults.note_type),
+                    card_count: entry.count.or(self.defaults.count),
+                    level: entry.level.or(self.defaults.level),
+                };
+                let mut settings = FlashcardSettings::resolve(given, false).map_err(WordcraftError::Config)?;
+                let deck_name = entry.deck.clone()
+                    .unwrap_or_else(|| format!("{} in {}", settings.topic, settings.target_language));
+                settings.deck_name = Some(deck_name.clone());
+
+                let mut seen = HashSet::new();
+                let tags = self.defaults.tags.iter()
+                    .chain(&entry.tags)
+                    .filter(|tag| seen.insert(tag.as_str()))
+                    .cloned()
+                    .collect();
+
+                Ok(Job {
+                    settings,
+                    deck_name,
+                    tags,
+                })
+            })
+            .collect()
+    }
+}
+
+// "jobs.toml" -> "jobs.toml.state.json"
+pub fn state_path(job_file: &Path) -> PathBuf {
+    let mut name = job_file.file_name().unwrap_or_default().to_os_string();
+    name.push(".state.json");
+    job_file.with_file_name(name)
+}
+
+// Jobs finished by earlier runs, by `Job::key`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobState {
+    pub completed: BTreeMap<String, JobCounts>,
+}
+
+impl JobState {
+    // A missing state file means nothing has run yet
+    pub fn load(path: &Path) -> Result<JobState, WordcraftError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Ok(serde_json::from_str(&text)
+                .map_err(|err| WordcraftError::Config(format!("Invalid job state {}: {}", path.display(), err)))?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(JobState::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    // Written to a temporary file first, so an interrupted save never
+    // leaves a state file that cannot be read
+    pub fn save(&self, path: &Path) -> Result<(), WordcraftError> {
+        let partial = path.with_extension("json.partial");
+        std::fs::write(&partial, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&partial, path)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobCounts {
+    pub added: usize,
+    pub duplicates: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum JobStatus {
+    Done(JobCounts),
+    // Finished by an earlier run
+    Skipped(JobCounts),
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobReport {
+    pub deck: String,
+    pub topic: String,
+    #[serde(flatten)]
+    pub status: JobStatus,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobSummary {
+    pub jobs: Vec<JobReport>,
+}
+
+impl JobSummary {
+    pub fn failed(&self) -> usize {
+        self.jobs.iter().filter(|job| matches!(job.status, JobStatus::Failed { .. })).count()
+    }
+
+    pub fn print(&self) {
+        println!("\nSummary:");
+        for job in &self.jobs {
+            match &job.status {
+                JobStatus::Done(counts) => println!(
+                    "  done     {}: {} added, {} duplicates, {} failed",
+                    job.deck, counts.added, counts.duplicates, counts.failed
+                ),
+                JobStatus::Skipped(_) => println!("  skipped  {}: finished in an earlier run", job.deck),
+                JobStatus::Failed { error } => println!("  failed   {}: {}", job.deck, error),
+            }
+        }
+        let added: usize = self.jobs.iter()
+            .filter_map(|job| match &job.status {
+                JobStatus::Done(counts) => Some(counts.added),
+                _ => None,
+            })
+            .sum();
+        println!("{} jobs, {} failed, {} cards added.", self.jobs.len(), self.failed(), added);
+    }
+}
+
+// Runs jobs against one generator and one Anki
+pub struct JobRunner {
+    pub generator: Arc<dyn FlashcardGenerator>,
+    // Prompt templates, picked per job by its target language
+    pub prompts: Arc<PromptLibrary>,
+    pub adapter: Arc<AnkiAdapter>,
+    pub concurrency: usize,
+    // Words to skip in every job, e.g. from KNOWN_WORDS_APKG
+    pub exclude: Vec<String>,
+    pub tts: Option<Arc<dyn TtsEngine>>,
+    // Decks generated recently for the same job are reused from here
+    pub cache: Option<Arc<ResponseCache>>,
+}
+
+impl JobRunner {
+    // Run every job not yet in the state file, at most `concurrency` at a
+    // time. The state file is updated as each job finishes, so an
+    // interrupted run loses at most the jobs in flight.
+    pub async fn run(&self, jobs: Vec<Job>, state_path: &Path) -> Result<JobSummary, WordcraftError> {
+        let mut state = JobState::load(state_path)?;
+
+        // Models are set up once up front rather than raced by the jobs
+        let mut kinds: Vec<NoteKind> = Vec::new();
+        for job in &jobs {
+            if !kinds.contains(&job.settings.note_kind) {
+                kinds.push(job.settings.note_kind);
+            }
+        }
+        for kind in kinds {
+            self.adapter.ensure_note_type_exists(kind).await?;
+        }
+
+        let total = jobs.len();
+        let mut statuses: Vec<Option<JobStatus>> = vec![None; total];
+        let semaphore = Arc::new(Semaphore::new(self.concurrency.max(1)));
+        let mut running = JoinSet::new();
+
+        for (index, job) in jobs.iter().enumerate() {
+            if let Some(counts) = state.completed.get(&job.key()) {
+                statuses[index] = Some(JobStatus::Skipped(*counts));
+                continue;
+            }
+
+            let job = job.clone();
+            let generator = Arc::clone(&self.generator);
+            let system_template = self.prompts.template(&job.settings.target_language);
+            let adapter = Arc::clone(&self.adapter);
+            let tts = self.tts.clone();
+            let cache = self.cache.clone();
+            let exclude = self.exclude.clone();
+            let semaphore = Arc::clone(&semaphore);
+            running.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                let result = run_job(generator.as_ref(), &adapter, tts.as_deref(), cache.as_deref(), &job, exclude, system_template).await;
+                (index, result)
+            });
+        }
+
+        let mut finished = statuses.iter().filter(|status| status.is_some()).count();
+        while let Some(joined) = running.join_next().await {
+            let (index, result) = joined.map_err(|err| std::io::Error::other(format!("Job task failed: {}", err)))?;
+            let job = &jobs[index];
+            finished += 1;
+
+            // Only a job that added its whole deck is finished; anything
+            // else is generated again by the next run. A deck that already
+            // has every word the model made is finished too.
+            let result = result.map_err(|err| err.to_string()).and_then(|counts| match counts.failed {
+                0 => Ok(counts),
+                failed => Err(format!("{} cards added, {} could not be added", counts.added, failed)),
+            });
+            statuses[index] = Some(match result {
+                Ok(counts) => {
+                    println!("[{}/{}] {}: {} cards added.", finished, total, job.deck_name, counts.added);
+                    state.completed.insert(job.key(), counts);
+                    state.save(state_path)?;
+                    JobStatus::Done(counts)
+                }
+                Err(error) => {
+                    eprintln!("[{}/{}] {} failed: {}", finished, total, job.deck_name, error);
+                    JobStatus::Failed { error }
+                }
+            });
+        }
+
+        Ok(JobSummary {
+            jobs: jobs.iter()
+                .zip(statuses)
+                .map(|(job, status)| JobReport {
+                    deck: job.deck_name.clone(),
+                    topic: job.settings.topic.clone(),
+                    status: status.unwrap_or_else(|| JobStatus::Failed { error: "Job did not run".to_string() }),
+                })
+                .collect(),
+        })
+    }
+}
+
+// Generate one deck and add it to Anki
+async fn run_job(
+    generator: &dyn FlashcardGenerator,
+    adapter: &AnkiAdapter,
+    tts: Option<&dyn TtsEngine>,
+    cache: Option<&ResponseCache>,
+    job: &Job,
+    mut exclude: Vec<String>,
+    system_template: String,
+) -> Result<JobCounts, WordcraftError> {
+    let known = adapter.fetch_deck_fronts(&job.deck_name).await?;
+    exclude.extend(known);
+    let options = GenerationOptions {
+        exclude,
+        note_kind: job.settings.note_kind,
+        card_count: job.settings.card_count,
+        level: job.settings.level,
+        system_template,
+        ..GenerationOptions::default()
+    };
+
+    let prompt = generation_prompt(&job.settings);
+    let mut response = generate_flashcards_cached(generator, cache, &prompt, &options).await?;
+    if response.cards.is_empty() {
+        return Ok(JobCounts::default());
+    }
+
+    adapter.create_deck(&job.deck_name).await?;
+    let report = insert_cards(
+        adapter,
+        &mut response.cards,
+        &job.deck_name,
+        job.settings.note_kind,
+        &job.tags,
+        &job.settings.target_language,
+        tts,
+    ).await?;
+
+    Ok(JobCounts {
+        added: report.added().len(),
+        duplicates: report.duplicates().len(),
+        failed: report.failures().len(),
+    })
+}
@@ -0,0 +1,150 @@
+// Locating JSON inside a model reply.
+//
+// Models wrap their JSON in prose, Markdown fences, or both, and the prose can
+// contain braces of its own. Instead of guessing one span, the reply is turned
+// into a list of candidate spans, best guesses first, and the caller keeps the
+// first one that deserializes.
+
+// Candidate JSON spans in the order they should be tried: balanced spans
+// inside ``` fenced blocks first, then every balanced span in the whole reply
+pub fn candidates(text: &str) -> Vec<&str> {
+    let mut candidates: Vec<&str> = Vec::new();
+
+    let fenced = fenced_blocks(text).into_iter().flat_map(balanced_spans);
+    for span in fenced.chain(balanced_spans(text)) {
+        if !candidates.contains(&span) {
+            candidates.push(span);
+        }
+    }
+
+    candidates
+}
+
+// Contents of ``` fenced blocks; the language tag after the opening fence is
+// skipped and an unterminated block runs to the end of the reply
+pub fn fenced_blocks(text: &str) -> Vec<&str> {
+    let mut blocks = Vec::new();
+    let mut rest = text;
+
+    while let Some(open) = rest.find("```") {
+        let after_fence = &rest[open + 3..];
+        let body_start = after_fence.find('\n').map(|newline| newline + 1).unwrap_or(after_fence.len());
+        let body = &after_fence[body_start..];
+
+        match body.find("```") {
+            Some(close) => {
+                blocks.push(&body[..close]);
+                rest = &body[close + 3..];
+            }
+            None => {
+                blocks.push(body);
+                break;
+            }
+        }
+    }
+
+    blocks
+}
+
+// Every balanced `{...}` and `[...]` span at any depth, ordered by where it
+// starts, so an outer object comes before the objects nested inside it.
+pub fn balanced_spans(text: &str) -> Vec<&str> {
+    let mut scanner = BracketScanner::default();
+    let mut spans: Vec<(usize, &str)> = Vec::new();
+
+    for (index, c) in text.char_indices() {
+        if let Some(start) = scanner.step(index, c) {
+            spans.push((start, &text[start..=index]));
+        }
+    }
+
+    spans.sort_by_key(|(start, _)| *start);
+    spans.into_iter().map(|(_, span)| span).collect()
+}
+
+// Incremental scanner for a streamed reply. Each object is returned as soon as
+// it closes inside an array, which for a deck means every card of
+// `{"cards": [{...}, {...}]}` (or of a bare `[{...}]`) as it completes.
+#[derive(Debug, Default)]
+pub struct StreamingObjects {
+    buffer: String,
+    scanner: BracketScanner,
+}
+
+impl StreamingObjects {
+    pub fn new() -> Self {
+        StreamingObjects::default()
+    }
+
+    // Feed the next piece of the reply and get the array elements it completed
+    pub fn push(&mut self, chunk: &str) -> Vec<String> {
+        let offset = self.buffer.len();
+        self.buffer.push_str(chunk);
+
+        let mut closed = Vec::new();
+        for (index, c) in chunk.char_indices() {
+            let index = offset + index;
+            if let Some(start) = self.scanner.step(index, c) {
+                if c == '}' && self.scanner.innermost() == Some('[') {
+                    closed.push(self.buffer[start..=index].to_string());
+                }
+            }
+        }
+
+        closed
+    }
+
+    // Everything received so far
+    pub fn text(&self) -> &str {
+        &self.buffer
+    }
+}
+
+// Tracks open brackets one character at a time. Brackets inside JSON strings
+// (including escaped quotes) are ignored, and a closing bracket that does not
+// match the innermost open one is skipped.
+#[derive(Debug, Default)]
+struct BracketScanner {
+    open: Vec<(char, usize)>,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl BracketScanner {
+    // Returns the start of the span closed by `c`, if any
+    fn step(&mut self, index: usize, c: char) -> Option<usize> {
+        if self.in_string {
+            match c {
+                _ if self.escaped => self.escaped = false,
+                '\\' => self.escaped = true,
+                '"' => self.in_string = false,
+                _ => {}
+            }
+            return None;
+        }
+
+        match c {
+            // Quotes in prose outside any span are apostrophes or citations, not JSON strings
+            '"' if !self.open.is_empty() => self.in_string = true,
+            '{' | '[' => self.open.push((c, index)),
+            '}' | ']' => {
+                let expected = if c == '}' { '{' } else { '[' };
+                if self.innermost() == Some(expected) {
+                    return self.open.pop().map(|(_, start)| start);
+                }
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    fn innermost(&self) -> Option<char> {
+        self.open.last().map(|(opener, _)| *opener)
+    }
+}
+
+// Balanced `{...}` spans only, used to pick individual cards out of a reply
+pub fn balanced_objects(text: &str) -> Vec<&str> {
+    balanced_spans(text).into_iter().filter(|span| span.starts_with('{')).collect()
+}
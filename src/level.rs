@@ -0,0 +1,108 @@
+// Proficiency levels a deck can be aimed at.
+//
+// A level is given on the scale the learner knows: CEFR (A1-C2) for most
+// languages, JLPT (N5-N1) for Japanese, HSK (1-9) for Chinese and TOPIK (1-6)
+// for Korean. The model gets the level's name and a rough description, since
+// not every model knows every scale equally well.
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ProficiencyLevel {
+    // A1 to C2 as 1 to 6
+    Cefr(u8),
+    // N5, the easiest, to N1, kept as the number after the N
+    Jlpt(u8),
+    // HSK 3.0 levels 1 to 9; 1 to 6 match the older HSK
+    Hsk(u8),
+    // TOPIK 1 to 6
+    Topik(u8),
+}
+
+const CEFR_LEVELS: [&str; 6] = ["A1", "A2", "B1", "B2", "C1", "C2"];
+
+impl ProficiencyLevel {
+    pub fn framework(&self) -> &'static str {
+        match self {
+            ProficiencyLevel::Cefr(_) => "CEFR",
+            ProficiencyLevel::Jlpt(_) => "JLPT",
+            ProficiencyLevel::Hsk(_) => "HSK",
+            ProficiencyLevel::Topik(_) => "TOPIK",
+        }
+    }
+
+    // How far along the learner is, for the prompt
+    pub fn description(&self) -> &'static str {
+        let step = match *self {
+            ProficiencyLevel::Cefr(level) | ProficiencyLevel::Topik(level) => level,
+            ProficiencyLevel::Jlpt(level) => 6u8.saturating_sub(level),
+            ProficiencyLevel::Hsk(level) => level.min(6),
+        };
+        match step {
+            0 | 1 => "beginner",
+            2 => "elementary",
+            3 => "intermediate",
+            4 => "upper intermediate",
+            5 => "advanced",
+            _ => "proficient",
+        }
+    }
+}
+
+impl fmt::Display for ProficiencyLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // A level built off the scale is shown as its number
+            ProficiencyLevel::Cefr(level) => match (*level as usize).checked_sub(1).and_then(|index| CEFR_LEVELS.get(index)) {
+                Some(name) => write!(f, "CEFR {}", name),
+                None => write!(f, "CEFR {}", level),
+            },
+            ProficiencyLevel::Jlpt(level) => write!(f, "JLPT N{}", level),
+            ProficiencyLevel::Hsk(level) => write!(f, "HSK {}", level),
+            ProficiencyLevel::Topik(level) => write!(f, "TOPIK {}", level),
+        }
+    }
+}
+
+// Accepts "B1", "cefr b1", "N4", "JLPT N4", "HSK3", "hsk 3", "TOPIK-2" and the like
+impl FromStr for ProficiencyLevel {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let normalized: String = value.chars()
+            .filter(|c| !c.is_whitespace() && *c != '-' && *c != '_')
+            .collect::<String>()
+            .to_uppercase();
+        let number = |digits: &str, range: std::ops::RangeInclusive<u8>| {
+            digits.parse::<u8>().ok().filter(|level| range.contains(level))
+        };
+
+        let level = if let Some(digits) = normalized.strip_prefix("HSK") {
+            number(digits, 1..=9).map(ProficiencyLevel::Hsk)
+        } else if let Some(digits) = normalized.strip_prefix("TOPIK") {
+            number(digits, 1..=6).map(ProficiencyLevel::Topik)
+        } else if let Some(digits) = normalized.strip_prefix("JLPT").unwrap_or(&normalized).strip_prefix('N') {
+            number(digits, 1..=5).map(ProficiencyLevel::Jlpt)
+        } else {
+            let cefr = normalized.strip_prefix("CEFR").unwrap_or(&normalized);
+            CEFR_LEVELS.iter()
+                .position(|level| *level == cefr)
+                .map(|index| ProficiencyLevel::Cefr(index as u8 + 1))
+        };
+
+        level.ok_or_else(|| format!(
+            "Unknown level '{}'. Expected a CEFR level (A1-C2), JLPT N5-N1, HSK 1-9 or TOPIK 1-6",
+            value.trim()
+        ))
+    }
+}
+
+impl TryFrom<String> for ProficiencyLevel {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
@@ -1,9 +1,31 @@
 pub mod anki_adapter;
-pub mod langchain;
+pub mod anki_connect;
+pub mod apkg;
+pub mod apkg_reader;
+pub mod cache;
+pub mod cli;
+pub mod config;
+pub mod error;
+pub mod fake_anki;
+pub mod generation;
+pub mod generator;
+pub mod jobs;
+pub mod json_extract;
+pub mod level;
+pub mod note_type;
+pub mod ollama;
+pub mod openai;
 pub mod prompt;
+pub mod prompt_pack;
+pub mod rag;
+pub mod recording;
+pub mod review;
+pub mod schema;
+pub mod tts;
 pub mod constant;
 
 #[cfg(test)]
 mod test_utils;
 
-pub use langchain::{Flashcard, FlashcardResponse};
\ No newline at end of file
+pub use error::WordcraftError;
+pub use generation::{Flashcard, FlashcardResponse};
\ No newline at end of file
@@ -1,79 +1,20 @@
-use std::process;
+use clap::Parser;
 use dotenv::dotenv;
-use tokio::time::{timeout, Duration};
+use std::process;
 
-use autoflashcard::anki_adapter::AnkiAdapter;
-use autoflashcard::langchain::generate_flashcards;
-use autoflashcard::prompt::FlashcardSettings;
-use autoflashcard::prompt::ask_for_confirmation;
+use autoflashcard::cli::{run, Cli};
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() {
     dotenv().ok();
+    let cli = Cli::parse();
 
-    let adapter = AnkiAdapter::new().unwrap_or_else(|err| {
-        eprintln!("Problem creating new adapter: {err}");
-        process::exit(1);
-    });
-
-    // Check if Anki Connect is available with a timeout
-    println!("Checking if Anki Connect is available...");
-    match timeout(Duration::from_secs(2), adapter.check_connection()).await {
-        Ok(result) => match result {
-            Ok(_) => println!("Successfully connected to Anki."),
-            Err(err) => {
-                eprintln!("Error connecting to Anki: {}", err);
-                eprintln!("Please make sure Anki is running and AnkiConnect is installed.");
-                process::exit(1);
-            }
-        },
-        Err(_) => {
-            eprintln!("Timeout while connecting to Anki. Please make sure Anki is running and AnkiConnect is installed.");
+    match run(cli).await {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            eprintln!("{}", err);
             process::exit(1);
         }
     }
-
-    // Ensure the Wordcraft model exists
-    adapter.ensure_wordcraft_model_exists().await?;
-
-    let settings = FlashcardSettings::new();
-
-    let complete_prompt = format!(
-        "Native Language: {}\nTarget Language: {}\nTopic: {}\n",
-        settings.native_language,
-        settings.target_language,
-        settings.topic
-    );
-
-    println!("Generating flashcards for:\n{}", &complete_prompt);
-
-    let response = generate_flashcards(&complete_prompt).await?;
-
-    response.cards.iter().for_each(|card| {
-        println!("Front: {}\nBack: {}\nExample: {}\nExample Translation: {}\n", card.front, card.back, card.example, card.example_translate);
-    });
-
-    if ask_for_confirmation("Would you like to add these flashcards? (y/n)") {
-        println!("Adding flashcards to Anki...");
-    } else {
-        println!("Exiting without adding flashcards.");
-        process::exit(0);
-    }
-
-    let deck_name = if let Some(name) = &settings.deck_name {
-        println!("Adding to existing deck: {}", name);
-        name
-    } else {
-        println!("No existing deck provided. Creating new deck.");
-        adapter.create_deck(&response.deck_name).await?;
-        &response.deck_name
-    };
-
-    println!("Inserting cards into deck: {}", deck_name);
-    for card in &response.cards {
-        println!("Adding card - Front: '{}', Back: '{}', Example: '{}', Example Translation: '{}'", &card.front, &card.back, &card.example, &card.example_translate);
-        adapter.add_card(deck_name, &card.front, &card.back, &card.example, &card.example_translate).await?;
-    }
-
-    Ok(())
 }
@@ -0,0 +1,313 @@
+// Registry of the Wordcraft note types.
+//
+// Every kind shares the card fields the model generates and differs in the
+// Anki fields and templates built from them: plain recognition, recognition
+// plus production, typing the answer, and a cloze over the example sentence.
+// Kinds that need more from the model than front, back and example add an
+// instruction to the system prompt and their fields to the reply schema.
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::anki_connect::{CardTemplate, CreateModel, Note, NoteOptions};
+use crate::generation::Flashcard;
+
+pub(crate) const WORDCRAFT_MODEL_NAME: &str = "Wordcraft";
+
+// Version of the fields, templates and CSS below. Bump it whenever they
+// change so existing models get migrated by `AnkiAdapter::migrate_note_type`.
+// 1: the original models, which carry no marker
+// 2: version marker in the styling
+// 3: FrontAudio and ExampleAudio fields
+pub const WORDCRAFT_MODEL_VERSION: u32 = 3;
+const MODEL_VERSION_MARKER: &str = "wordcraft-model-version:";
+
+pub(crate) const WORDCRAFT_FIELDS: [&str; 4] = ["Front", "Back", "Example", "ExampleTranslation"];
+const WORDCRAFT_NOTE_FIELDS: [&str; 6] = ["Front", "Back", "Example", "ExampleTranslation", "FrontAudio", "ExampleAudio"];
+pub(crate) const WORDCRAFT_TEMPLATE_NAME: &str = "Card 1";
+pub(crate) const WORDCRAFT_FRONT_TEMPLATE: &str = "<div class='front'>{{Front}}</div><br><div class='example'>{{Example}}</div>";
+pub(crate) const WORDCRAFT_BACK_TEMPLATE: &str = "<div class='front'>{{Front}}</div><hr id=answer><div>{{Back}}</div><br><div class='example'>{{Example}}</div><br><div>{{ExampleTranslation}}</div>{{FrontAudio}}{{ExampleAudio}}";
+pub(crate) const WORDCRAFT_TAGS: [&str; 2] = ["wordcraft", "language_learning"];
+pub(crate) const WORDCRAFT_MODEL_CSS: &str = ".card {
+                        font-family: arial;
+                        font-size: 20px;
+                        text-align: center;
+                        color: black;
+                        background-color: white;
+                    }
+                    .front {
+                        font-weight: bold;
+                    }
+                    .example {
+                        font-style: italic;
+                        color: #AAA;
+                    }";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub enum NoteKind {
+    // Front -> Back
+    #[default]
+    Basic,
+    // Front -> Back and Back -> Front
+    Bidirectional,
+    // Back -> type the word
+    TypeAnswer,
+    // Example sentence with the word clozed
+    Cloze,
+}
+
+pub const NOTE_KINDS: [NoteKind; 4] = [NoteKind::Basic, NoteKind::Bidirectional, NoteKind::TypeAnswer, NoteKind::Cloze];
+
+impl NoteKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoteKind::Basic => "basic",
+            NoteKind::Bidirectional => "bidirectional",
+            NoteKind::TypeAnswer => "type-answer",
+            NoteKind::Cloze => "cloze",
+        }
+    }
+
+    pub fn note_type(&self) -> NoteType {
+        note_type(*self)
+    }
+}
+
+impl fmt::Display for NoteKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for NoteKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "basic" | "" => Ok(NoteKind::Basic),
+            "bidirectional" | "reverse" | "both" => Ok(NoteKind::Bidirectional),
+            "type-answer" | "type" | "typing" => Ok(NoteKind::TypeAnswer),
+            "cloze" => Ok(NoteKind::Cloze),
+            other => Err(format!(
+                "Unknown note type '{}'. Expected one of: basic, bidirectional, type-answer, cloze",
+                other
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for NoteKind {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateSpec {
+    pub name: &'static str,
+    pub front: &'static str,
+    pub back: &'static str,
+}
+
+impl TemplateSpec {
+    pub fn card_template(&self) -> CardTemplate {
+        CardTemplate {
+            name: Some(self.name.to_string()),
+            front: self.front.to_string(),
+            back: self.back.to_string(),
+        }
+    }
+}
+
+// Everything needed to create the Anki model and fill its notes
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoteType {
+    pub kind: NoteKind,
+    pub model_name: &'static str,
+    pub fields: &'static [&'static str],
+    pub templates: Vec<TemplateSpec>,
+    pub css: &'static str,
+    pub is_cloze: bool,
+    // JSON keys each generated card must carry
+    pub card_fields: &'static [&'static str],
+    // Extra system prompt text asking for the additional card fields
+    pub prompt_instruction: Option<&'static str>,
+}
+
+const BASE_CARD_FIELDS: &[&str] = &["front", "back", "example", "example_translate"];
+
+pub fn note_type(kind: NoteKind) -> NoteType {
+    let recognition = TemplateSpec {
+        name: WORDCRAFT_TEMPLATE_NAME,
+        front: WORDCRAFT_FRONT_TEMPLATE,
+        back: WORDCRAFT_BACK_TEMPLATE,
+    };
+
+    match kind {
+        NoteKind::Basic => NoteType {
+            kind,
+            model_name: WORDCRAFT_MODEL_NAME,
+            fields: &WORDCRAFT_NOTE_FIELDS,
+            templates: vec![recognition],
+            css: WORDCRAFT_MODEL_CSS,
+            is_cloze: false,
+            card_fields: BASE_CARD_FIELDS,
+            prompt_instruction: None,
+        },
+        NoteKind::Bidirectional => NoteType {
+            kind,
+            model_name: "Wordcraft Bidirectional",
+            fields: &WORDCRAFT_NOTE_FIELDS,
+            templates: vec![
+                TemplateSpec { name: "Recognition", ..recognition },
+                TemplateSpec {
+                    name: "Production",
+                    front: "<div>{{Back}}</div><br><div class='example'>{{ExampleTranslation}}</div>",
+                    back: "<div>{{Back}}</div><hr id=answer><div class='front'>{{Front}}</div><br><div class='example'>{{Example}}</div><br><div>{{ExampleTranslation}}</div>{{FrontAudio}}{{ExampleAudio}}",
+                },
+            ],
+            css: WORDCRAFT_MODEL_CSS,
+            is_cloze: false,
+            card_fields: BASE_CARD_FIELDS,
+            prompt_instruction: None,
+        },
+        NoteKind::TypeAnswer => NoteType {
+            kind,
+            model_name: "Wordcraft Type Answer",
+            fields: &["Front", "Back", "Answer", "Example", "ExampleTranslation", "FrontAudio", "ExampleAudio"],
+            templates: vec![TemplateSpec {
+                name: "Type Answer",
+                front: "<div>{{Back}}</div><br><div class='example'>{{ExampleTranslation}}</div><br>{{type:Answer}}",
+                back: "<div>{{Back}}</div><hr id=answer>{{type:Answer}}<br><div class='front'>{{Front}}</div><br><div class='example'>{{Example}}</div>{{FrontAudio}}{{ExampleAudio}}",
+            }],
+            css: WORDCRAFT_MODEL_CSS,
+            is_cloze: false,
+            card_fields: &["front", "back", "answer", "example", "example_translate"],
+            prompt_instruction: Some(
+                "Each card also needs an \"answer\" field: the word from the front exactly as the student should type it, in the target language's usual script, without readings, romanization or punctuation.",
+            ),
+        },
+        NoteKind::Cloze => NoteType {
+            kind,
+            model_name: "Wordcraft Cloze",
+            fields: &["Text", "Front", "Back", "ExampleTranslation", "FrontAudio", "ExampleAudio"],
+            templates: vec![TemplateSpec {
+                name: "Cloze",
+                front: "<div class='example'>{{cloze:Text}}</div><br><div>{{ExampleTranslation}}</div>",
+                back: "<div class='example'>{{cloze:Text}}</div><br><div>{{ExampleTranslation}}</div><hr><div class='front'>{{Front}}</div><div>{{Back}}</div>{{FrontAudio}}{{ExampleAudio}}",
+            }],
+            css: WORDCRAFT_MODEL_CSS,
+            is_cloze: true,
+            card_fields: &["front", "back", "example", "example_translate", "cloze"],
+            prompt_instruction: Some(
+                "Each card also needs a \"cloze\" field: the example sentence with the target word wrapped as {{c1::word}}, for example \"私は{{c1::家}}にいます。\". Use exactly one cloze deletion per sentence.",
+            ),
+        },
+    }
+}
+
+impl NoteType {
+    // The CSS with the model version marker in front
+    pub fn styling(&self) -> String {
+        format!("/* {} {} */\n{}", MODEL_VERSION_MARKER, WORDCRAFT_MODEL_VERSION, self.css)
+    }
+
+    pub fn card_templates(&self) -> BTreeMap<String, CardTemplate> {
+        self.templates.iter()
+            .map(|template| (template.name.to_string(), template.card_template()))
+            .collect()
+    }
+
+    pub fn create_model(&self) -> CreateModel {
+        CreateModel {
+            model_name: self.model_name.to_string(),
+            in_order_fields: self.fields.iter().map(|field| field.to_string()).collect(),
+            css: self.styling(),
+            is_cloze: self.is_cloze.then_some(true),
+            card_templates: self.templates.iter().map(TemplateSpec::card_template).collect(),
+        }
+    }
+
+    // Field values of the note for a card, in `fields` order
+    pub fn field_values(&self, card: &Flashcard) -> Vec<String> {
+        self.fields.iter()
+            .map(|field| match *field {
+                "Front" => card.front.clone(),
+                "Back" => card.back.clone(),
+                "Example" => card.example.clone(),
+                "ExampleTranslation" => card.example_translate.clone(),
+                "Answer" => card.answer.clone()
+                    .filter(|answer| !answer.trim().is_empty())
+                    .unwrap_or_else(|| plain_word(&card.front)),
+                "Text" => cloze_text(card),
+                "FrontAudio" => sound_tag(card.front_audio.as_deref()),
+                "ExampleAudio" => sound_tag(card.example_audio.as_deref()),
+                _ => String::new(),
+            })
+            .collect()
+    }
+
+    pub fn note(&self, deck_name: &str, card: &Flashcard) -> Note {
+        Note {
+            deck_name: deck_name.to_string(),
+            model_name: self.model_name.to_string(),
+            fields: self.fields.iter()
+                .map(|field| field.to_string())
+                .zip(self.field_values(card))
+                .collect(),
+            options: NoteOptions { allow_duplicate: false, duplicate_scope: None },
+            tags: WORDCRAFT_TAGS.iter().map(|tag| tag.to_string()).collect(),
+        }
+    }
+}
+
+// Model version recorded in a model's CSS; models from before the marker
+// was introduced count as version 1
+pub fn model_version(css: &str) -> u32 {
+    css.split(MODEL_VERSION_MARKER)
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|version| version.parse().ok())
+        .unwrap_or(1)
+}
+
+// Anki's reference to an audio file in the media folder, empty without one
+fn sound_tag(filename: Option<&str>) -> String {
+    filename.map(|filename| format!("[sound:{}]", filename)).unwrap_or_default()
+}
+
+// The word without readings in parentheses, e.g. "家" for "家 (いえ) (ie)"
+pub fn plain_word(front: &str) -> String {
+    let mut word = String::with_capacity(front.len());
+    let mut depth = 0usize;
+    for c in front.chars() {
+        match c {
+            '(' | '（' => depth += 1,
+            ')' | '）' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => word.push(c),
+            _ => {}
+        }
+    }
+
+    word.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Cloze text for a card: the model's own cloze when it has one, otherwise
+// the example with the first occurrence of the word clozed, otherwise the
+// clozed word on its own line above the example
+pub fn cloze_text(card: &Flashcard) -> String {
+    if let Some(cloze) = card.cloze.as_ref().filter(|cloze| cloze.contains("{{c")) {
+        return cloze.clone();
+    }
+
+    let word = plain_word(&card.front);
+    if !word.is_empty() && card.example.contains(&word) {
+        return card.example.replacen(&word, &format!("{{{{c1::{}}}}}", word), 1);
+    }
+
+    format!("{{{{c1::{}}}}}<br>{}", word, card.example)
+}
@@ -0,0 +1,117 @@
+// Client for Ollama's /api/chat endpoint
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::WordcraftError;
+use crate::generator::{read_line_stream, ChatMessage, ChunkHandler, CompletionRequest, FlashcardGenerator};
+
+pub const DEFAULT_OLLAMA_BASE_URL: &str = "http://localhost:11434";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OllamaConfig {
+    pub base_url: String,
+    pub model: String,
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: &'a [ChatMessage],
+    stream: bool,
+    // JSON schema constraining the reply (Ollama 0.5+)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'a Value>,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    message: ChatResponseMessage,
+}
+
+// One line of a streamed /api/chat reply
+#[derive(Deserialize)]
+struct ChatStreamLine {
+    #[serde(default)]
+    message: Option<ChatResponseMessage>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatResponseMessage {
+    #[serde(default)]
+    content: String,
+}
+
+pub struct OllamaGenerator {
+    config: OllamaConfig,
+    client: Client,
+}
+
+impl OllamaGenerator {
+    pub fn new(config: OllamaConfig) -> Self {
+        OllamaGenerator { config, client: Client::new() }
+    }
+
+    fn chat_url(&self) -> String {
+        format!("{}/api/chat", self.config.base_url.trim_end_matches('/'))
+    }
+
+    async fn send(&self, request: &CompletionRequest, stream: bool) -> Result<reqwest::Response, WordcraftError> {
+        let body = ChatRequest {
+            model: &self.config.model,
+            messages: &request.messages,
+            stream,
+            format: request.response_schema.as_ref(),
+        };
+
+        let response = self.client
+            .post(self.chat_url())
+            .json(&body)
+            .send().await
+            .map_err(WordcraftError::llm_transport)?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(WordcraftError::llm_transport(format!("{} returned {}: {}", self.config.base_url, status, body)));
+        }
+
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl FlashcardGenerator for OllamaGenerator {
+    fn engine(&self) -> &str {
+        "ollama"
+    }
+
+    fn model(&self) -> &str {
+        &self.config.model
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<String, WordcraftError> {
+        let response = self.send(request, false).await?;
+        let reply = response.json::<ChatResponse>().await.map_err(WordcraftError::llm_transport)?;
+        Ok(reply.message.content)
+    }
+
+    // The streamed reply is newline-delimited JSON, one message fragment per line
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+        on_chunk: &mut ChunkHandler<'_>,
+    ) -> Result<String, WordcraftError> {
+        let response = self.send(request, true).await?;
+        read_line_stream(response, on_chunk, |line| {
+            let line: ChatStreamLine = serde_json::from_str(line).map_err(WordcraftError::llm_transport)?;
+            if let Some(error) = line.error {
+                return Err(WordcraftError::llm_transport(format!("{} returned an error: {}", self.config.base_url, error)));
+            }
+            Ok(line.message.map(|message| message.content))
+        }).await
+    }
+}
@@ -0,0 +1,183 @@
+// Chat-completions client for OpenAI and any server speaking the same
+// protocol (llama.cpp server, vLLM, LM Studio, LocalAI, ...).
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::error::WordcraftError;
+use crate::generator::{read_line_stream, ChatMessage, ChunkHandler, CompletionRequest, FlashcardGenerator};
+use crate::schema::SCHEMA_NAME;
+
+pub const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenAIConfig {
+    // Base URL up to and including the API version, e.g. http://192.168.1.20:8080/v1
+    pub base_url: String,
+    pub model: String,
+    // Sent as a bearer token when present; local servers usually need none
+    pub api_key: Option<String>,
+    // Extra headers sent with every request
+    pub headers: Vec<(String, String)>,
+    // Send the reply schema as `response_format`; turn off for servers without json_schema support
+    pub structured_output: bool,
+}
+
+impl OpenAIConfig {
+    pub fn is_default_endpoint(&self) -> bool {
+        self.base_url.trim_end_matches('/') == DEFAULT_OPENAI_BASE_URL
+    }
+}
+
+// Parse "Name: value; Other-Name: value" into header pairs
+pub fn parse_headers(raw: &str) -> Result<Vec<(String, String)>, WordcraftError> {
+    raw.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (name, value) = entry.split_once(':')
+                .ok_or_else(|| WordcraftError::Config(format!("Invalid header '{}', expected 'Name: value'", entry)))?;
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct ChatCompletionRequest<'a> {
+    model: &'a str,
+    messages: &'a [ChatMessage],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<Value>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+#[derive(Deserialize)]
+struct ChatCompletionResponse {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatChoiceMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatChoiceMessage {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+// One `data:` event of a streamed completion
+#[derive(Deserialize)]
+struct ChatCompletionChunk {
+    #[serde(default)]
+    choices: Vec<ChatChunkChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatChunkChoice {
+    delta: ChatChoiceMessage,
+}
+
+pub struct OpenAIGenerator {
+    config: OpenAIConfig,
+    client: Client,
+}
+
+impl OpenAIGenerator {
+    pub fn new(config: OpenAIConfig) -> Result<Self, WordcraftError> {
+        let mut headers = HeaderMap::new();
+        if let Some(api_key) = &config.api_key {
+            let value = HeaderValue::from_str(&format!("Bearer {}", api_key))
+                .map_err(|err| WordcraftError::Config(format!("Invalid API key: {}", err)))?;
+            headers.insert(AUTHORIZATION, value);
+        }
+        for (name, value) in &config.headers {
+            let invalid = |err: &dyn std::fmt::Display| WordcraftError::Config(format!("Invalid header '{}': {}", name, err));
+            let header_name = HeaderName::from_bytes(name.as_bytes()).map_err(|err| invalid(&err))?;
+            headers.insert(header_name, HeaderValue::from_str(value).map_err(|err| invalid(&err))?);
+        }
+
+        let client = Client::builder().default_headers(headers).build().map_err(WordcraftError::llm_transport)?;
+        Ok(OpenAIGenerator { config, client })
+    }
+
+    fn completions_url(&self) -> String {
+        format!("{}/chat/completions", self.config.base_url.trim_end_matches('/'))
+    }
+
+    async fn send(&self, request: &CompletionRequest, stream: bool) -> Result<reqwest::Response, WordcraftError> {
+        let response_format = request.response_schema.as_ref()
+            .filter(|_| self.config.structured_output)
+            .map(|schema| json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": SCHEMA_NAME,
+                    "strict": true,
+                    "schema": schema
+                }
+            }));
+        let body = ChatCompletionRequest {
+            model: &self.config.model,
+            messages: &request.messages,
+            response_format,
+            stream,
+        };
+
+        let response = self.client
+            .post(self.completions_url())
+            .json(&body)
+            .send().await
+            .map_err(WordcraftError::llm_transport)?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(WordcraftError::llm_transport(format!("{} returned {}: {}", self.config.base_url, status, body)));
+        }
+
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl FlashcardGenerator for OpenAIGenerator {
+    fn engine(&self) -> &str {
+        "openai"
+    }
+
+    fn model(&self) -> &str {
+        &self.config.model
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<String, WordcraftError> {
+        let response = self.send(request, false).await?;
+        let completion = response.json::<ChatCompletionResponse>().await.map_err(WordcraftError::llm_transport)?;
+        completion.choices.into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .ok_or_else(|| WordcraftError::llm_transport("Chat completion contained no message content"))
+    }
+
+    // The streamed reply is server-sent events: `data: {chunk}` lines ending with `data: [DONE]`
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+        on_chunk: &mut ChunkHandler<'_>,
+    ) -> Result<String, WordcraftError> {
+        let response = self.send(request, true).await?;
+        read_line_stream(response, on_chunk, |line| {
+            let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                return Ok(None);
+            };
+            if data == "[DONE]" {
+                return Ok(None);
+            }
+            let chunk: ChatCompletionChunk = serde_json::from_str(data).map_err(WordcraftError::llm_transport)?;
+            Ok(Some(chunk.choices.into_iter().filter_map(|choice| choice.delta.content).collect()))
+        }).await
+    }
+}
@@ -1,112 +1,227 @@
 use std::io::{self, Write};
 
+use crate::constant::DEFAULT_CARD_COUNT;
+use crate::level::ProficiencyLevel;
+use crate::note_type::NoteKind;
+
 // Struct to store user input for flashcard generation
+#[derive(Debug, Clone)]
 pub struct FlashcardSettings {
     pub native_language: String,
     pub target_language: String,
     pub topic: String,
     pub deck_name: Option<String>,
+    pub note_kind: NoteKind,
+    // Exact number of cards to make; the model picks at least 15 when None
+    pub card_count: Option<usize>,
+    pub level: Option<ProficiencyLevel>,
+}
+
+// Settings given up front, e.g. as command line flags
+#[derive(Debug, Clone, Default)]
+pub struct SettingsOverrides {
+    pub native_language: Option<String>,
+    pub target_language: Option<String>,
+    pub topic: Option<String>,
+    pub deck_name: Option<String>,
+    pub note_kind: Option<NoteKind>,
+    pub card_count: Option<usize>,
+    pub level: Option<ProficiencyLevel>,
 }
 
 impl FlashcardSettings {
-    // Function to prompt user input for flashcard generation
-    pub fn new() -> Self {
-        let native_language = prompt_with_default(
-            "Enter your native language (default: English): ",
-            "English",
-        );
-        let target_language = prompt_with_default(
-            "Enter the target language you want to learn (default: Japanese): ",
-            "Japanese",
-        );
-        let topic = prompt_for_topic("Enter the topic you want to learn: ");
-        let deck_name = prompt_existing_deck("Do you want to add to an existing deck? (y/N): ");
-
-        FlashcardSettings {
+    // Complete the given settings. Without a topic the learner is asked for
+    // everything not given, which needs `interactive`; with a topic the
+    // missing settings take their defaults so scripted runs never block.
+    // Stdin closing before a topic is entered is an error.
+    pub fn resolve(given: SettingsOverrides, interactive: bool) -> Result<Self, String> {
+        if given.card_count == Some(0) {
+            return Err("The number of cards must be at least 1.".to_string());
+        }
+        if let Some(topic) = given.topic {
+            return Ok(FlashcardSettings {
+                native_language: given.native_language.unwrap_or_else(|| DEFAULT_NATIVE_LANGUAGE.to_string()),
+                target_language: given.target_language.unwrap_or_else(|| DEFAULT_TARGET_LANGUAGE.to_string()),
+                topic,
+                deck_name: given.deck_name,
+                note_kind: given.note_kind.unwrap_or_default(),
+                card_count: given.card_count,
+                level: given.level,
+            });
+        }
+        if !interactive {
+            return Err("No topic given. Pass --topic or run Wordcraft in a terminal.".to_string());
+        }
+
+        let terminal = |err: io::Error| format!("Could not read from the terminal: {}", err);
+        let native_language = match given.native_language {
+            Some(language) => language,
+            None => prompt_with_default("Enter your native language (default: English): ", DEFAULT_NATIVE_LANGUAGE)
+                .map_err(terminal)?,
+        };
+        let target_language = match given.target_language {
+            Some(language) => language,
+            None => prompt_with_default(
+                "Enter the target language you want to learn (default: Japanese): ",
+                DEFAULT_TARGET_LANGUAGE,
+            ).map_err(terminal)?,
+        };
+        let topic = prompt_for_topic("Enter the topic you want to learn: ")
+            .map_err(terminal)?
+            .ok_or_else(|| "No topic given.".to_string())?;
+        let deck_name = match given.deck_name {
+            Some(deck_name) => Some(deck_name),
+            None => prompt_existing_deck("Do you want to add to an existing deck? (y/N): ").map_err(terminal)?,
+        };
+        let note_kind = match given.note_kind {
+            Some(kind) => kind,
+            None => prompt_for_note_kind("Choose a note type: basic, bidirectional, type-answer or cloze (default: basic): ")
+                .map_err(terminal)?,
+        };
+        let card_count = match given.card_count {
+            Some(count) => Some(count),
+            None => prompt_for_card_count(&format!("How many cards? (default: at least {}): ", DEFAULT_CARD_COUNT))
+                .map_err(terminal)?,
+        };
+        let level = match given.level {
+            Some(level) => Some(level),
+            None => prompt_for_level("Your level, e.g. A2, N4, HSK 3 or TOPIK 2 (optional): ").map_err(terminal)?,
+        };
+
+        Ok(FlashcardSettings {
             native_language,
             target_language,
             topic,
             deck_name,
-        }
+            note_kind,
+            card_count,
+            level,
+        })
     }
 }
 
+const DEFAULT_NATIVE_LANGUAGE: &str = "English";
+const DEFAULT_TARGET_LANGUAGE: &str = "Japanese";
+
 // Function to prompt user for input with a default value
-fn prompt_with_default(prompt: &str, default: &str) -> String {
+fn prompt_with_default(prompt: &str, default: &str) -> io::Result<String> {
     print!("{}", prompt);
-    io::stdout().flush().unwrap(); // Ensure prompt is displayed immediately
+    io::stdout().flush()?; // Ensure prompt is displayed immediately
 
     let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
+    io::stdin().read_line(&mut input)?;
     let trimmed_input = input.trim();
 
     if trimmed_input.is_empty() {
-        default.to_string()
+        Ok(default.to_string())
     } else {
-        trimmed_input.to_string()
+        Ok(trimmed_input.to_string())
     }
 }
 
-// Function to prompt user for topic input and ensure it is not empty
-fn prompt_for_topic(prompt: &str) -> String {
+// Function to prompt user for topic input and ensure it is not empty; None
+// when stdin is closed
+fn prompt_for_topic(prompt: &str) -> io::Result<Option<String>> {
     loop {
         print!("{}", prompt);
-        io::stdout().flush().unwrap();
+        io::stdout().flush()?;
 
         let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(None);
+        }
         let trimmed_input = input.trim();
 
         if !trimmed_input.is_empty() {
-            return trimmed_input.to_string();
+            return Ok(Some(trimmed_input.to_string()));
         } else {
             println!("Topic cannot be empty. Please try again.");
         }
     }
 }
 
+// Function to prompt user for the note type until a known one is entered
+fn prompt_for_note_kind(prompt: &str) -> io::Result<NoteKind> {
+    loop {
+        match prompt_with_default(prompt, "basic")?.parse::<NoteKind>() {
+            Ok(kind) => return Ok(kind),
+            Err(err) => println!("{}", err),
+        }
+    }
+}
+
+// Function to prompt user for a card count; empty means no exact count
+fn prompt_for_card_count(prompt: &str) -> io::Result<Option<usize>> {
+    loop {
+        let input = prompt_with_default(prompt, "")?;
+        if input.is_empty() {
+            return Ok(None);
+        }
+        match input.parse::<usize>() {
+            Ok(count) if count > 0 => return Ok(Some(count)),
+            _ => println!("Please enter a number of at least 1, or nothing to let the model decide."),
+        }
+    }
+}
+
+// Function to prompt user for a proficiency level until a known one or nothing is entered
+fn prompt_for_level(prompt: &str) -> io::Result<Option<ProficiencyLevel>> {
+    loop {
+        let input = prompt_with_default(prompt, "")?;
+        if input.is_empty() {
+            return Ok(None);
+        }
+        match input.parse::<ProficiencyLevel>() {
+            Ok(level) => return Ok(Some(level)),
+            Err(err) => println!("{}", err),
+        }
+    }
+}
+
 // Function to prompt user if they want to add to an existing deck and get deck name if yes
-fn prompt_existing_deck(prompt: &str) -> Option<String> {
+fn prompt_existing_deck(prompt: &str) -> io::Result<Option<String>> {
     print!("{}", prompt);
-    io::stdout().flush().unwrap();
+    io::stdout().flush()?;
 
     let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
+    io::stdin().read_line(&mut input)?;
     let trimmed_input = input.trim().to_lowercase();
 
     if trimmed_input == "y" || trimmed_input == "yes" {
         print!("Enter the name of the existing deck: ");
-        io::stdout().flush().unwrap();
+        io::stdout().flush()?;
 
         let mut deck_name = String::new();
-        io::stdin().read_line(&mut deck_name).unwrap();
+        io::stdin().read_line(&mut deck_name)?;
         let deck_name_trimmed = deck_name.trim();
 
         if !deck_name_trimmed.is_empty() {
-            Some(deck_name_trimmed.to_string())
+            Ok(Some(deck_name_trimmed.to_string()))
         } else {
             println!("Deck name cannot be empty. Skipping adding to existing deck.");
-            None
+            Ok(None)
         }
     } else {
-        None
+        Ok(None)
     }
 }
 
-pub fn ask_for_confirmation(prompt: &str) -> bool {
+pub fn ask_for_confirmation(prompt: &str) -> io::Result<bool> {
     let mut input = String::new();
 
     // Prompt the user for confirmation
     print!("{}", prompt);
-    io::stdout().flush().unwrap(); // Ensure the prompt is shown
+    io::stdout().flush()?; // Ensure the prompt is shown
 
-    // Read the user's input
-    io::stdin().read_line(&mut input).unwrap();
+    // Read the user's input; a closed stdin counts as "no"
+    if io::stdin().read_line(&mut input)? == 0 {
+        return Ok(false);
+    }
 
     // Trim the input and check for 'y' or 'Y'
     match input.trim().to_lowercase().as_str() {
-        "y" => true,
-        "n" => false,
+        "y" => Ok(true),
+        "n" => Ok(false),
         _ => {
             println!("Invalid input. Please enter 'y' or 'n'.");
             ask_for_confirmation(prompt) // Recursively ask again for valid input
@@ -126,6 +241,9 @@ mod tests {
             target_language: "Japanese".to_string(),
             topic: "Vocabulary".to_string(),
             deck_name: None,
+            note_kind: NoteKind::Basic,
+            card_count: None,
+            level: None,
         };
 
         assert_eq!(settings.native_language, "English");
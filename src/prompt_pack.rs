@@ -0,0 +1,186 @@
+// System prompt templates per target language.
+//
+// The system prompt is prompts/base.txt with the pack of the target language
+// filled in: notes on its script and transliteration and an example deck.
+// The files in prompts/ are compiled in. Files of the same name in the user's
+// prompt directory (~/.config/wordcraft/prompts) override them, and a pack
+// for a language Wordcraft has none for adds one:
+//
+//   base.txt        the template: {card_count}, {level}, {notes} and {example}
+//   default.toml    languages without a pack of their own
+//   spanish.toml    aliases = ["es"], notes = "...", example = "..."
+//
+// A user pack only needs the fields it changes; the rest come from the
+// built-in pack of the same name, or from default.toml.
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::config::default_prompt_dir;
+use crate::error::WordcraftError;
+use crate::generation::render_template;
+
+pub const DEFAULT_PACK: &str = "default";
+
+const BUILTIN_BASE: &str = include_str!("../prompts/base.txt");
+const BUILTIN_PACKS: [(&str, &str); 8] = [
+    ("default", include_str!("../prompts/default.toml")),
+    ("chinese", include_str!("../prompts/chinese.toml")),
+    ("french", include_str!("../prompts/french.toml")),
+    ("german", include_str!("../prompts/german.toml")),
+    ("japanese", include_str!("../prompts/japanese.toml")),
+    ("korean", include_str!("../prompts/korean.toml")),
+    ("russian", include_str!("../prompts/russian.toml")),
+    ("spanish", include_str!("../prompts/spanish.toml")),
+];
+
+// prompts.dir / WORDCRAFT_PROMPT_DIR replaces ~/.config/wordcraft/prompts
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PromptConfig {
+    pub dir: Option<String>,
+}
+
+// A <language>.toml file; unset fields are inherited
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PackFile {
+    aliases: Option<Vec<String>>,
+    notes: Option<String>,
+    example: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptPack {
+    // The file name without .toml, lowercase, e.g. "japanese"
+    pub language: String,
+    // Other names the target language may be given as, e.g. "ja" or "日本語"
+    pub aliases: Vec<String>,
+    pub notes: String,
+    // Example deck shown to the model
+    pub example: String,
+}
+
+impl PromptPack {
+    pub fn matches(&self, target_language: &str) -> bool {
+        let target = target_language.trim().to_lowercase();
+        self.language == target || self.aliases.iter().any(|alias| alias.to_lowercase() == target)
+    }
+
+    fn apply(&mut self, file: PackFile) {
+        if let Some(aliases) = file.aliases {
+            self.aliases = aliases;
+        }
+        if let Some(notes) = file.notes {
+            self.notes = notes;
+        }
+        if let Some(example) = file.example {
+            self.example = example;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PromptLibrary {
+    pub base: String,
+    // By language; always has DEFAULT_PACK
+    pub packs: BTreeMap<String, PromptPack>,
+    // Files read from the user's prompt directory
+    pub overrides: Vec<PathBuf>,
+}
+
+impl PromptLibrary {
+    // The templates compiled into Wordcraft
+    pub fn builtin() -> &'static PromptLibrary {
+        static BUILTIN: OnceLock<PromptLibrary> = OnceLock::new();
+        BUILTIN.get_or_init(|| {
+            let mut library = PromptLibrary { base: BUILTIN_BASE.to_string(), packs: BTreeMap::new(), overrides: Vec::new() };
+            for (language, text) in BUILTIN_PACKS {
+                let file = toml::from_str(text).unwrap_or_else(|err| panic!("prompts/{}.toml: {}", language, err));
+                library.add_pack(language, file);
+            }
+            library
+        })
+    }
+
+    // The built-in templates with the files in `dir` on top. A missing
+    // directory has no overrides.
+    pub fn load(dir: &Path) -> Result<PromptLibrary, WordcraftError> {
+        let mut library = PromptLibrary::builtin().clone();
+        let unreadable = |path: &Path, err: std::io::Error| {
+            WordcraftError::Config(format!("Could not read prompt template {}: {}", path.display(), err))
+        };
+
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(library),
+            Err(err) => return Err(unreadable(dir, err)),
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()
+            .map_err(|err| unreadable(dir, err))?;
+        // The base and the default pack first, since new packs start from the
+        // default pack's example
+        let default_file = format!("{}.toml", DEFAULT_PACK);
+        paths.sort_by_key(|path| {
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            let rank = if name == "base.txt" { 0 } else if name == default_file { 1 } else { 2 };
+            (rank, path.clone())
+        });
+
+        for path in paths {
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            if name == "base.txt" {
+                library.base = std::fs::read_to_string(&path).map_err(|err| unreadable(&path, err))?;
+            } else if let Some(language) = name.strip_suffix(".toml") {
+                let text = std::fs::read_to_string(&path).map_err(|err| unreadable(&path, err))?;
+                let file = toml::from_str(&text).map_err(|err: toml::de::Error| {
+                    WordcraftError::Config(format!("Invalid prompt pack {}: {}", path.display(), err.message()))
+                })?;
+                library.add_pack(language, file);
+            } else {
+                continue;
+            }
+            library.overrides.push(path);
+        }
+        Ok(library)
+    }
+
+    pub fn from_config(config: &PromptConfig) -> Result<PromptLibrary, WordcraftError> {
+        let dir = config.dir.as_ref().map(PathBuf::from).unwrap_or_else(default_prompt_dir);
+        PromptLibrary::load(&dir)
+    }
+
+    // The pack for `target_language`, by name or alias, or the default pack
+    pub fn pack(&self, target_language: &str) -> &PromptPack {
+        self.packs.values()
+            .find(|pack| pack.language != DEFAULT_PACK && pack.matches(target_language))
+            .or_else(|| self.packs.get(DEFAULT_PACK))
+            .expect("the default prompt pack is built in")
+    }
+
+    // The system prompt for `target_language`, with {card_count} and {level}
+    // left for `generation::build_system_message`
+    pub fn template(&self, target_language: &str) -> String {
+        let pack = self.pack(target_language);
+        render_template(&self.base, &[("notes", pack.notes.trim()), ("example", pack.example.trim())])
+    }
+
+    fn add_pack(&mut self, language: &str, file: PackFile) {
+        let language = language.to_lowercase();
+        let mut pack = self.packs.get(&language).cloned().unwrap_or_else(|| PromptPack {
+            language: language.clone(),
+            aliases: Vec::new(),
+            notes: String::new(),
+            example: self.packs.get(DEFAULT_PACK).map(|pack| pack.example.clone()).unwrap_or_default(),
+        });
+        pack.apply(file);
+        self.packs.insert(language, pack);
+    }
+}
+
+// The built-in template of languages without a pack
+pub fn default_template() -> String {
+    PromptLibrary::builtin().template(DEFAULT_PACK)
+}
@@ -0,0 +1,304 @@
+// Retrieval-augmented generation from the learner's Anki history.
+//
+// `build_index` pulls cards and their review logs from AnkiConnect and
+// classifies every word as mastered, learning, struggling or unseen. The
+// index is stored as JSON, one file per Anki search query, so the last known
+// state is still available when Anki is not running. `SelectionStrategy` then
+// picks a small, prompt-sized subset of the index for the model.
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::anki_adapter::{strip_html, AnkiAdapter};
+use crate::anki_connect::{CardInfo, ReviewEntry};
+use crate::error::WordcraftError;
+use crate::generation::normalize_front;
+
+// Cards sent per cardsInfo / getReviewsOfCards request
+const CARD_CHUNK_SIZE: usize = 500;
+
+// The notes of every Wordcraft note type, for runs without a deck
+pub const WORDCRAFT_NOTES_QUERY: &str = "note:Wordcraft*";
+
+// Mature cards in Anki have an interval of at least 21 days
+const MASTERED_INTERVAL_DAYS: i64 = 21;
+const STRUGGLING_EASE_FACTOR: i64 = 2000;
+const STRUGGLING_LAPSES: i64 = 2;
+const STRUGGLING_AGAIN_RATIO: f64 = 0.3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WordStatus {
+    Mastered,
+    Learning,
+    Struggling,
+    Unseen,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VocabularyEntry {
+    pub word: String,
+    pub deck: String,
+    pub status: WordStatus,
+    pub interval: i64,
+    pub ease_factor: i64,
+    pub reps: i64,
+    pub lapses: i64,
+    pub again_count: usize,
+    pub review_count: usize,
+    // Unix time in milliseconds of the latest review
+    pub last_review: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VocabularyIndex {
+    // Unix time in seconds when the index was built
+    pub built_at: u64,
+    pub query: String,
+    pub entries: Vec<VocabularyEntry>,
+}
+
+impl VocabularyIndex {
+    pub fn load(path: &Path) -> Result<VocabularyIndex, WordcraftError> {
+        let contents = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    // The stored index, if it was built from `query`; an index of other decks
+    // says nothing about this one
+    pub fn load_for_query(path: &Path, query: &str) -> Option<VocabularyIndex> {
+        VocabularyIndex::load(path).ok().filter(|index| index.query == query)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), WordcraftError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn words(&self) -> Vec<String> {
+        self.entries.iter().map(|entry| entry.word.clone()).collect()
+    }
+
+    pub fn with_status(&self, status: WordStatus) -> impl Iterator<Item = &VocabularyEntry> {
+        self.entries.iter().filter(move |entry| entry.status == status)
+    }
+}
+
+// Location of the offline index of `query`: a file named after its hash in
+// $XDG_DATA_HOME/wordcraft or ~/.local/share/wordcraft
+pub fn default_index_path(query: &str) -> PathBuf {
+    let data_dir = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))
+        .unwrap_or_else(std::env::temp_dir);
+    let hash: String = Sha1::digest(query.as_bytes()).iter().take(8).map(|byte| format!("{:02x}", byte)).collect();
+
+    data_dir.join("wordcraft").join(format!("vocabulary-{}.json", hash))
+}
+
+// Build the index from every card matching the Anki search query
+pub async fn build_index(adapter: &AnkiAdapter, query: &str) -> Result<VocabularyIndex, WordcraftError> {
+    let card_ids = adapter.find_cards(query).await?;
+
+    let mut cards = Vec::with_capacity(card_ids.len());
+    for chunk in card_ids.chunks(CARD_CHUNK_SIZE) {
+        cards.extend(adapter.cards_info(chunk).await?);
+    }
+
+    let reviewed: Vec<i64> = cards.iter().filter(|card| card.reps > 0).map(|card| card.card_id).collect();
+    let mut reviews: HashMap<String, Vec<ReviewEntry>> = HashMap::new();
+    for chunk in reviewed.chunks(CARD_CHUNK_SIZE) {
+        reviews.extend(adapter.get_reviews_of_cards(chunk).await?);
+    }
+
+    let mut entries: Vec<VocabularyEntry> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for card in &cards {
+        let card_reviews = reviews.get(&card.card_id.to_string()).map(Vec::as_slice).unwrap_or(&[]);
+        let Some(entry) = entry_from_card(card, card_reviews) else {
+            continue;
+        };
+
+        // Notes with several cards (e.g. reverse cards) produce one entry per word
+        let key = normalize_front(&entry.word);
+        match positions.get(&key) {
+            Some(&position) => {
+                if status_rank(entry.status) > status_rank(entries[position].status) {
+                    entries[position] = entry;
+                }
+            }
+            None => {
+                positions.insert(key, entries.len());
+                entries.push(entry);
+            }
+        }
+    }
+
+    Ok(VocabularyIndex {
+        built_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default(),
+        query: query.to_string(),
+        entries,
+    })
+}
+
+fn entry_from_card(card: &CardInfo, reviews: &[ReviewEntry]) -> Option<VocabularyEntry> {
+    let word = card.fields.get("Front").map(|field| strip_html(&field.value))?;
+    if word.is_empty() {
+        return None;
+    }
+
+    let again_count = reviews.iter().filter(|review| review.ease == 1).count();
+    let mut entry = VocabularyEntry {
+        word,
+        deck: card.deck_name.clone(),
+        status: WordStatus::Unseen,
+        interval: card.interval,
+        ease_factor: card.ease_factor,
+        reps: card.reps,
+        lapses: card.lapses,
+        again_count,
+        review_count: reviews.len(),
+        last_review: reviews.iter().map(|review| review.id).max(),
+    };
+    entry.status = classify(&entry);
+
+    Some(entry)
+}
+
+// Classify a word from its scheduling state and review history
+pub fn classify(entry: &VocabularyEntry) -> WordStatus {
+    if entry.reps == 0 && entry.review_count == 0 {
+        return WordStatus::Unseen;
+    }
+
+    let again_ratio = if entry.review_count > 0 {
+        entry.again_count as f64 / entry.review_count as f64
+    } else {
+        0.0
+    };
+    let low_ease = entry.ease_factor > 0 && entry.ease_factor < STRUGGLING_EASE_FACTOR;
+
+    if entry.lapses >= STRUGGLING_LAPSES || low_ease || again_ratio >= STRUGGLING_AGAIN_RATIO {
+        WordStatus::Struggling
+    } else if entry.interval >= MASTERED_INTERVAL_DAYS {
+        WordStatus::Mastered
+    } else {
+        WordStatus::Learning
+    }
+}
+
+// When two cards share a word, the one needing the most attention wins
+fn status_rank(status: WordStatus) -> u8 {
+    match status {
+        WordStatus::Unseen => 0,
+        WordStatus::Mastered => 1,
+        WordStatus::Learning => 2,
+        WordStatus::Struggling => 3,
+    }
+}
+
+// Decides which words from the index are worth showing to the model
+#[derive(Debug, Clone)]
+pub struct SelectionStrategy {
+    pub max_mastered: usize,
+    pub max_learning: usize,
+    pub max_struggling: usize,
+    pub max_unseen: usize,
+}
+
+impl Default for SelectionStrategy {
+    fn default() -> Self {
+        SelectionStrategy {
+            max_mastered: 40,
+            max_learning: 20,
+            max_struggling: 20,
+            max_unseen: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Selection {
+    pub mastered: Vec<String>,
+    pub learning: Vec<String>,
+    pub struggling: Vec<String>,
+    pub unseen: Vec<String>,
+}
+
+impl Selection {
+    // Every selected word, as listed in the learner profile
+    pub fn words(&self) -> Vec<String> {
+        [&self.mastered, &self.learning, &self.struggling, &self.unseen].into_iter().flatten().cloned().collect()
+    }
+}
+
+impl SelectionStrategy {
+    pub fn select(&self, index: &VocabularyIndex) -> Selection {
+        // Mastered: longest intervals first, they are the most reliable foundation
+        let mut mastered: Vec<&VocabularyEntry> = index.with_status(WordStatus::Mastered).collect();
+        mastered.sort_by_key(|entry| std::cmp::Reverse(entry.interval));
+
+        // Learning: most recently reviewed first
+        let mut learning: Vec<&VocabularyEntry> = index.with_status(WordStatus::Learning).collect();
+        learning.sort_by_key(|entry| std::cmp::Reverse(entry.last_review));
+
+        // Struggling: most lapses, then lowest ease first
+        let mut struggling: Vec<&VocabularyEntry> = index.with_status(WordStatus::Struggling).collect();
+        struggling.sort_by(|a, b| b.lapses.cmp(&a.lapses).then(a.ease_factor.cmp(&b.ease_factor)));
+
+        let unseen: Vec<&VocabularyEntry> = index.with_status(WordStatus::Unseen).collect();
+
+        let take = |entries: Vec<&VocabularyEntry>, limit: usize| -> Vec<String> {
+            entries.into_iter().take(limit).map(|entry| entry.word.clone()).collect()
+        };
+
+        Selection {
+            mastered: take(mastered, self.max_mastered),
+            learning: take(learning, self.max_learning),
+            struggling: take(struggling, self.max_struggling),
+            unseen: take(unseen, self.max_unseen),
+        }
+    }
+
+    // Compact learner profile for the system prompt, or None for an empty index
+    pub fn summarize(&self, index: &VocabularyIndex) -> Option<String> {
+        if index.entries.is_empty() {
+            return None;
+        }
+
+        let selection = self.select(index);
+        let count = |status| index.with_status(status).count();
+
+        let mut lines = vec![format!(
+            "Learner profile from their Anki history ({} words: {} mastered, {} learning, {} struggling, {} not studied yet).",
+            index.entries.len(),
+            count(WordStatus::Mastered),
+            count(WordStatus::Learning),
+            count(WordStatus::Struggling),
+            count(WordStatus::Unseen),
+        )];
+
+        let mut section = |label: &str, words: &[String]| {
+            if !words.is_empty() {
+                lines.push(format!("- {}: {}", label, words.join(", ")));
+            }
+        };
+        section("Mastered (use freely in example sentences)", &selection.mastered);
+        section("Learning", &selection.learning);
+        section("Struggling (reinforce them in example sentences)", &selection.struggling);
+        section("Not studied yet", &selection.unseen);
+
+        lines.push(
+            "Build on what the learner already knows: introduce new words at a similar or slightly higher level and do not repeat the words listed above as new flashcards."
+                .to_string(),
+        );
+
+        Some(lines.join("\n"))
+    }
+}
@@ -0,0 +1,162 @@
+// Record and replay of LLM completions.
+//
+// `RecordingGenerator` wraps a backend and writes every request with its raw
+// reply to a fixture file; `ReplayGenerator` answers from those files without
+// a model. Fixtures are named after a hash of the user's request, the schema
+// and the turn, so a replay gets the reply recorded for the same request,
+// including repair turns. The system prompt is left out: it lists words from
+// the recording machine's Anki, which a replay elsewhere cannot reproduce.
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::WordcraftError;
+use crate::generator::{ChunkHandler, CompletionRequest, FlashcardGenerator, Role};
+
+// Where completions are recorded to or replayed from; at most one is set
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FixturesConfig {
+    pub record: Option<String>,
+    pub replay: Option<String>,
+}
+
+// One recorded call: who answered, what was asked and the raw reply
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fixture {
+    pub engine: String,
+    pub model: String,
+    pub request: CompletionRequest,
+    pub completion: String,
+}
+
+// Short hash of the first user message, the schema and the number of user
+// turns of a request
+pub fn fixture_key(request: &CompletionRequest) -> String {
+    let mut user_turns = request.messages.iter().filter(|message| message.role == Role::User);
+    let user_input = user_turns.next().map(|message| message.content.as_str()).unwrap_or_default();
+    let turn = user_turns.count();
+    let text = serde_json::to_string(&(user_input, &request.response_schema, turn)).unwrap_or_default();
+    let digest = Sha1::digest(text.as_bytes());
+    digest.iter().take(8).map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn fixture_path(dir: &Path, request: &CompletionRequest) -> PathBuf {
+    dir.join(format!("{}.json", fixture_key(request)))
+}
+
+pub struct RecordingGenerator {
+    inner: Box<dyn FlashcardGenerator>,
+    dir: PathBuf,
+}
+
+impl RecordingGenerator {
+    pub fn new(inner: Box<dyn FlashcardGenerator>, dir: impl Into<PathBuf>) -> Self {
+        RecordingGenerator { inner, dir: dir.into() }
+    }
+
+    // A request recorded again replaces the earlier fixture. The completion
+    // is already paid for, so a fixture that cannot be written only warns.
+    fn record(&self, request: &CompletionRequest, completion: &str) {
+        let fixture = Fixture {
+            engine: self.inner.engine().to_string(),
+            model: self.inner.model().to_string(),
+            request: request.clone(),
+            completion: completion.to_string(),
+        };
+        let path = fixture_path(&self.dir, request);
+        let written = std::fs::create_dir_all(&self.dir)
+            .and_then(|_| std::fs::write(&path, serde_json::to_string_pretty(&fixture)?));
+        if let Err(err) = written {
+            eprintln!("Could not record {}: {}", path.display(), err);
+        }
+    }
+}
+
+#[async_trait]
+impl FlashcardGenerator for RecordingGenerator {
+    fn engine(&self) -> &str {
+        self.inner.engine()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<String, WordcraftError> {
+        let completion = self.inner.complete(request).await?;
+        self.record(request, &completion);
+        Ok(completion)
+    }
+
+    async fn complete_stream(
+        &self,
+        request: &CompletionRequest,
+        on_chunk: &mut ChunkHandler<'_>,
+    ) -> Result<String, WordcraftError> {
+        let completion = self.inner.complete_stream(request, on_chunk).await?;
+        self.record(request, &completion);
+        Ok(completion)
+    }
+}
+
+pub struct ReplayGenerator {
+    dir: PathBuf,
+    // The directory, shown as the model
+    label: String,
+    fixtures: HashMap<String, Fixture>,
+}
+
+impl ReplayGenerator {
+    // Read every .json fixture in `dir`. They are looked up by their request,
+    // so the files can be renamed.
+    pub fn load(dir: impl Into<PathBuf>) -> Result<Self, WordcraftError> {
+        let dir = dir.into();
+        let unreadable = |err: std::io::Error| WordcraftError::Config(format!("Could not read fixtures from {}: {}", dir.display(), err));
+
+        let mut fixtures = HashMap::new();
+        for entry in std::fs::read_dir(&dir).map_err(unreadable)? {
+            let path = entry.map_err(unreadable)?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+                continue;
+            }
+            let text = std::fs::read_to_string(&path).map_err(unreadable)?;
+            let fixture: Fixture = serde_json::from_str(&text)
+                .map_err(|err| WordcraftError::Config(format!("{} is not a fixture: {}", path.display(), err)))?;
+            fixtures.insert(fixture_key(&fixture.request), fixture);
+        }
+
+        Ok(ReplayGenerator { label: dir.display().to_string(), dir, fixtures })
+    }
+
+    pub fn len(&self) -> usize {
+        self.fixtures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fixtures.is_empty()
+    }
+}
+
+#[async_trait]
+impl FlashcardGenerator for ReplayGenerator {
+    fn engine(&self) -> &str {
+        "replay"
+    }
+
+    fn model(&self) -> &str {
+        &self.label
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> Result<String, WordcraftError> {
+        match self.fixtures.get(&fixture_key(request)) {
+            Some(fixture) => Ok(fixture.completion.clone()),
+            None => Err(WordcraftError::llm_transport(format!(
+                "no recorded completion for this request ({} is missing); record it with --record {}",
+                fixture_path(&self.dir, request).display(),
+                self.dir.display()
+            ))),
+        }
+    }
+}
@@ -0,0 +1,414 @@
+// Terminal UI to review generated cards before they are added.
+//
+// `ReviewSession` holds the cards and the learner's verdict on each and turns
+// key presses into changes, so it can be tested without a terminal.
+// `review_cards` draws it with ratatui and runs the regenerate and add-more
+// requests through a `CardSource`.
+use async_trait::async_trait;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::error::WordcraftError;
+use crate::generator::FlashcardGenerator;
+use crate::generation::{generate_flashcards, Flashcard, GenerationOptions};
+use crate::note_type::NoteKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Pending,
+    Accepted,
+    Rejected,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReviewCard {
+    pub card: Flashcard,
+    pub verdict: Verdict,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mode {
+    Browse,
+    /// Editing field `field` of the selected card; `buffer` is saved on Enter.
+    Edit { field: usize, buffer: String },
+}
+
+// What the session needs from outside after a key press
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReviewRequest {
+    /// Replace the card at this index with a new one.
+    Regenerate(usize),
+    /// Ask for more cards on the same topic.
+    MoreCards,
+    /// Add the accepted cards.
+    Finish,
+    /// Add nothing.
+    Cancel,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReviewSession {
+    pub cards: Vec<ReviewCard>,
+    pub selected: usize,
+    pub mode: Mode,
+    // The card fields the note type uses, in the order Tab goes through them
+    pub fields: &'static [&'static str],
+    // Shown under the table: the outcome of the last request
+    pub status: String,
+}
+
+impl ReviewSession {
+    pub fn new(cards: Vec<Flashcard>, note_kind: NoteKind) -> Self {
+        ReviewSession {
+            cards: cards.into_iter().map(|card| ReviewCard { card, verdict: Verdict::Pending }).collect(),
+            selected: 0,
+            mode: Mode::Browse,
+            fields: note_kind.note_type().card_fields,
+            status: String::new(),
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<ReviewRequest> {
+        // Raw mode swallows the signal, so Ctrl-C is handled as a key
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Some(ReviewRequest::Cancel);
+        }
+        match self.mode {
+            Mode::Browse => self.handle_browse_key(key),
+            Mode::Edit { .. } => {
+                self.handle_edit_key(key);
+                None
+            }
+        }
+    }
+
+    fn handle_browse_key(&mut self, key: KeyEvent) -> Option<ReviewRequest> {
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(self.cards.len().saturating_sub(1)),
+            KeyCode::Char('a') => self.set_verdict(Verdict::Accepted),
+            KeyCode::Char('r') | KeyCode::Char('x') => self.set_verdict(Verdict::Rejected),
+            KeyCode::Char('A') => {
+                for card in self.cards.iter_mut().filter(|card| card.verdict == Verdict::Pending) {
+                    card.verdict = Verdict::Accepted;
+                }
+            }
+            KeyCode::Char('e') | KeyCode::Enter => self.start_edit(0),
+            KeyCode::Char('g') if !self.cards.is_empty() => return Some(ReviewRequest::Regenerate(self.selected)),
+            KeyCode::Char('m') => return Some(ReviewRequest::MoreCards),
+            KeyCode::Char('s') => return Some(ReviewRequest::Finish),
+            KeyCode::Char('q') | KeyCode::Esc => return Some(ReviewRequest::Cancel),
+            _ => {}
+        }
+        None
+    }
+
+    fn handle_edit_key(&mut self, key: KeyEvent) {
+        let count = self.fields.len();
+        let Mode::Edit { field, buffer } = &mut self.mode else {
+            return;
+        };
+        match key.code {
+            KeyCode::Char(c) => buffer.push(c),
+            KeyCode::Backspace => {
+                buffer.pop();
+            }
+            KeyCode::Tab => {
+                let next = (*field + 1) % count;
+                self.save_edit();
+                self.start_edit(next);
+            }
+            KeyCode::BackTab => {
+                let previous = (*field + count - 1) % count;
+                self.save_edit();
+                self.start_edit(previous);
+            }
+            KeyCode::Enter => {
+                self.save_edit();
+                self.mode = Mode::Browse;
+            }
+            KeyCode::Esc => self.mode = Mode::Browse,
+            _ => {}
+        }
+    }
+
+    fn set_verdict(&mut self, verdict: Verdict) {
+        if let Some(card) = self.cards.get_mut(self.selected) {
+            card.verdict = verdict;
+        }
+        // Move on, so a deck can be reviewed with a run of a/r presses
+        if self.selected + 1 < self.cards.len() {
+            self.selected += 1;
+        }
+    }
+
+    fn start_edit(&mut self, field: usize) {
+        if let Some(card) = self.cards.get(self.selected) {
+            let buffer = field_value(&card.card, self.fields[field]);
+            self.mode = Mode::Edit { field, buffer };
+        }
+    }
+
+    fn save_edit(&mut self) {
+        if let (Mode::Edit { field, buffer }, Some(card)) = (&self.mode, self.cards.get_mut(self.selected)) {
+            set_field(&mut card.card, self.fields[*field], buffer.clone());
+        }
+    }
+
+    // Put a regenerated card in place of the one at `index`, to be reviewed again
+    pub fn replace(&mut self, index: usize, card: Flashcard) {
+        if let Some(entry) = self.cards.get_mut(index) {
+            *entry = ReviewCard { card, verdict: Verdict::Pending };
+        }
+    }
+
+    pub fn extend(&mut self, cards: Vec<Flashcard>) {
+        self.cards.extend(cards.into_iter().map(|card| ReviewCard { card, verdict: Verdict::Pending }));
+    }
+
+    // Every front in the session, rejected ones included, so they are not generated again
+    pub fn fronts(&self) -> Vec<String> {
+        self.cards.iter().map(|card| card.card.front.clone()).collect()
+    }
+
+    pub fn count(&self, verdict: Verdict) -> usize {
+        self.cards.iter().filter(|card| card.verdict == verdict).count()
+    }
+
+    // The cards to add to Anki
+    pub fn approved(&self) -> Vec<Flashcard> {
+        self.cards.iter()
+            .filter(|card| card.verdict == Verdict::Accepted)
+            .map(|card| card.card.clone())
+            .collect()
+    }
+}
+
+// How a card field is labelled in the detail pane
+pub fn field_label(field: &str) -> &str {
+    match field {
+        "front" => "Front",
+        "back" => "Back",
+        "answer" => "Answer",
+        "example" => "Example",
+        "example_translate" => "Example Translation",
+        "cloze" => "Cloze",
+        other => other,
+    }
+}
+
+fn field_value(card: &Flashcard, field: &str) -> String {
+    match field {
+        "front" => card.front.clone(),
+        "back" => card.back.clone(),
+        "answer" => card.answer.clone().unwrap_or_default(),
+        "example" => card.example.clone(),
+        "cloze" => card.cloze.clone().unwrap_or_default(),
+        _ => card.example_translate.clone(),
+    }
+}
+
+// An emptied answer or cloze is removed, so the note type derives it again
+fn set_field(card: &mut Flashcard, field: &str, value: String) {
+    let optional = || Some(value.clone()).filter(|value| !value.trim().is_empty());
+    match field {
+        "front" => card.front = value,
+        "back" => card.back = value,
+        "answer" => card.answer = optional(),
+        "example" => card.example = value,
+        "cloze" => card.cloze = optional(),
+        _ => card.example_translate = value,
+    }
+}
+
+// Where regenerated and additional cards come from
+#[async_trait]
+pub trait CardSource: Send + Sync {
+    /// One new card to replace `card`, whose front is not in `existing`.
+    async fn replacement(&self, card: &Flashcard, existing: &[String]) -> Result<Flashcard, WordcraftError>;
+
+    /// More cards on the same topic, whose fronts are not in `existing`.
+    async fn more(&self, existing: &[String]) -> Result<Vec<Flashcard>, WordcraftError>;
+}
+
+// Asks the model that generated the deck, with the same prompt and options
+pub struct GeneratorSource<'a> {
+    pub generator: &'a dyn FlashcardGenerator,
+    // The generation prompt without a card count
+    pub prompt: String,
+    // How many cards `more` asks for; the model decides when None
+    pub count: Option<usize>,
+    pub options: GenerationOptions,
+}
+
+impl GeneratorSource<'_> {
+    async fn generate(&self, count: Option<usize>, rejected: Option<&Flashcard>, existing: &[String]) -> Result<Vec<Flashcard>, WordcraftError> {
+        let mut prompt = self.prompt.clone();
+        if let Some(card) = rejected {
+            prompt.push_str(&format!("Replace the rejected card: {} ({}), with a different word\n", card.front, card.back));
+        }
+        if let Some(count) = count {
+            prompt.push_str(&format!("Number of cards: {}\n", count));
+        }
+        let mut options = self.options.clone();
+        options.card_count = count;
+        options.exclude.extend(existing.iter().cloned());
+
+        let outcome = generate_flashcards(self.generator, &prompt, &options).await?;
+        Ok(outcome.response.cards)
+    }
+}
+
+#[async_trait]
+impl CardSource for GeneratorSource<'_> {
+    async fn replacement(&self, card: &Flashcard, existing: &[String]) -> Result<Flashcard, WordcraftError> {
+        self.generate(Some(1), Some(card), existing).await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| WordcraftError::LlmMalformedOutput {
+                raw: String::new(),
+                message: "The model returned no new card".to_string(),
+            })
+    }
+
+    async fn more(&self, existing: &[String]) -> Result<Vec<Flashcard>, WordcraftError> {
+        self.generate(self.count, None, existing).await
+    }
+}
+
+// Review `cards` in the terminal. Returns the accepted cards, or None when the
+// review was cancelled.
+pub async fn review_cards(
+    cards: Vec<Flashcard>,
+    note_kind: NoteKind,
+    source: &dyn CardSource,
+) -> Result<Option<Vec<Flashcard>>, WordcraftError> {
+    let mut terminal = ratatui::try_init()?;
+    let result = run_review(&mut terminal, ReviewSession::new(cards, note_kind), source).await;
+    ratatui::try_restore()?;
+    result
+}
+
+async fn run_review(
+    terminal: &mut DefaultTerminal,
+    mut session: ReviewSession,
+    source: &dyn CardSource,
+) -> Result<Option<Vec<Flashcard>>, WordcraftError> {
+    loop {
+        terminal.draw(|frame| draw(frame, &session))?;
+        // Wait for the key off the runtime's worker threads
+        let event = tokio::task::spawn_blocking(event::read).await.map_err(std::io::Error::other)??;
+        let Event::Key(key) = event else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match session.handle_key(key) {
+            None => {}
+            Some(ReviewRequest::Finish) => return Ok(Some(session.approved())),
+            Some(ReviewRequest::Cancel) => return Ok(None),
+            Some(ReviewRequest::Regenerate(index)) => {
+                session.status = "Regenerating the card...".to_string();
+                terminal.draw(|frame| draw(frame, &session))?;
+                let card = session.cards[index].card.clone();
+                session.status = match source.replacement(&card, &session.fronts()).await {
+                    Ok(new_card) => {
+                        let message = format!("Replaced '{}' with '{}'.", card.front, new_card.front);
+                        session.replace(index, new_card);
+                        message
+                    }
+                    Err(err) => format!("Could not regenerate the card: {}", err),
+                };
+            }
+            Some(ReviewRequest::MoreCards) => {
+                session.status = "Generating more cards...".to_string();
+                terminal.draw(|frame| draw(frame, &session))?;
+                session.status = match source.more(&session.fronts()).await {
+                    Ok(cards) => {
+                        let message = format!("Added {} cards.", cards.len());
+                        session.extend(cards);
+                        message
+                    }
+                    Err(err) => format!("Could not generate more cards: {}", err),
+                };
+            }
+        }
+    }
+}
+
+// The card table, the selected card's fields and a line of key help
+pub fn draw(frame: &mut Frame, session: &ReviewSession) {
+    let [table_area, detail_area, status_area, help_area] = Layout::vertical([
+        Constraint::Min(5),
+        Constraint::Length(session.fields.len() as u16 + 2),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let rows = session.cards.iter().map(|entry| {
+        let (mark, style) = match entry.verdict {
+            Verdict::Pending => ("[ ]", Style::default()),
+            Verdict::Accepted => ("[+]", Style::default().fg(Color::Green)),
+            Verdict::Rejected => ("[-]", Style::default().fg(Color::DarkGray).add_modifier(Modifier::CROSSED_OUT)),
+        };
+        Row::new(vec![
+            mark.to_string(),
+            entry.card.front.clone(),
+            entry.card.back.clone(),
+            entry.card.example.clone(),
+        ])
+        .style(style)
+    });
+    let title = format!(
+        " Review cards: {} accepted, {} rejected, {} pending ",
+        session.count(Verdict::Accepted),
+        session.count(Verdict::Rejected),
+        session.count(Verdict::Pending)
+    );
+    let table = Table::new(rows, [
+        Constraint::Length(3),
+        Constraint::Percentage(25),
+        Constraint::Percentage(30),
+        Constraint::Fill(1),
+    ])
+    .header(Row::new(vec!["", "Front", "Back", "Example"]).style(Style::default().add_modifier(Modifier::BOLD)))
+    .block(Block::bordered().title(title))
+    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = TableState::default().with_selected((!session.cards.is_empty()).then_some(session.selected));
+    frame.render_stateful_widget(table, table_area, &mut state);
+
+    if let Some(entry) = session.cards.get(session.selected) {
+        let lines: Vec<Line> = session.fields.iter().enumerate().map(|(index, field)| {
+            let name = field_label(field);
+            match &session.mode {
+                Mode::Edit { field, buffer } if *field == index => Line::from(vec![
+                    Span::styled(format!("{}: ", name), Style::default().add_modifier(Modifier::BOLD)),
+                    Span::styled(format!("{}_", buffer), Style::default().fg(Color::Yellow)),
+                ]),
+                _ => Line::from(vec![
+                    Span::styled(format!("{}: ", name), Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(field_value(&entry.card, field)),
+                ]),
+            }
+        }).collect();
+        let detail = Paragraph::new(lines)
+            .block(Block::bordered().title(format!(" Card {} of {} ", session.selected + 1, session.cards.len())))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(detail, detail_area);
+    }
+
+    frame.render_widget(Paragraph::new(session.status.as_str()), status_area);
+
+    let help = match session.mode {
+        Mode::Browse => "j/k move  a accept  r reject  A accept rest  e edit  g regenerate  m more  s add accepted  q quit",
+        Mode::Edit { .. } => "Tab next field  Shift-Tab previous field  Enter save  Esc discard",
+    };
+    frame.render_widget(Paragraph::new(help).style(Style::default().fg(Color::DarkGray)), help_area);
+}
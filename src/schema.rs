@@ -0,0 +1,136 @@
+// JSON schema for the model's reply, derived from `FlashcardResponse`.
+//
+// The same schema is sent to the backends as a structured-output constraint
+// and used by the parser to check the reply, so the two cannot drift apart.
+use schemars::gen::SchemaSettings;
+use serde_json::Value;
+
+use crate::generation::FlashcardResponse;
+use crate::note_type::NoteKind;
+
+pub const SCHEMA_NAME: &str = "flashcard_response";
+
+// Self-contained schema (no $ref) accepted by OpenAI strict mode and Ollama
+pub fn flashcard_response_schema() -> Value {
+    flashcard_response_schema_for(NoteKind::Basic)
+}
+
+// Reply schema for a note type: cards carry exactly the fields the note type
+// needs, all required as strict mode expects
+pub fn flashcard_response_schema_for(kind: NoteKind) -> Value {
+    let mut schema = derived_schema();
+    let card_fields = kind.note_type().card_fields;
+
+    if let Some(card) = schema.pointer_mut("/properties/cards/items").and_then(Value::as_object_mut) {
+        let mut required: Vec<&str> = card_fields.to_vec();
+        required.sort_unstable();
+        let properties: serde_json::Map<String, Value> = card_fields.iter()
+            .map(|field| (field.to_string(), serde_json::json!({ "type": "string" })))
+            .collect();
+
+        card.insert("properties".to_string(), Value::Object(properties));
+        card.insert("required".to_string(), serde_json::json!(required));
+    }
+
+    schema
+}
+
+fn derived_schema() -> Value {
+    let generator = SchemaSettings::draft07()
+        .with(|settings| {
+            settings.inline_subschemas = true;
+            settings.meta_schema = None;
+        })
+        .into_generator();
+
+    let mut schema = serde_json::to_value(generator.into_root_schema_for::<FlashcardResponse>())
+        .unwrap_or_default();
+    if let Some(object) = schema.as_object_mut() {
+        object.remove("title");
+        object.remove("definitions");
+    }
+
+    schema
+}
+
+// Check a value against the subset of JSON schema the derived schema uses:
+// type, properties, required, additionalProperties and items.
+// Returns every violation with the JSON path where it occurred.
+pub fn validate(value: &Value, schema: &Value) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    validate_at(value, schema, "$", &mut errors);
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn validate_at(value: &Value, schema: &Value, path: &str, errors: &mut Vec<String>) {
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|name| matches_type(value, name)) {
+            errors.push(format!("{}: expected {}, found {}", path, allowed.join(" or "), type_name(value)));
+            return;
+        }
+    }
+
+    if let Value::Object(object) = value {
+        let properties = schema.get("properties").and_then(Value::as_object);
+
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    errors.push(format!("{}: missing required field '{}'", path, name));
+                }
+            }
+        }
+
+        for (name, field) in object {
+            let field_path = format!("{}.{}", path, name);
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(field_schema) => validate_at(field, field_schema, &field_path, errors),
+                None => {
+                    if schema.get("additionalProperties") == Some(&Value::Bool(false)) {
+                        errors.push(format!("{}: unexpected field", field_path));
+                    }
+                }
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (index, item) in items.iter().enumerate() {
+            validate_at(item, item_schema, &format!("{}[{}]", path, index), errors);
+        }
+    }
+}
+
+fn matches_type(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
@@ -1,4 +1,5 @@
 #[cfg(test)]
+#[allow(dead_code)]
 pub mod test_helpers {
     use crate::{Flashcard, FlashcardResponse};
     
@@ -8,6 +9,7 @@ pub mod test_helpers {
             back: back.to_string(),
             example: format!("Example with {}", front),
             example_translate: format!("Translation of example with {}", back),
+            ..Default::default()
         }
     }
     
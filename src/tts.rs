@@ -0,0 +1,304 @@
+// Text-to-speech for the FrontAudio and ExampleAudio fields.
+//
+// Audio is stored as Anki media under a name derived from its bytes, so the
+// same recording always gets the same file name and re-running Wordcraft does
+// not fill the media folder with copies.
+use async_trait::async_trait;
+use sha1::{Digest, Sha1};
+use std::env;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::error::WordcraftError;
+use crate::generation::Flashcard;
+use crate::note_type::plain_word;
+
+pub const DEFAULT_TTS_EXTENSION: &str = "wav";
+
+// tts.command / TTS_COMMAND selects the command; tts.extension / TTS_EXTENSION
+// the kind of file it writes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TtsConfig {
+    pub command: Option<String>,
+    pub extension: String,
+}
+
+#[async_trait]
+pub trait TtsEngine: Send + Sync {
+    /// Short name of the backend, e.g. `"espeak-ng"`.
+    fn name(&self) -> &str;
+
+    /// Extension of the audio files `synthesize` produces, without the dot.
+    fn extension(&self) -> &str;
+
+    /// Speak `text` in `language`, an ISO 639-1 code such as `"ja"`, and
+    /// return the encoded audio.
+    async fn synthesize(&self, text: &str, language: &str) -> Result<Vec<u8>, WordcraftError>;
+}
+
+// Runs a configured command such as
+//   espeak-ng -v {lang} -w {output} {text}
+//   piper --model ja_JP-voice.onnx --output_file {output}
+// The command is split like a shell would, so quoted arguments may contain
+// spaces. {text}, {lang} and {output} are replaced in every argument. Without
+// {text} the text is written to the command's stdin, without {output} the
+// audio is read from its stdout.
+pub struct CommandTts {
+    program: String,
+    args: Vec<String>,
+    extension: String,
+}
+
+static NEXT_OUTPUT: AtomicUsize = AtomicUsize::new(0);
+
+impl CommandTts {
+    pub fn new(command: &str, extension: &str) -> Result<Self, WordcraftError> {
+        let mut parts = shlex::split(command)
+            .ok_or_else(|| WordcraftError::Config(format!("TTS command has an unclosed quote: {}", command)))?
+            .into_iter();
+        let program = parts.next().ok_or_else(|| WordcraftError::Config("TTS command is empty".to_string()))?;
+
+        Ok(CommandTts {
+            program,
+            args: parts.collect(),
+            extension: extension.trim_start_matches('.').to_string(),
+        })
+    }
+
+    // None when no command is configured
+    pub fn from_config(config: &TtsConfig) -> Result<Option<Self>, WordcraftError> {
+        match &config.command {
+            Some(command) if !command.trim().is_empty() => CommandTts::new(command, &config.extension).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl TtsEngine for CommandTts {
+    fn name(&self) -> &str {
+        &self.program
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    async fn synthesize(&self, text: &str, language: &str) -> Result<Vec<u8>, WordcraftError> {
+        let output = env::temp_dir().join(format!(
+            "wordcraft-tts-{}-{}.{}",
+            std::process::id(),
+            NEXT_OUTPUT.fetch_add(1, Ordering::Relaxed),
+            self.extension
+        ));
+        let output_arg = output.to_string_lossy().to_string();
+        let text_in_args = self.args.iter().any(|arg| arg.contains("{text}"));
+        let output_in_args = self.args.iter().any(|arg| arg.contains("{output}"));
+
+        let args: Vec<String> = self.args.iter()
+            .map(|arg| arg.replace("{lang}", language).replace("{output}", &output_arg).replace("{text}", text))
+            .collect();
+        let mut child = Command::new(&self.program)
+            .args(&args)
+            .stdin(if text_in_args { Stdio::null() } else { Stdio::piped() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| WordcraftError::Tts(format!("Could not run {}: {}", self.program, err)))?;
+
+        let failed = |err: std::io::Error| WordcraftError::Tts(format!("{} failed: {}", self.program, err));
+        // A command may exit without reading its input; its exit status says
+        // whether that was a failure
+        if let Some(mut stdin) = child.stdin.take() {
+            match stdin.write_all(text.as_bytes()).await {
+                Err(err) if err.kind() != std::io::ErrorKind::BrokenPipe => return Err(failed(err)),
+                _ => {}
+            }
+        }
+        let result = child.wait_with_output().await.map_err(failed)?;
+        if !result.status.success() {
+            let _ = std::fs::remove_file(&output);
+            return Err(WordcraftError::Tts(format!(
+                "{} exited with {}: {}",
+                self.program,
+                result.status,
+                String::from_utf8_lossy(&result.stderr).trim()
+            )));
+        }
+
+        let audio = if output_in_args {
+            let audio = std::fs::read(&output)
+                .map_err(|err| WordcraftError::Tts(format!("{} wrote no audio to {}: {}", self.program, output.display(), err)))?;
+            let _ = std::fs::remove_file(&output);
+            audio
+        } else {
+            result.stdout
+        };
+
+        if audio.is_empty() {
+            return Err(WordcraftError::Tts(format!("{} produced no audio", self.program)));
+        }
+        Ok(audio)
+    }
+}
+
+// Fake backend for tests: returns a silent WAV whose length follows the
+// text and records every request
+#[derive(Default)]
+pub struct SilentTts {
+    calls: Mutex<Vec<(String, String)>>,
+}
+
+impl SilentTts {
+    pub fn new() -> Self {
+        SilentTts::default()
+    }
+
+    // (text, language) of every synthesize call so far
+    pub fn calls(&self) -> Vec<(String, String)> {
+        self.calls.lock().map(|calls| calls.clone()).unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl TtsEngine for SilentTts {
+    fn name(&self) -> &str {
+        "silent"
+    }
+
+    fn extension(&self) -> &str {
+        "wav"
+    }
+
+    async fn synthesize(&self, text: &str, language: &str) -> Result<Vec<u8>, WordcraftError> {
+        if let Ok(mut calls) = self.calls.lock() {
+            calls.push((text.to_string(), language.to_string()));
+        }
+        Ok(silent_wav(text.chars().count() * 800))
+    }
+}
+
+// 8 kHz, 8-bit mono PCM of `samples` samples of silence
+pub fn silent_wav(samples: usize) -> Vec<u8> {
+    let data_len = samples as u32;
+    let mut wav = Vec::with_capacity(44 + samples);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&8000u32.to_le_bytes());
+    wav.extend_from_slice(&8000u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&8u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.resize(44 + samples, 0x80);
+    wav
+}
+
+// An audio file to store in Anki's media folder
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaFile {
+    pub filename: String,
+    pub data: Vec<u8>,
+}
+
+impl MediaFile {
+    pub fn new(data: Vec<u8>, extension: &str) -> Self {
+        MediaFile { filename: media_filename(&data, extension), data }
+    }
+}
+
+// "wordcraft-<sha1 of the bytes>.<extension>"
+pub fn media_filename(data: &[u8], extension: &str) -> String {
+    let digest = Sha1::digest(data);
+    let hash: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("wordcraft-{}.{}", hash, extension.trim_start_matches('.'))
+}
+
+// Speak the card's word and example and point its audio fields at the
+// results. The card is only changed when both succeed.
+pub async fn add_audio(
+    tts: &dyn TtsEngine,
+    card: &mut Flashcard,
+    language: &str,
+) -> Result<Vec<MediaFile>, WordcraftError> {
+    let mut files = Vec::new();
+
+    let front = match plain_word(&card.front) {
+        word if word.is_empty() => None,
+        word => Some(MediaFile::new(tts.synthesize(&word, language).await?, tts.extension())),
+    };
+    let example = match card.example.trim() {
+        "" => None,
+        example => Some(MediaFile::new(tts.synthesize(example, language).await?, tts.extension())),
+    };
+
+    card.front_audio = front.as_ref().map(|file| file.filename.clone());
+    card.example_audio = example.as_ref().map(|file| file.filename.clone());
+    files.extend(front);
+    files.extend(example.filter(|file| !files.contains(file)));
+
+    Ok(files)
+}
+
+// Speak every card with `tts`, if any. A card whose audio fails is kept
+// without it.
+pub async fn synthesize_audio(tts: Option<&dyn TtsEngine>, cards: &mut [Flashcard], language: &str) -> Vec<MediaFile> {
+    let Some(tts) = tts else {
+        return Vec::new();
+    };
+
+    println!("Generating audio with {}...", tts.name());
+    let language = language_code(language);
+    let mut media: Vec<MediaFile> = Vec::new();
+    for card in cards.iter_mut() {
+        match add_audio(tts, card, &language).await {
+            Ok(files) => {
+                for file in files {
+                    if !media.contains(&file) {
+                        media.push(file);
+                    }
+                }
+            }
+            Err(err) => eprintln!("No audio for '{}': {}", card.front, err),
+        }
+    }
+    media
+}
+
+// ISO 639-1 code for a language name as the learner types it, e.g.
+// "Japanese" -> "ja". Unknown names are passed through lowercased, so a code
+// or a voice name can be given directly.
+pub fn language_code(language: &str) -> String {
+    let language = language.trim().to_lowercase();
+    let code = match language.as_str() {
+        "arabic" => "ar",
+        "chinese" | "mandarin" => "zh",
+        "dutch" => "nl",
+        "english" => "en",
+        "french" => "fr",
+        "german" => "de",
+        "hindi" => "hi",
+        "indonesian" => "id",
+        "italian" => "it",
+        "japanese" => "ja",
+        "korean" => "ko",
+        "polish" => "pl",
+        "portuguese" => "pt",
+        "russian" => "ru",
+        "spanish" => "es",
+        "swedish" => "sv",
+        "thai" => "th",
+        "turkish" => "tr",
+        "ukrainian" => "uk",
+        "vietnamese" => "vi",
+        _ => return language,
+    };
+    code.to_string()
+}
@@ -1,33 +1,33 @@
-use autoflashcard::anki_adapter::AnkiAdapter;
-use mockito::{Mock, Server};
+use autoflashcard::anki_adapter::{AnkiAdapter, CardOutcome};
+use autoflashcard::generation::Flashcard;
+use autoflashcard::anki_connect::FindNotes;
+use autoflashcard::config::Config;
+use autoflashcard::note_type::NoteKind;
+use autoflashcard::tts::MediaFile;
+use autoflashcard::WordcraftError;
 use serde_json::json;
 use serial_test::serial;
 
 async fn setup_mock_server() -> (mockito::ServerGuard, AnkiAdapter) {
     let server = mockito::Server::new_async().await;
     let url = server.url();
-    std::env::set_var("ANKI_CONNECT_URL", &url);
     
-    let adapter = AnkiAdapter::new().expect("Failed to create adapter");
+    let adapter = AnkiAdapter::new(&url);
     (server, adapter)
 }
 
 #[tokio::test]
-#[serial]
-async fn test_anki_adapter_new_with_default_url() {
-    std::env::remove_var("ANKI_CONNECT_URL");
-    let adapter = AnkiAdapter::new().expect("Failed to create adapter");
-    // Note: url field is private, so we'll test by checking the behavior instead
-    // This test would pass if the adapter connects to the default URL
-}
+async fn test_anki_adapter_from_config_uses_configured_url() {
+    let mut server = mockito::Server::new_async().await;
+    let config = Config { anki_connect_url: server.url(), ..Config::default() };
+    let version = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({ "action": "version" }).to_string()))
+        .with_body(json!({ "result": 6, "error": null }).to_string())
+        .expect(1)
+        .create();
 
-#[tokio::test]
-#[serial]
-async fn test_anki_adapter_new_with_custom_url() {
-    std::env::set_var("ANKI_CONNECT_URL", "http://custom:9999");
-    let adapter = AnkiAdapter::new().expect("Failed to create adapter");
-    // Note: url field is private, so we'll test by checking the behavior instead
-    std::env::remove_var("ANKI_CONNECT_URL");
+    AnkiAdapter::from_config(&config).check_connection().await.expect("Connection failed");
+    version.assert();
 }
 
 #[tokio::test]
@@ -102,6 +102,53 @@ async fn test_create_deck_already_exists() {
     assert!(result.is_ok()); // Should not return error, just print message
 }
 
+#[tokio::test]
+#[serial]
+async fn test_create_deck_failure_is_returned() {
+    let (mut server, adapter) = setup_mock_server().await;
+
+    let _m = server.mock("POST", "/")
+        .with_body(json!({
+            "result": null,
+            "error": "collection is not available"
+        }).to_string())
+        .create();
+
+    match adapter.create_deck("TestDeck").await {
+        Err(WordcraftError::AnkiActionFailed { action, message }) => {
+            assert_eq!(action, "createDeck");
+            assert_eq!(message, "Error creating deck: AnkiConnect error (createDeck): collection is not available");
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_unreachable_anki_is_reported_as_unavailable() {
+    let adapter = AnkiAdapter::new("http://127.0.0.1:9");
+
+    match adapter.check_connection().await {
+        Err(WordcraftError::AnkiUnavailable { url, .. }) => assert_eq!(url, "http://127.0.0.1:9"),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_duplicate_card_is_reported_as_duplicate() {
+    let (mut server, adapter) = setup_mock_server().await;
+
+    let _m = server.mock("POST", "/")
+        .with_body(json!({
+            "result": null,
+            "error": "cannot create note because it is a duplicate"
+        }).to_string())
+        .create();
+
+    let result = adapter.add_card("TestDeck", "rojo", "red", "Es rojo.", "It is red.").await;
+    assert!(matches!(result, Err(WordcraftError::DuplicateNote { front }) if front == "rojo"));
+}
+
 #[tokio::test]
 #[serial]
 async fn test_add_card_success() {
@@ -184,9 +231,116 @@ async fn test_ensure_wordcraft_model_exists_already_exists() {
             "error": null
         }).to_string())
         .create();
+
+    let _styling = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "modelStyling"
+        }).to_string()))
+        .with_body(json!({
+            "result": { "css": NoteKind::Basic.note_type().styling() },
+            "error": null
+        }).to_string())
+        .create();
+
+    let update = server.mock("POST", "/")
+        .match_body(mockito::Matcher::Regex("updateModel".to_string()))
+        .expect(0)
+        .create();
     
     let result = adapter.ensure_wordcraft_model_exists().await;
     assert!(result.is_ok());
+    update.assert();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_migrate_note_type_adds_fields_and_updates_model() {
+    let (mut server, adapter) = setup_mock_server().await;
+
+    let _styling = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "modelStyling",
+            "params": { "modelName": "Wordcraft Type Answer" }
+        }).to_string()))
+        .with_body(json!({
+            "result": { "css": ".card { font-family: arial; }" },
+            "error": null
+        }).to_string())
+        .create();
+
+    let _fields = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "modelFieldNames"
+        }).to_string()))
+        .with_body(json!({
+            "result": ["Front", "Back", "Example", "ExampleTranslation"],
+            "error": null
+        }).to_string())
+        .create();
+
+    let field_add = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "modelFieldAdd",
+            "params": { "modelName": "Wordcraft Type Answer", "fieldName": "Answer", "index": 2 }
+        }).to_string()))
+        .with_body(json!({ "result": null, "error": null }).to_string())
+        .expect(1)
+        .create();
+
+    let audio_fields: Vec<mockito::Mock> = [("FrontAudio", 5), ("ExampleAudio", 6)]
+        .into_iter()
+        .map(|(field, index)| server.mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJsonString(json!({
+                "action": "modelFieldAdd",
+                "params": { "modelName": "Wordcraft Type Answer", "fieldName": field, "index": index }
+            }).to_string()))
+            .with_body(json!({ "result": null, "error": null }).to_string())
+            .expect(1)
+            .create())
+        .collect();
+
+    let templates = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "updateModelTemplates",
+            "params": { "model": { "name": "Wordcraft Type Answer" } }
+        }).to_string()))
+        .with_body(json!({ "result": null, "error": null }).to_string())
+        .expect(1)
+        .create();
+
+    let styling = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "updateModelStyling",
+            "params": { "model": { "name": "Wordcraft Type Answer", "css": NoteKind::TypeAnswer.note_type().styling() } }
+        }).to_string()))
+        .with_body(json!({ "result": null, "error": null }).to_string())
+        .expect(1)
+        .create();
+
+    let migrated = adapter.migrate_note_type(NoteKind::TypeAnswer).await.expect("migration failed");
+
+    assert_eq!(migrated, Some(1));
+    field_add.assert();
+    audio_fields.iter().for_each(|mock| mock.assert());
+    templates.assert();
+    styling.assert();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_migrate_note_type_reports_failures() {
+    let (mut server, adapter) = setup_mock_server().await;
+
+    let _styling = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "modelStyling"
+        }).to_string()))
+        .with_body(json!({ "result": null, "error": "model was not found: Wordcraft" }).to_string())
+        .create();
+
+    let err = adapter.migrate_note_type(NoteKind::Basic).await.unwrap_err().to_string();
+    assert!(err.starts_with("Error migrating Wordcraft model:"));
+    assert!(err.contains("model was not found"));
 }
 
 #[tokio::test]
@@ -226,6 +380,75 @@ async fn test_ensure_wordcraft_model_creates_new() {
     model_create.assert();
 }
 
+#[tokio::test]
+#[serial]
+async fn test_ensure_note_type_creates_cloze_model() {
+    let (mut server, adapter) = setup_mock_server().await;
+
+    let _model_check = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "modelNames"
+        }).to_string()))
+        .with_body(json!({
+            "result": ["Basic", "Wordcraft"],
+            "error": null
+        }).to_string())
+        .create();
+
+    let model_create = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "createModel",
+            "params": {
+                "modelName": "Wordcraft Cloze",
+                "inOrderFields": ["Text", "Front", "Back", "ExampleTranslation"],
+                "isCloze": true
+            }
+        }).to_string()))
+        .with_body(json!({
+            "result": 1234567891,
+            "error": null
+        }).to_string())
+        .expect(1)
+        .create();
+
+    adapter.ensure_note_type_exists(NoteKind::Cloze).await.expect("model creation failed");
+
+    model_create.assert();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_add_cards_as_uses_the_note_type_fields() {
+    let (mut server, adapter) = setup_mock_server().await;
+
+    let _can_add = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "canAddNotesWithErrorDetail"
+        }).to_string()))
+        .with_body(json!({ "result": [{ "canAdd": true }], "error": null }).to_string())
+        .create();
+
+    let add_notes = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "addNotes",
+            "params": {
+                "notes": [{
+                    "deckName": "Colors",
+                    "modelName": "Wordcraft Type Answer",
+                    "fields": { "Answer": "赤", "Front": "赤" }
+                }]
+            }
+        }).to_string()))
+        .with_body(json!({ "result": [1001], "error": null }).to_string())
+        .expect(1)
+        .create();
+
+    let report = adapter.add_cards_as("Colors", &[batch_card("赤")], NoteKind::TypeAnswer).await.expect("Batch insertion failed");
+
+    assert_eq!(report.added(), vec![1001]);
+    add_notes.assert();
+}
+
 #[tokio::test]
 #[serial]
 async fn test_ensure_wordcraft_model_create_error() {
@@ -267,4 +490,293 @@ async fn test_connection_timeout() {
     
     let result = adapter.check_connection().await;
     assert!(result.is_err());
-}
\ No newline at end of file
+}
+#[tokio::test]
+#[serial]
+async fn test_invoke_sends_params_and_unwraps_result() {
+    let (mut server, adapter) = setup_mock_server().await;
+
+    let _m = server.mock("POST", "/")
+        .match_body(mockito::Matcher::JsonString(json!({
+            "action": "findNotes",
+            "version": 6,
+            "params": {
+                "query": "deck:\"Japanese Colors\""
+            }
+        }).to_string()))
+        .with_body(json!({
+            "result": [1496198395707i64, 1496198395708i64],
+            "error": null
+        }).to_string())
+        .create();
+
+    let notes = adapter.invoke(FindNotes { query: "deck:\"Japanese Colors\"".to_string() }).await
+        .expect("findNotes failed");
+    assert_eq!(notes, vec![1496198395707, 1496198395708]);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_invoke_reports_action_name_on_error() {
+    let (mut server, adapter) = setup_mock_server().await;
+
+    let _m = server.mock("POST", "/")
+        .with_body(json!({
+            "result": null,
+            "error": "collection is not available"
+        }).to_string())
+        .create();
+
+    let result = adapter.deck_names().await;
+    let message = result.unwrap_err().to_string();
+    assert!(message.contains("deckNames"));
+    assert!(message.contains("collection is not available"));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_notes_info_deserializes_fields() {
+    let (mut server, adapter) = setup_mock_server().await;
+
+    let _m = server.mock("POST", "/")
+        .match_body(mockito::Matcher::JsonString(json!({
+            "action": "notesInfo",
+            "version": 6,
+            "params": { "notes": [42] }
+        }).to_string()))
+        .with_body(json!({
+            "result": [{
+                "noteId": 42,
+                "modelName": "Wordcraft",
+                "tags": ["wordcraft"],
+                "fields": {
+                    "Front": { "value": "赤 (あか) (aka)", "order": 0 },
+                    "Back": { "value": "Red", "order": 1 }
+                },
+                "cards": [4242]
+            }],
+            "error": null
+        }).to_string())
+        .create();
+
+    let notes = adapter.notes_info(&[42]).await.expect("notesInfo failed");
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].model_name, "Wordcraft");
+    assert_eq!(notes[0].fields["Front"].value, "赤 (あか) (aka)");
+    assert_eq!(notes[0].cards, vec![4242]);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_store_media_file_returns_filename() {
+    let (mut server, adapter) = setup_mock_server().await;
+
+    let _m = server.mock("POST", "/")
+        .match_body(mockito::Matcher::JsonString(json!({
+            "action": "storeMediaFile",
+            "version": 6,
+            "params": { "filename": "wordcraft_hello.mp3", "data": "SGVsbG8=" }
+        }).to_string()))
+        .with_body(json!({
+            "result": "wordcraft_hello.mp3",
+            "error": null
+        }).to_string())
+        .create();
+
+    let stored = adapter.store_media_file("wordcraft_hello.mp3", "SGVsbG8=").await
+        .expect("storeMediaFile failed");
+    assert_eq!(stored, "wordcraft_hello.mp3");
+}
+
+#[tokio::test]
+#[serial]
+async fn test_gui_deck_overview() {
+    let (mut server, adapter) = setup_mock_server().await;
+
+    let _m = server.mock("POST", "/")
+        .match_body(mockito::Matcher::JsonString(json!({
+            "action": "guiDeckOverview",
+            "version": 6,
+            "params": { "name": "Default" }
+        }).to_string()))
+        .with_body(json!({
+            "result": true,
+            "error": null
+        }).to_string())
+        .create();
+
+    assert!(adapter.gui_deck_overview("Default").await.expect("guiDeckOverview failed"));
+}
+
+fn batch_card(front: &str) -> Flashcard {
+    Flashcard {
+        front: front.to_string(),
+        back: format!("{} (back)", front),
+        example: format!("{} example", front),
+        example_translate: format!("{} translation", front),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_add_cards_reports_added_duplicates_and_failures() {
+    let (mut server, adapter) = setup_mock_server().await;
+
+    let can_add = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "canAddNotesWithErrorDetail",
+            "version": 6
+        }).to_string()))
+        .with_body(json!({
+            "result": [
+                { "canAdd": true },
+                { "canAdd": false, "error": "cannot create note because it is a duplicate" },
+                { "canAdd": true },
+                { "canAdd": false, "error": "deck was not found: Colors" }
+            ],
+            "error": null
+        }).to_string())
+        .expect(1)
+        .create();
+
+    let add_notes = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "addNotes",
+            "version": 6
+        }).to_string()))
+        .with_body(json!({
+            "result": [1001, null],
+            "error": null
+        }).to_string())
+        .expect(1)
+        .create();
+
+    let cards = vec![
+        batch_card("赤"),
+        batch_card("青"),
+        batch_card("赤"),
+        batch_card("緑"),
+        batch_card("紫"),
+        batch_card("  "),
+    ];
+
+    let report = adapter.add_cards("Colors", &cards).await.expect("Batch insertion failed");
+
+    assert_eq!(report.entries.len(), 6);
+    assert_eq!(report.entries[0].outcome, CardOutcome::Added(1001));
+    assert_eq!(report.entries[1].outcome, CardOutcome::Duplicate);
+    assert_eq!(report.entries[2].outcome, CardOutcome::Duplicate);
+    assert!(matches!(report.entries[3].outcome, CardOutcome::Failed(_)));
+    assert_eq!(report.entries[4].outcome, CardOutcome::Failed("deck was not found: Colors".to_string()));
+    assert!(matches!(report.entries[5].outcome, CardOutcome::Failed(_)));
+    assert_eq!(report.added(), vec![1001]);
+    assert_eq!(report.duplicates(), vec!["青", "赤"]);
+    assert_eq!(report.failures().len(), 3);
+
+    can_add.assert();
+    add_notes.assert();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_add_cards_adds_one_by_one_when_add_notes_fails() {
+    let (mut server, adapter) = setup_mock_server().await;
+
+    let _can_add = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "canAddNotesWithErrorDetail"
+        }).to_string()))
+        .with_body(json!({
+            "result": [{ "canAdd": true }, { "canAdd": true }, { "canAdd": true }],
+            "error": null
+        }).to_string())
+        .create();
+
+    let _add_notes = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "addNotes"
+        }).to_string()))
+        .with_body(json!({
+            "result": null,
+            "error": "['cannot create note because it is a duplicate']"
+        }).to_string())
+        .create();
+
+    let mut add_note = |front: &str, body: serde_json::Value| {
+        server.mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJsonString(json!({
+                "action": "addNote",
+                "params": { "note": { "fields": { "Front": front } } }
+            }).to_string()))
+            .with_body(body.to_string())
+            .expect(1)
+            .create()
+    };
+    let added = add_note("赤", json!({ "result": 1001, "error": null }));
+    let duplicate = add_note("青", json!({ "result": null, "error": "cannot create note because it is a duplicate" }));
+    let rejected = add_note("緑", json!({ "result": null, "error": "model was not found: Wordcraft" }));
+
+    let report = adapter.add_cards("Colors", &[batch_card("赤"), batch_card("青"), batch_card("緑")]).await
+        .expect("Batch insertion should report failures instead of erroring");
+
+    assert_eq!(report.added(), vec![1001]);
+    assert_eq!(report.duplicates(), vec!["青"]);
+    assert_eq!(report.failures(), vec![("緑", "AnkiConnect error (addNote): model was not found: Wordcraft")]);
+    added.assert();
+    duplicate.assert();
+    rejected.assert();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_fetch_deck_fronts() {
+    let (mut server, adapter) = setup_mock_server().await;
+
+    let _find = server.mock("POST", "/")
+        .match_body(mockito::Matcher::JsonString(json!({
+            "action": "findNotes",
+            "version": 6,
+            "params": { "query": "deck:\"Places in Japanese\"" }
+        }).to_string()))
+        .with_body(json!({ "result": [1, 2], "error": null }).to_string())
+        .create();
+
+    let _info = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "notesInfo",
+            "params": { "notes": [1, 2] }
+        }).to_string()))
+        .with_body(json!({
+            "result": [
+                { "noteId": 1, "modelName": "Wordcraft", "tags": [], "cards": [],
+                  "fields": { "Front": { "value": "<b>家</b> (いえ) (ie)", "order": 0 } } },
+                { "noteId": 2, "modelName": "Basic", "tags": [], "cards": [],
+                  "fields": { "Front": { "value": "駅&nbsp;", "order": 0 } } }
+            ],
+            "error": null
+        }).to_string())
+        .create();
+
+    let fronts = adapter.fetch_deck_fronts("Places in Japanese").await.expect("fetch failed");
+    assert_eq!(fronts, vec!["家 (いえ) (ie)".to_string(), "駅".to_string()]);
+}
+
+#[tokio::test]
+#[serial]
+async fn test_store_media_uploads_base64() {
+    let (mut server, adapter) = setup_mock_server().await;
+    let file = MediaFile::new(b"RIFF".to_vec(), "wav");
+
+    let store = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "storeMediaFile",
+            "params": { "filename": file.filename, "data": "UklGRg==" }
+        }).to_string()))
+        .with_body(json!({ "result": file.filename, "error": null }).to_string())
+        .expect(1)
+        .create();
+
+    assert_eq!(adapter.store_media(&file).await.unwrap(), file.filename);
+    store.assert();
+}
@@ -0,0 +1,150 @@
+use autoflashcard::apkg::ApkgWriter;
+use autoflashcard::apkg_reader::{read_apkg, ImportedNote};
+use autoflashcard::generation::Flashcard;
+use rusqlite::Connection;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use tempfile::TempDir;
+use zip::write::SimpleFileOptions;
+
+// Minimal schema 18 collection as written by current Anki versions
+fn write_modern_collection(path: &Path) {
+    let connection = Connection::open(path).unwrap();
+    connection.execute_batch("
+        CREATE TABLE col (id integer primary key, models text not null, decks text not null);
+        INSERT INTO col VALUES (1, '', '');
+        CREATE TABLE notetypes (id integer primary key, name text not null, config blob not null);
+        CREATE TABLE fields (ntid integer not null, ord integer not null, name text not null, config blob not null);
+        CREATE TABLE decks (id integer primary key, name text not null);
+        CREATE TABLE notes (id integer primary key, guid text not null, mid integer not null, tags text not null, flds text not null);
+        CREATE TABLE cards (id integer primary key, nid integer not null, did integer not null, ord integer not null);
+        INSERT INTO notetypes VALUES (10, 'Core 2k', x'');
+        INSERT INTO fields VALUES (10, 1, 'Meaning', x''), (10, 0, 'Vocabulary', x''), (10, 2, 'Sentence', x'');
+        INSERT INTO decks VALUES (20, 'Japanese' || char(31) || 'Core 2k');
+        INSERT INTO notes VALUES (100, 'g1', 10, ' core n5 ', '<b>水</b>' || char(31) || 'water' || char(31) || '水を飲む');
+        INSERT INTO cards VALUES (1000, 100, 20, 0), (1001, 100, 20, 1);
+    ").unwrap();
+}
+
+fn write_archive(path: &Path, entries: &[(&str, Vec<u8>)]) {
+    let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+    for (name, data) in entries {
+        zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+#[test]
+fn test_read_apkg_round_trips_wordcraft_export() {
+    let dir = TempDir::new().unwrap();
+    let archive = dir.path().join("colors.apkg");
+    let mut writer = ApkgWriter::new("Colors in Spanish");
+    writer.add_flashcard(&Flashcard {
+        front: "rojo".to_string(),
+        back: "red".to_string(),
+        example: "Es rojo.".to_string(),
+        example_translate: "It is red.".to_string(),
+        ..Default::default()
+    });
+    writer.add_media("rojo.mp3", b"audio".to_vec());
+    writer.write(&archive).unwrap();
+
+    let collection = read_apkg(&archive).expect("read failed");
+
+    assert_eq!(collection.notes.len(), 1);
+    let note = &collection.notes[0];
+    assert_eq!(note.model_name, "Wordcraft");
+    assert_eq!(note.deck_names, vec!["Colors in Spanish"]);
+    assert_eq!(note.tags, vec!["wordcraft", "language_learning"]);
+    assert_eq!(note.field("ExampleTranslation"), Some("It is red."));
+    assert_eq!(collection.flashcards()[0].example, "Es rojo.");
+    assert_eq!(collection.media.get("0").map(String::as_str), Some("rojo.mp3"));
+}
+
+#[test]
+fn test_read_colpkg_prefers_anki21_collection() {
+    let dir = TempDir::new().unwrap();
+    let modern = dir.path().join("modern.anki21");
+    write_modern_collection(&modern);
+
+    // Anki puts a placeholder anki2 next to the real collection
+    let mut writer = ApkgWriter::new("Placeholder");
+    writer.add_flashcard(&Flashcard {
+        front: "Please update to the latest Anki version".to_string(),
+        back: String::new(),
+        example: String::new(),
+        example_translate: String::new(),
+        ..Default::default()
+    });
+    let placeholder_collection = dir.path().join("placeholder.anki2");
+    writer.write_collection(&placeholder_collection).unwrap();
+
+    let archive = dir.path().join("backup.colpkg");
+    write_archive(&archive, &[
+        ("collection.anki2", std::fs::read(&placeholder_collection).unwrap()),
+        ("collection.anki21", std::fs::read(&modern).unwrap()),
+    ]);
+
+    let collection = read_apkg(&archive).expect("read failed");
+
+    assert_eq!(collection.notes, vec![ImportedNote {
+        note_id: 100,
+        model_name: "Core 2k".to_string(),
+        deck_names: vec!["Japanese::Core 2k".to_string()],
+        fields: vec![
+            ("Vocabulary".to_string(), "<b>水</b>".to_string()),
+            ("Meaning".to_string(), "water".to_string()),
+            ("Sentence".to_string(), "水を飲む".to_string()),
+        ],
+        tags: vec!["core".to_string(), "n5".to_string()],
+    }]);
+    assert_eq!(collection.words(), vec!["水"]);
+
+    let card = collection.notes[0].to_flashcard();
+    assert_eq!(card.back, "water");
+    assert_eq!(card.example, "水を飲む");
+    assert_eq!(card.example_translate, "");
+    assert!(collection.media.is_empty());
+}
+
+#[test]
+fn test_read_apkg_does_not_import_the_placeholder_of_compressed_exports() {
+    let dir = TempDir::new().unwrap();
+
+    // Current exports put a placeholder anki2 next to the compressed collection
+    let mut writer = ApkgWriter::new("Placeholder");
+    writer.add_flashcard(&Flashcard {
+        front: "Please update to the latest Anki version".to_string(),
+        ..Default::default()
+    });
+    let placeholder = dir.path().join("placeholder.anki2");
+    writer.write_collection(&placeholder).unwrap();
+    let archive = dir.path().join("current.apkg");
+    write_archive(&archive, &[
+        ("collection.anki2", std::fs::read(&placeholder).unwrap()),
+        ("collection.anki21b", vec![0x28, 0xb5, 0x2f, 0xfd]),
+    ]);
+
+    let message = read_apkg(&archive).unwrap_err().to_string();
+    assert!(message.contains("Support older Anki versions"));
+}
+
+#[test]
+fn test_read_apkg_rejects_unsupported_archives() {
+    let dir = TempDir::new().unwrap();
+
+    let compressed = dir.path().join("new.apkg");
+    write_archive(&compressed, &[("collection.anki21b", vec![0x28, 0xb5, 0x2f, 0xfd])]);
+    let message = read_apkg(&compressed).unwrap_err().to_string();
+    assert!(message.contains("Support older Anki versions"));
+
+    let empty = dir.path().join("empty.apkg");
+    write_archive(&empty, &[("notes.txt", b"hello".to_vec())]);
+    assert!(read_apkg(&empty).unwrap_err().to_string().contains("contains no Anki collection"));
+
+    let not_zip = dir.path().join("plain.apkg");
+    std::fs::write(&not_zip, "not a zip").unwrap();
+    assert!(read_apkg(&not_zip).unwrap_err().to_string().contains("is not an Anki package"));
+}
@@ -0,0 +1,182 @@
+use autoflashcard::apkg::{
+    apkg_file_name, export_apkg, export_apkg_as, field_checksum, model_id, ApkgWriter, COLLECTION_FILE, MEDIA_FILE,
+    WORDCRAFT_MODEL_ID,
+};
+use autoflashcard::generation::{Flashcard, FlashcardResponse};
+use autoflashcard::note_type::NoteKind;
+use rusqlite::Connection;
+use serde_json::Value;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn card(front: &str, back: &str) -> Flashcard {
+    Flashcard {
+        front: front.to_string(),
+        back: back.to_string(),
+        example: format!("Example with {}", front),
+        example_translate: format!("Example with {}", back),
+        ..Default::default()
+    }
+}
+
+fn read_entry(archive: &Path, name: &str) -> Vec<u8> {
+    let mut zip = zip::ZipArchive::new(File::open(archive).unwrap()).unwrap();
+    let mut entry = zip.by_name(name).unwrap();
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data).unwrap();
+    data
+}
+
+// Unpack the collection from the archive and open it
+fn open_collection(archive: &Path, dir: &TempDir) -> Connection {
+    let path = dir.path().join("unpacked.anki2");
+    std::fs::write(&path, read_entry(archive, COLLECTION_FILE)).unwrap();
+    Connection::open(path).unwrap()
+}
+
+#[test]
+fn test_export_apkg_writes_notes_and_cards() {
+    let dir = TempDir::new().unwrap();
+    let archive = dir.path().join("colors.apkg");
+    let response = FlashcardResponse {
+        deck_name: "Colors in Spanish".to_string(),
+        cards: vec![card("rojo", "red"), card("<b>azul</b>", "blue")],
+    };
+
+    export_apkg(&response, &archive).expect("export failed");
+    let collection = open_collection(&archive, &dir);
+
+    let (version, models, decks): (i64, String, String) = collection
+        .query_row("SELECT ver, models, decks FROM col", [], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap();
+    assert_eq!(version, 11);
+
+    let models: Value = serde_json::from_str(&models).unwrap();
+    let model = &models[WORDCRAFT_MODEL_ID.to_string()];
+    assert_eq!(model["name"], "Wordcraft");
+    let field_names: Vec<&str> = model["flds"].as_array().unwrap().iter().map(|field| field["name"].as_str().unwrap()).collect();
+    assert_eq!(field_names, vec!["Front", "Back", "Example", "ExampleTranslation", "FrontAudio", "ExampleAudio"]);
+
+    let decks: Value = serde_json::from_str(&decks).unwrap();
+    let deck = decks.as_object().unwrap().values().find(|deck| deck["name"] == "Colors in Spanish").expect("deck");
+    let deck_id = deck["id"].as_i64().unwrap();
+
+    let mut statement = collection.prepare("SELECT id, mid, flds, sfld, csum, tags FROM notes ORDER BY id").unwrap();
+    let notes: Vec<(i64, i64, String, String, i64, String)> = statement
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(notes.len(), 2);
+    assert_eq!(notes[0].1, WORDCRAFT_MODEL_ID);
+    assert_eq!(notes[0].2, "rojo\x1fred\x1fExample with rojo\x1fExample with red\x1f\x1f");
+    assert_eq!(notes[1].3, "azul");
+    assert_eq!(notes[1].4, field_checksum("azul"));
+    assert_eq!(notes[0].5, " wordcraft language_learning ");
+
+    let cards: Vec<(i64, i64, i64)> = collection
+        .prepare("SELECT nid, did, queue FROM cards ORDER BY nid").unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(cards, vec![(notes[0].0, deck_id, 0), (notes[1].0, deck_id, 0)]);
+
+    let manifest: Value = serde_json::from_slice(&read_entry(&archive, MEDIA_FILE)).unwrap();
+    assert_eq!(manifest, serde_json::json!({}));
+}
+
+#[test]
+fn test_apkg_bundles_media_with_manifest() {
+    let dir = TempDir::new().unwrap();
+    let archive = dir.path().join("audio.apkg");
+
+    let mut writer = ApkgWriter::new("Audio");
+    writer.add_flashcard(&card("[sound:rojo.mp3] rojo", "red"));
+    writer.add_media("rojo.mp3", b"ID3 audio".to_vec());
+    writer.write(&archive).expect("export failed");
+
+    let manifest: Value = serde_json::from_slice(&read_entry(&archive, MEDIA_FILE)).unwrap();
+    assert_eq!(manifest, serde_json::json!({ "0": "rojo.mp3" }));
+    assert_eq!(read_entry(&archive, "0"), b"ID3 audio");
+}
+
+#[test]
+fn test_repeated_exports_keep_note_guids() {
+    let dir = TempDir::new().unwrap();
+    let guid = |name: &str| {
+        let archive = dir.path().join(name);
+        let mut writer = ApkgWriter::new("Colors");
+        writer.add_flashcard(&card("rojo", "red"));
+        writer.write(&archive).unwrap();
+        open_collection(&archive, &dir)
+            .query_row("SELECT guid FROM notes", [], |row| row.get::<_, String>(0))
+            .unwrap()
+    };
+
+    assert_eq!(guid("first.apkg"), guid("second.apkg"));
+}
+
+#[test]
+fn test_apkg_file_name() {
+    assert_eq!(apkg_file_name("Colors in Spanish"), "Colors in Spanish.apkg");
+    assert_eq!(apkg_file_name("Japanese::N5/Food"), "Japanese__N5_Food.apkg");
+    assert_eq!(apkg_file_name("  "), "wordcraft.apkg");
+}
+
+#[test]
+fn test_apkg_writes_one_card_per_template_or_cloze() {
+    let dir = TempDir::new().unwrap();
+    let count_cards = |kind: NoteKind, card: Flashcard| {
+        let archive = dir.path().join(format!("{}.apkg", kind));
+        export_apkg_as(&FlashcardResponse { deck_name: "Japanese".to_string(), cards: vec![card] }, kind, &[], &archive)
+            .expect("export failed");
+        let collection = open_collection(&archive, &dir);
+
+        let (mid, fields): (i64, String) = collection
+            .query_row("SELECT mid, flds FROM notes", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!(mid, model_id(kind));
+        let ords: Vec<i64> = collection
+            .prepare("SELECT ord FROM cards ORDER BY ord").unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        (fields, ords)
+    };
+
+    let (_, ords) = count_cards(NoteKind::Bidirectional, card("家", "house"));
+    assert_eq!(ords, vec![0, 1]);
+
+    let clozed = Flashcard { cloze: Some("{{c1::家}} and {{c2::庭}}".to_string()), ..card("家", "house") };
+    let (fields, ords) = count_cards(NoteKind::Cloze, clozed);
+    assert!(fields.starts_with("{{c1::家}} and {{c2::庭}}\x1f家"));
+    assert_eq!(ords, vec![0, 1]);
+}
+
+#[test]
+fn test_concurrent_exports_do_not_share_a_scratch_directory() {
+    let dir = TempDir::new().unwrap();
+    let archives: Vec<_> = (0..8).map(|i| dir.path().join(format!("deck-{}.apkg", i))).collect();
+
+    std::thread::scope(|scope| {
+        for (i, archive) in archives.iter().enumerate() {
+            scope.spawn(move || {
+                let response = FlashcardResponse {
+                    deck_name: format!("Deck {}", i),
+                    cards: vec![card(&format!("word {}", i), "meaning")],
+                };
+                export_apkg(&response, archive).expect("export failed");
+            });
+        }
+    });
+
+    for (i, archive) in archives.iter().enumerate() {
+        let collection = open_collection(archive, &dir);
+        let front: String = collection.query_row("SELECT sfld FROM notes", [], |row| row.get(0)).unwrap();
+        assert_eq!(front, format!("word {}", i));
+    }
+}
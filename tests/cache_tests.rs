@@ -0,0 +1,175 @@
+use autoflashcard::cache::{cache_key, generate_flashcards_cached, CacheConfig, CachedGeneration, ResponseCache};
+use autoflashcard::cli::{run_generate, GenerateArgs, GenerationArgs};
+use autoflashcard::config::Config;
+use autoflashcard::generator::{FakeGenerator, FAKE_CARD_COUNT};
+use autoflashcard::generation::{generate_flashcards, FlashcardResponse, GenerationOptions};
+use autoflashcard::note_type::NoteKind;
+use std::time::Duration;
+use tempfile::TempDir;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+async fn generated(generator: &FakeGenerator, user_input: &str) -> CachedGeneration {
+    let options = GenerationOptions::default();
+    let outcome = generate_flashcards(generator, user_input, &options).await.unwrap();
+    CachedGeneration::new(generator, user_input, &options, &outcome)
+}
+
+#[test]
+fn test_key_covers_settings_and_input_but_not_known_words() {
+    let generator = FakeGenerator::new();
+    let options = GenerationOptions::default();
+    let key = cache_key(&generator, "Topic: Food", &options);
+
+    assert_eq!(key, cache_key(&generator, "Topic: Food", &options));
+    assert_ne!(key, cache_key(&generator, "Topic: Travel", &options));
+    let cloze = GenerationOptions { note_kind: NoteKind::Cloze, ..GenerationOptions::default() };
+    assert_ne!(key, cache_key(&generator, "Topic: Food", &cloze));
+    let counted = GenerationOptions { card_count: Some(5), ..GenerationOptions::default() };
+    assert_ne!(key, cache_key(&generator, "Topic: Food", &counted));
+    let templated = GenerationOptions { system_template: "Make cards.".to_string(), ..GenerationOptions::default() };
+    assert_ne!(key, cache_key(&generator, "Topic: Food", &templated));
+
+    // New words in Anki do not change the key
+    let learner = GenerationOptions {
+        exclude: vec!["pan".to_string()],
+        learner_profile: Some("Struggles with food words.".to_string()),
+        ..GenerationOptions::default()
+    };
+    assert_eq!(key, cache_key(&generator, "Topic: Food", &learner));
+}
+
+#[tokio::test]
+async fn test_entries_keep_the_replies_and_the_deck() {
+    let dir = TempDir::new().unwrap();
+    let cache = ResponseCache::new(dir.path(), DAY);
+    let generator = FakeGenerator::new();
+    let entry = generated(&generator, "Topic: Food\nTarget Language: Spanish\n").await;
+
+    assert!(cache.get("food").is_none());
+    let path = cache.put("food", &entry).unwrap();
+    assert_eq!(path, dir.path().join("food.json"));
+
+    let cached = cache.get("food").unwrap();
+    assert_eq!((cached.engine.as_str(), cached.model.as_str()), ("fake", "fake"));
+    assert_eq!(cached.replies.len(), 1);
+    assert_eq!(cached.response.cards.len(), FAKE_CARD_COUNT);
+    assert_eq!(cached.response.deck_name, "Food in Spanish");
+}
+
+#[tokio::test]
+async fn test_expired_entries_are_ignored_and_pruned() {
+    let dir = TempDir::new().unwrap();
+    let cache = ResponseCache::new(dir.path(), DAY);
+    let mut entry = generated(&FakeGenerator::new(), "Topic: Food").await;
+
+    cache.put("fresh", &entry).unwrap();
+    entry.created_at -= 2 * DAY.as_secs();
+    cache.put("stale", &entry).unwrap();
+    std::fs::write(dir.path().join("broken.json"), "{").unwrap();
+    std::fs::write(dir.path().join("interrupted.json.partial"), "{\"created_at\"").unwrap();
+    std::fs::write(dir.path().join("README"), "not a cache entry").unwrap();
+
+    assert!(cache.get("stale").is_none());
+    assert!(cache.get("fresh").is_some());
+
+    let report = cache.prune(false).unwrap();
+    assert_eq!((report.removed, report.kept), (3, 1));
+    assert!(!dir.path().join("stale.json").exists());
+    assert!(dir.path().join("README").exists());
+
+    let report = cache.prune(true).unwrap();
+    assert_eq!((report.removed, report.kept), (1, 0));
+    assert!(cache.get("fresh").is_none());
+}
+
+#[test]
+fn test_pruning_a_missing_cache_does_nothing() {
+    let dir = TempDir::new().unwrap();
+    let cache = ResponseCache::new(dir.path().join("never-created"), DAY);
+    let report = cache.prune(true).unwrap();
+    assert_eq!((report.removed, report.kept), (0, 0));
+}
+
+#[test]
+fn test_from_config() {
+    let config = CacheConfig { dir: Some("/tmp/wordcraft-cache".to_string()), ttl_hours: 2 };
+    let cache = ResponseCache::from_config(&config);
+    assert_eq!(cache.dir.to_str(), Some("/tmp/wordcraft-cache"));
+    assert_eq!(cache.ttl, Duration::from_secs(2 * 60 * 60));
+    assert_eq!(CacheConfig::default().ttl_hours, 24);
+
+    // A TTL too long to count in seconds is as long as it gets
+    let forever = ResponseCache::from_config(&CacheConfig { dir: None, ttl_hours: u64::MAX });
+    assert_eq!(forever.ttl, Duration::from_secs(u64::MAX));
+}
+
+#[tokio::test]
+async fn test_generate_flashcards_cached_asks_the_model_once() {
+    let dir = TempDir::new().unwrap();
+    let cache = ResponseCache::new(dir.path(), DAY);
+    let generator = FakeGenerator::new();
+    let options = GenerationOptions::default();
+
+    let first = generate_flashcards_cached(&generator, Some(&cache), "Topic: Colors", &options).await.unwrap();
+    let second = generate_flashcards_cached(&generator, Some(&cache), "Topic: Colors", &options).await.unwrap();
+    assert_eq!(first.cards.len(), FAKE_CARD_COUNT);
+    assert_eq!(second.cards.len(), FAKE_CARD_COUNT);
+    assert_eq!(generator.calls().len(), 1);
+
+    // Cards learned since are dropped from the cached deck
+    let learned = GenerationOptions { exclude: vec![first.cards[0].front.clone()], ..GenerationOptions::default() };
+    let third = generate_flashcards_cached(&generator, Some(&cache), "Topic: Colors", &learned).await.unwrap();
+    assert_eq!(third.cards.len(), FAKE_CARD_COUNT - 1);
+    assert_eq!(generator.calls().len(), 1);
+
+    // Without a cache every call asks the model
+    generate_flashcards_cached(&generator, None, "Topic: Colors", &options).await.unwrap();
+    assert_eq!(generator.calls().len(), 2);
+}
+
+fn saved_deck(path: &std::path::Path) -> FlashcardResponse {
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_generate_reuses_the_cached_deck() {
+    let dir = TempDir::new().unwrap();
+    let cache_dir = dir.path().join("cache");
+    let saved = dir.path().join("deck.json");
+    let config = Config {
+        // Nothing listens here, so the run stays offline
+        anki_connect_url: "http://127.0.0.1:9".to_string(),
+        engine: "fake".to_string(),
+        cache: CacheConfig { dir: Some(cache_dir.display().to_string()), ..CacheConfig::default() },
+        ..Config::default()
+    };
+    let args = |no_cache: bool| GenerateArgs {
+        generation: GenerationArgs {
+            native: Some("English".to_string()),
+            target: Some("Spanish".to_string()),
+            topic: Some("Food".to_string()),
+            note_type: Some(NoteKind::Basic),
+            no_cache,
+            ..Default::default()
+        },
+        dry_run: true,
+        save: Some(saved.clone()),
+        ..Default::default()
+    };
+
+    run_generate(&config, args(false)).await.unwrap();
+    let entries: Vec<_> = std::fs::read_dir(&cache_dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+    assert_eq!(entries.len(), 1);
+
+    // Mark the cached deck to tell it apart from a new one
+    let mut entry: CachedGeneration = serde_json::from_str(&std::fs::read_to_string(&entries[0]).unwrap()).unwrap();
+    entry.response.cards.truncate(1);
+    std::fs::write(&entries[0], serde_json::to_string(&entry).unwrap()).unwrap();
+
+    run_generate(&config, args(false)).await.unwrap();
+    assert_eq!(saved_deck(&saved).cards.len(), 1);
+
+    run_generate(&config, args(true)).await.unwrap();
+    assert_eq!(saved_deck(&saved).cards.len(), FAKE_CARD_COUNT);
+}
@@ -0,0 +1,173 @@
+use autoflashcard::cli::{
+    command_overrides, run, run_add, run_decks, run_export, AddArgs, Cli, Command, ExportArgs,
+    GenerateArgs, GenerationArgs,
+};
+use autoflashcard::config::{Config, ConfigFile, ConfigLayer};
+use autoflashcard::generator::EngineConfig;
+use autoflashcard::generation::{Flashcard, FlashcardResponse};
+use autoflashcard::level::ProficiencyLevel;
+use autoflashcard::note_type::NoteKind;
+use autoflashcard::ollama::OllamaConfig;
+use clap::Parser;
+use serde_json::json;
+use serial_test::serial;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn write_deck(dir: &TempDir) -> std::path::PathBuf {
+    let deck = FlashcardResponse {
+        deck_name: "Colors in Spanish".to_string(),
+        cards: vec![Flashcard {
+            front: "rojo".to_string(),
+            back: "red".to_string(),
+            example: "El coche es rojo.".to_string(),
+            example_translate: "The car is red.".to_string(),
+            ..Default::default()
+        }],
+    };
+    let path = dir.path().join("colors.json");
+    std::fs::write(&path, serde_json::to_string(&deck).unwrap()).unwrap();
+    path
+}
+
+#[test]
+fn test_parse_generate_flags() {
+    let cli = Cli::try_parse_from([
+        "wordcraft", "generate", "--target", "Spanish", "--topic", "Colors", "--count", "12",
+        "--engine", "ollama", "--model", "llama3.2", "--note-type", "cloze", "--level", "hsk 3", "--yes", "--dry-run",
+    ]).unwrap();
+
+    match cli.command {
+        Some(Command::Generate(args)) => {
+            assert_eq!(args.generation.target.as_deref(), Some("Spanish"));
+            assert_eq!(args.generation.topic.as_deref(), Some("Colors"));
+            assert_eq!(args.generation.count, Some(12));
+            assert_eq!(args.generation.level, Some(ProficiencyLevel::Hsk(3)));
+            assert_eq!(args.generation.note_type, Some(NoteKind::Cloze));
+            assert!(args.yes);
+            assert!(args.dry_run);
+        }
+        other => panic!("Unexpected command: {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_rejects_unknown_note_type_and_bare_run_is_generate() {
+    let err = Cli::try_parse_from(["wordcraft", "generate", "--note-type", "flip"]).unwrap_err().to_string();
+    assert!(err.contains("Unknown note type 'flip'"));
+
+    assert!(Cli::try_parse_from(["wordcraft"]).unwrap().command.is_none());
+    assert!(matches!(Cli::try_parse_from(["wordcraft", "doctor"]).unwrap().command, Some(Command::Doctor)));
+}
+
+#[test]
+fn test_engine_and_model_flags_override_environment() {
+    let env = ConfigLayer { engine: Some("fake".to_string()), ..Default::default() };
+    let file = ConfigFile::default();
+
+    let command = Command::Generate(GenerateArgs {
+        generation: GenerationArgs {
+            engine: Some("ollama".to_string()),
+            model: Some("qwen2.5".to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    let config = Config::layered(&file, &env, None, &command_overrides(&command)).unwrap();
+    assert_eq!(config.engine_config().unwrap(), EngineConfig::Ollama(OllamaConfig {
+        base_url: "http://localhost:11434".to_string(),
+        model: "qwen2.5".to_string(),
+    }));
+
+    let plain = Command::Generate(GenerateArgs::default());
+    let config = Config::layered(&file, &env, None, &command_overrides(&plain)).unwrap();
+    assert_eq!(config.engine_config().unwrap(), EngineConfig::Fake);
+}
+
+#[tokio::test]
+async fn test_export_from_json_file() {
+    let dir = TempDir::new().unwrap();
+    let input = write_deck(&dir);
+    let output = dir.path().join("out.apkg");
+
+    run_export(&Config::default(), ExportArgs {
+        input: Some(input),
+        output: Some(output.clone()),
+        ..Default::default()
+    }).await.expect("export failed");
+
+    assert!(Path::new(&output).exists());
+}
+
+#[tokio::test]
+async fn test_deck_file_audio_needs_the_target_language() {
+    let dir = TempDir::new().unwrap();
+    let input = write_deck(&dir);
+    let output = dir.path().join("out.apkg");
+    let mut config = Config::default();
+    config.tts.command = Some("espeak-ng -v {lang} -w {output} {text}".to_string());
+
+    let err = run_export(&config, ExportArgs {
+        input: Some(input.clone()),
+        output: Some(output.clone()),
+        ..Default::default()
+    }).await.unwrap_err();
+    assert!(err.to_string().contains("--target"));
+    assert!(!output.exists());
+
+    let args = AddArgs { file: input, deck: None, note_type: None, target: None, yes: true, dry_run: true };
+    assert!(run_add(&config, args).await.unwrap_err().to_string().contains("--target"));
+}
+
+#[tokio::test]
+async fn test_add_dry_run_does_not_need_anki() {
+    let dir = TempDir::new().unwrap();
+    let file = write_deck(&dir);
+
+    let args = AddArgs { file, deck: None, note_type: None, target: None, yes: false, dry_run: true };
+    run_add(&Config::default(), args).await.expect("dry run failed");
+
+    let missing = AddArgs {
+        file: dir.path().join("missing.json"),
+        deck: None,
+        note_type: None,
+        target: None,
+        yes: true,
+        dry_run: true,
+    };
+    assert!(run_add(&Config::default(), missing).await.unwrap_err().to_string().starts_with("Could not read"));
+}
+
+#[tokio::test]
+async fn test_decks_lists_deck_names() {
+    let mut server = mockito::Server::new_async().await;
+    let config = Config { anki_connect_url: server.url(), ..Config::default() };
+
+    let names = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({ "action": "deckNames" }).to_string()))
+        .with_body(json!({ "result": ["Spanish", "Default"], "error": null }).to_string())
+        .expect(1)
+        .create();
+    let _stats = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({ "action": "getDeckStats" }).to_string()))
+        .with_body(json!({ "result": {}, "error": null }).to_string())
+        .create();
+
+    run_decks(&config).await.expect("listing decks failed");
+    names.assert();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_doctor_reports_unreachable_anki() {
+    std::env::set_var("ANKI_CONNECT_URL", "http://127.0.0.1:9");
+    std::env::set_var("ENGINE", "fake");
+    std::env::remove_var("TTS_COMMAND");
+    std::env::remove_var("KNOWN_WORDS_APKG");
+
+    let healthy = run(Cli::try_parse_from(["wordcraft", "doctor"]).unwrap()).await.unwrap();
+    assert!(!healthy);
+
+    std::env::remove_var("ANKI_CONNECT_URL");
+    std::env::remove_var("ENGINE");
+}
@@ -0,0 +1,209 @@
+use autoflashcard::config::{Config, ConfigFile, ConfigLayer, ConfigSources, DEFAULT_ANKI_CONNECT_URL};
+use autoflashcard::generator::EngineConfig;
+use serial_test::serial;
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+const CONFIG_TOML: &str = r#"
+engine = "ollama"
+anki_connect_url = "http://anki-box:8765"
+profile = "work-ollama"
+
+[ollama]
+model = "gemma2"
+
+[tts]
+command = "espeak-ng -v {lang} -w {output} {text}"
+
+[profiles.work-ollama.ollama]
+base_url = "http://gpu-box:11434"
+
+[profiles.home-openai]
+engine = "openai"
+openai = { model = "gpt-4o", api_key = "sk-test", headers = { X-Team = "lab" } }
+"#;
+
+fn env(vars: &[(&str, &str)]) -> ConfigLayer {
+    let vars: HashMap<String, String> = vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+    ConfigLayer::from_lookup(|name| vars.get(name).cloned()).unwrap()
+}
+
+#[test]
+fn test_defaults_without_any_layer() {
+    let config = Config::layered(&ConfigFile::default(), &ConfigLayer::default(), None, &ConfigLayer::default()).unwrap();
+
+    // The default engine is the OpenAI API, which needs a key, but only
+    // commands that generate cards ask for the engine
+    assert!(config.engine_config().unwrap_err().to_string().contains("OPEN_API_KEY"));
+
+    let fake = ConfigLayer { engine: Some("fake".to_string()), ..Default::default() };
+    let config = Config::layered(&ConfigFile::default(), &fake, None, &ConfigLayer::default()).unwrap();
+    assert_eq!(config.anki_connect_url, DEFAULT_ANKI_CONNECT_URL);
+    assert_eq!(config.profile, None);
+    assert_eq!(config.tts.command, None);
+    assert_eq!(config.tts.extension, "wav");
+}
+
+#[test]
+fn test_file_default_profile_applies() {
+    let file = ConfigFile::parse(CONFIG_TOML).unwrap();
+    assert_eq!(file.profiles.len(), 2);
+
+    let config = Config::layered(&file, &ConfigLayer::default(), None, &ConfigLayer::default()).unwrap();
+    assert_eq!(config.profile.as_deref(), Some("work-ollama"));
+    assert_eq!(config.anki_connect_url, "http://anki-box:8765");
+    assert_eq!(config.tts.command.as_deref(), Some("espeak-ng -v {lang} -w {output} {text}"));
+    assert_eq!(config.engine_config().unwrap(), EngineConfig::Ollama(autoflashcard::ollama::OllamaConfig {
+        base_url: "http://gpu-box:11434".to_string(),
+        model: "gemma2".to_string(),
+    }));
+}
+
+#[test]
+fn test_layers_override_in_order() {
+    let file = ConfigFile::parse(CONFIG_TOML).unwrap();
+    let env = env(&[
+        ("ENGINE", "ollama"),
+        ("ANKI_CONNECT_URL", "http://localhost:9999"),
+        ("OPENAI_MODEL", "gpt-4o-mini"),
+        ("TTS_COMMAND", ""),
+    ]);
+
+    // The environment beats the file, the chosen profile beats the environment
+    let config = Config::layered(&file, &env, Some("home-openai"), &ConfigLayer::default()).unwrap();
+    assert_eq!(config.anki_connect_url, "http://localhost:9999");
+    match config.engine_config().unwrap() {
+        EngineConfig::OpenAI(openai) => {
+            assert_eq!(openai.model, "gpt-4o");
+            assert_eq!(openai.api_key.as_deref(), Some("sk-test"));
+            assert_eq!(openai.headers, vec![("X-Team".to_string(), "lab".to_string())]);
+        }
+        other => panic!("Unexpected engine config: {:?}", other),
+    }
+    // An empty variable leaves the file's value alone
+    assert!(config.tts.command.is_some());
+
+    // Flags beat everything
+    let mut flags = ConfigLayer { engine: Some("fake".to_string()), ..Default::default() };
+    flags.anki_connect_url = Some("http://127.0.0.1:8765".to_string());
+    let config = Config::layered(&file, &env, Some("home-openai"), &flags).unwrap();
+    assert_eq!(config.engine_config().unwrap(), EngineConfig::Fake);
+    assert_eq!(config.anki_connect_url, "http://127.0.0.1:8765");
+}
+
+#[test]
+fn test_env_layer_reads_known_variables() {
+    let layer = env(&[
+        ("KNOWN_WORDS_APKG", "core.apkg, extra.colpkg,"),
+        ("OPENAI_HEADERS", "X-Team: lab; X-Trace: on"),
+        ("OPENAI_STRUCTURED_OUTPUT", "no"),
+        ("TTS_EXTENSION", "mp3"),
+    ]);
+    assert_eq!(layer.known_words_apkg, Some(vec!["core.apkg".to_string(), "extra.colpkg".to_string()]));
+    assert_eq!(layer.openai.headers.unwrap().len(), 2);
+    assert_eq!(layer.openai.structured_output, Some(false));
+    assert_eq!(layer.tts.extension.as_deref(), Some("mp3"));
+
+    let vars: HashMap<&str, &str> = HashMap::from([("OPENAI_HEADERS", "no colon")]);
+    let err = ConfigLayer::from_lookup(|name| vars.get(name).map(|value| value.to_string())).unwrap_err();
+    assert!(err.to_string().starts_with("OPENAI_HEADERS: Invalid header"));
+}
+
+#[test]
+fn test_cache_settings() {
+    let layer = env(&[("ENGINE", "fake"), ("CACHE_TTL_HOURS", "0"), ("WORDCRAFT_CACHE_DIR", "/tmp/decks")]);
+    let config = Config::layered(&ConfigFile::default(), &layer, None, &ConfigLayer::default()).unwrap();
+    assert_eq!(config.cache.ttl_hours, 0);
+    assert_eq!(config.cache.dir.as_deref(), Some("/tmp/decks"));
+
+    let file = ConfigFile::parse("[cache]\nttl_hours = 72\n").unwrap();
+    assert_eq!(file.base.cache.ttl_hours, Some(72));
+
+    let vars: HashMap<&str, &str> = HashMap::from([("CACHE_TTL_HOURS", "a week")]);
+    let err = ConfigLayer::from_lookup(|name| vars.get(name).map(|value| value.to_string())).unwrap_err();
+    assert!(err.to_string().contains("CACHE_TTL_HOURS must be a whole number"));
+}
+
+#[test]
+fn test_prompt_directory() {
+    let layer = env(&[("ENGINE", "fake"), ("WORDCRAFT_PROMPT_DIR", "/tmp/prompts")]);
+    let config = Config::layered(&ConfigFile::default(), &layer, None, &ConfigLayer::default()).unwrap();
+    assert_eq!(config.prompts.dir.as_deref(), Some("/tmp/prompts"));
+
+    let file = ConfigFile::parse("[prompts]\ndir = \"~/prompts\"\n").unwrap();
+    assert_eq!(file.base.prompts.dir.as_deref(), Some("~/prompts"));
+}
+
+#[test]
+fn test_validation_messages() {
+    let file = ConfigFile::default();
+    let fake = ConfigLayer { engine: Some("fake".to_string()), ..Default::default() };
+
+    let bad_url = ConfigLayer { anki_connect_url: Some("localhost:8765".to_string()), ..Default::default() };
+    assert_eq!(
+        Config::layered(&file, &fake, None, &bad_url).unwrap_err(),
+        "anki_connect_url (ANKI_CONNECT_URL) must be an http:// or https:// URL, got 'localhost:8765'"
+    );
+
+    let engine = ConfigLayer { engine: Some("gpt".to_string()), ..Default::default() };
+    let config = Config::layered(&file, &engine, None, &ConfigLayer::default()).unwrap();
+    assert!(config.engine_config().unwrap_err().to_string().contains("Unsupported engine 'gpt'"));
+
+    assert_eq!(
+        Config::layered(&file, &fake, Some("work"), &ConfigLayer::default()).unwrap_err(),
+        "Unknown profile 'work': the config file defines no profiles"
+    );
+    let file = ConfigFile::parse(CONFIG_TOML).unwrap();
+    assert_eq!(
+        Config::layered(&file, &fake, Some("work"), &ConfigLayer::default()).unwrap_err(),
+        "Unknown profile 'work'. Profiles: home-openai, work-ollama"
+    );
+}
+
+#[test]
+fn test_config_file_rejects_unknown_keys() {
+    assert!(ConfigFile::parse("enigne = \"ollama\"").unwrap_err().contains("enigne"));
+    assert!(ConfigFile::parse("[profiles.home]\nmodel = \"x\"").unwrap_err().starts_with("profile 'home'"));
+    assert!(ConfigFile::parse("profile = 3").is_err());
+}
+
+// Reads the environment, which other tests change
+#[test]
+#[serial]
+fn test_load_reads_the_given_file() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.toml");
+
+    let missing = Config::load(ConfigSources { path: Some(path.clone()), ..Default::default() });
+    assert!(missing.unwrap_err().to_string().contains("does not exist"));
+
+    std::fs::write(&path, "engine = \"fake\"\nanki_connect_url = \"ftp://anki\"\n").unwrap();
+    let invalid = Config::load(ConfigSources { path: Some(path.clone()), ..Default::default() });
+    let message = invalid.unwrap_err().to_string();
+    assert!(message.contains("anki_connect_url"));
+    assert!(message.contains(&path.display().to_string()));
+
+    // A flag that is wrong does not blame the file
+    std::fs::write(&path, "engine = \"fake\"\n").unwrap();
+    let overrides = ConfigLayer { anki_connect_url: Some("ftp://anki".to_string()), ..Default::default() };
+    let flagged = Config::load(ConfigSources { path: Some(path.clone()), overrides, ..Default::default() });
+    let message = flagged.unwrap_err().to_string();
+    assert!(message.contains("anki_connect_url"));
+    assert!(!message.contains(&path.display().to_string()));
+}
+
+#[test]
+fn test_fixture_directories() {
+    let file = ConfigFile::default();
+    let recording = env(&[("ENGINE", "fake"), ("LLM_RECORD_DIR", "fixtures")]);
+    let config = Config::layered(&file, &recording, None, &ConfigLayer::default()).unwrap();
+    assert_eq!(config.fixtures.record.as_deref(), Some("fixtures"));
+
+    let mut both = ConfigLayer::default();
+    both.fixtures.replay = Some("fixtures".to_string());
+    assert!(Config::layered(&file, &recording, None, &both).unwrap_err().contains("cannot both be set"));
+
+    // A replay needs no API key
+    let replay = env(&[("ENGINE", "openai"), ("LLM_REPLAY_DIR", "fixtures")]);
+    assert!(Config::layered(&file, &replay, None, &ConfigLayer::default()).is_ok());
+}
@@ -0,0 +1,310 @@
+use autoflashcard::anki_adapter::{insert_cards, wordcraft_note, AnkiAdapter, CardOutcome};
+use autoflashcard::anki_connect::NoteOptions;
+use autoflashcard::cache::CacheConfig;
+use autoflashcard::cli::{run_generate, GenerateArgs, GenerationArgs};
+use autoflashcard::config::Config;
+use autoflashcard::fake_anki::{Collection, FakeAnki, FakeAnkiServer};
+use autoflashcard::generator::FAKE_CARD_COUNT;
+use autoflashcard::generation::Flashcard;
+use autoflashcard::note_type::{model_version, NoteKind, NOTE_KINDS, WORDCRAFT_MODEL_VERSION};
+use autoflashcard::rag::WORDCRAFT_NOTES_QUERY;
+use autoflashcard::tts::{MediaFile, SilentTts};
+use serde_json::json;
+use serial_test::serial;
+
+async fn start() -> (FakeAnki, FakeAnkiServer, AnkiAdapter) {
+    let anki = FakeAnki::new();
+    let server = anki.start().await.expect("could not start the fake AnkiConnect");
+    let adapter = AnkiAdapter::new(&server.url);
+    (anki, server, adapter)
+}
+
+fn card(front: &str) -> Flashcard {
+    Flashcard {
+        front: front.to_string(),
+        back: format!("{} (back)", front),
+        example: format!("Example with {}", front),
+        example_translate: format!("Translation of {}", front),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_notes_land_in_their_deck() {
+    let (anki, _server, adapter) = start().await;
+
+    adapter.check_connection().await.unwrap();
+    adapter.ensure_note_type_exists(NoteKind::Basic).await.unwrap();
+    adapter.create_deck("Spanish::Colors").await.unwrap();
+    let report = adapter.add_cards_tagged("Spanish::Colors", &[card("rojo"), card("azul")], NoteKind::Basic, &["colors".to_string()]).await.unwrap();
+
+    assert_eq!(report.added().len(), 2);
+    assert_eq!(adapter.fetch_deck_fronts("Spanish::Colors").await.unwrap(), vec!["rojo", "azul"]);
+    // The parent deck is created too and its search includes the subdeck
+    assert!(adapter.deck_names().await.unwrap().contains(&"Spanish".to_string()));
+    assert_eq!(adapter.find_notes("deck:Spanish").await.unwrap().len(), 2);
+
+    let collection = anki.collection();
+    let note = collection.notes.values().find(|note| note.field("Front") == Some("azul")).unwrap();
+    assert_eq!(note.model, NoteKind::Basic.note_type().model_name);
+    assert!(note.tags.contains(&"colors".to_string()));
+    assert!(collection.cards.values().any(|card| card.note == note.id && card.deck == "Spanish::Colors"));
+}
+
+#[tokio::test]
+async fn test_duplicates_are_refused_like_anki() {
+    let (_anki, _server, adapter) = start().await;
+    adapter.ensure_note_type_exists(NoteKind::Basic).await.unwrap();
+    adapter.create_deck("Colors").await.unwrap();
+    adapter.add_card("Colors", "rojo", "red", "Es rojo.", "It is red.").await.unwrap();
+
+    // Anki compares the first field, without HTML
+    let err = adapter.add_card("Colors", "<b>rojo</b>", "red", "", "").await.unwrap_err();
+    assert_eq!(err.to_string(), "Error adding card: '<b>rojo</b>' is already in the collection");
+
+    let report = adapter.add_cards("Colors", &[card("rojo"), card("verde")]).await.unwrap();
+    assert_eq!(report.duplicates(), vec!["rojo"]);
+    assert!(matches!(report.entries[1].outcome, CardOutcome::Added(_)));
+
+    let mut allowed = wordcraft_note("Colors", "rojo", "red", "", "");
+    allowed.options = NoteOptions { allow_duplicate: true, duplicate_scope: None };
+    assert!(adapter.add_note(allowed).await.is_ok());
+}
+
+#[test]
+fn test_duplicate_scope_deck_only_checks_the_target_deck() {
+    let mut collection = Collection::default();
+    collection.create_deck("One");
+    collection.create_deck("Two");
+    let mut note = |deck: &str, scope: Option<&str>| {
+        let mut note = wordcraft_note(deck, "rojo", "red", "", "");
+        note.model_name = "Basic".to_string();
+        note.fields = [("Front", "rojo"), ("Back", "red")].iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        note.options.duplicate_scope = scope.map(str::to_string);
+        collection.add_note(&note)
+    };
+
+    assert!(note("One", None).is_ok());
+    assert!(note("Two", Some("deck")).is_ok());
+    assert_eq!(note("Two", None).unwrap_err(), "cannot create note because it is a duplicate");
+}
+
+#[tokio::test]
+async fn test_invalid_notes_are_refused() {
+    let (_anki, _server, adapter) = start().await;
+    adapter.ensure_note_type_exists(NoteKind::Basic).await.unwrap();
+
+    let err = adapter.add_note(wordcraft_note("Missing", "rojo", "red", "", "")).await.unwrap_err();
+    assert_eq!(err.to_string(), "AnkiConnect error (addNote): deck was not found: Missing");
+
+    adapter.create_deck("Colors").await.unwrap();
+    let err = adapter.add_note(wordcraft_note("Colors", "  ", "red", "", "")).await.unwrap_err();
+    assert!(err.to_string().ends_with("cannot create note because it is empty"));
+
+    let mut note = wordcraft_note("Colors", "rojo", "red", "", "");
+    note.model_name = "Nope".to_string();
+    assert!(adapter.can_add_notes(vec![note.clone()]).await.unwrap() == vec![false]);
+    let detail = adapter.can_add_notes_with_error_detail(vec![note]).await.unwrap();
+    assert!(!detail[0].can_add);
+    assert_eq!(detail[0].error.as_deref(), Some("model was not found: Nope"));
+}
+
+#[tokio::test]
+async fn test_cloze_notes_get_a_card_per_deletion() {
+    let (anki, _server, adapter) = start().await;
+    adapter.create_deck("Cloze").await.unwrap();
+    let mut note = wordcraft_note("Cloze", "", "", "", "");
+    note.model_name = "Cloze".to_string();
+    note.fields = [("Text", "{{c1::Hola}}, {{c2::amigo}}. {{c1::Hola}}")].iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+    let id = adapter.add_note(note.clone()).await.unwrap();
+    assert_eq!(adapter.notes_info(&[id]).await.unwrap()[0].cards.len(), 2);
+
+    note.fields.insert("Text".to_string(), "Hola amigo".to_string());
+    let err = adapter.add_note(note).await.unwrap_err();
+    assert!(err.to_string().contains("no cloze deletions"));
+    assert_eq!(anki.collection().notes.len(), 1);
+}
+
+#[tokio::test]
+async fn test_outdated_note_type_is_migrated() {
+    let (anki, _server, adapter) = start().await;
+    let note_type = NoteKind::Basic.note_type();
+    let mut old = note_type.create_model();
+    old.in_order_fields.retain(|field| field != "FrontAudio");
+    old.css = ".card { color: black; }".to_string();
+    adapter.create_model(old).await.unwrap();
+    adapter.create_deck("Old").await.unwrap();
+    adapter.add_card("Old", "rojo", "red", "", "").await.unwrap();
+
+    assert_eq!(adapter.migrate_note_type(NoteKind::Basic).await.unwrap(), Some(1));
+
+    let styling = adapter.model_styling(note_type.model_name).await.unwrap();
+    assert_eq!(model_version(&styling.css), WORDCRAFT_MODEL_VERSION);
+    assert_eq!(adapter.model_field_names(note_type.model_name).await.unwrap(), note_type.fields);
+    // The old note gets the new field, empty
+    let collection = anki.collection();
+    let note = collection.notes.values().next().unwrap();
+    assert_eq!(note.field("FrontAudio"), Some(""));
+    assert_eq!(note.field("Front"), Some("rojo"));
+}
+
+#[tokio::test]
+async fn test_inserted_cards_get_audio_from_any_tts_engine() {
+    let (anki, _server, adapter) = start().await;
+    adapter.ensure_note_type_exists(NoteKind::Basic).await.unwrap();
+    adapter.create_deck("Spanish").await.unwrap();
+    let tts = SilentTts::new();
+
+    let mut cards = vec![card("rojo")];
+    let report = insert_cards(&adapter, &mut cards, "Spanish", NoteKind::Basic, &[], "Spanish", Some(&tts)).await.unwrap();
+
+    assert_eq!(report.added().len(), 1);
+    assert_eq!(tts.calls(), vec![
+        ("rojo".to_string(), "es".to_string()),
+        ("Example with rojo".to_string(), "es".to_string()),
+    ]);
+    let front_audio = cards[0].front_audio.clone().unwrap();
+    let collection = anki.collection();
+    assert!(collection.media.contains_key(&front_audio));
+    let note = collection.notes.values().next().unwrap();
+    assert_eq!(note.field("FrontAudio"), Some(format!("[sound:{}]", front_audio).as_str()));
+}
+
+#[tokio::test]
+async fn test_wordcraft_notes_query_finds_every_note_type() {
+    let (_anki, _server, adapter) = start().await;
+    adapter.create_deck("Mixed").await.unwrap();
+    let mut added = Vec::new();
+    for kind in NOTE_KINDS {
+        adapter.ensure_note_type_exists(kind).await.unwrap();
+        let report = adapter.add_cards_tagged("Mixed", &[card(kind.name())], kind, &[]).await.unwrap();
+        added.extend(report.added());
+    }
+
+    assert_eq!(added.len(), NOTE_KINDS.len());
+    assert_eq!(adapter.find_notes(WORDCRAFT_NOTES_QUERY).await.unwrap(), added);
+}
+
+#[tokio::test]
+async fn test_deck_search_handles_non_ascii_deck_names() {
+    let (_anki, _server, adapter) = start().await;
+    adapter.ensure_note_type_exists(NoteKind::Basic).await.unwrap();
+    adapter.create_deck("食べ物::果物").await.unwrap();
+    let fruit = adapter.add_cards_tagged("食べ物::果物", &[card("りんご")], NoteKind::Basic, &[]).await.unwrap();
+    let food = adapter.add_cards_tagged("Food", &[card("pan")], NoteKind::Basic, &[]).await.unwrap();
+
+    assert_eq!(adapter.find_notes("deck:Food").await.unwrap(), food.added());
+    assert_eq!(adapter.find_notes("deck:食べ物").await.unwrap(), fruit.added());
+}
+
+#[tokio::test]
+async fn test_searches_and_card_state() {
+    let (_anki, _server, adapter) = start().await;
+    adapter.ensure_note_type_exists(NoteKind::Bidirectional).await.unwrap();
+    adapter.create_deck("Food").await.unwrap();
+    let report = adapter.add_cards_tagged("Food", &[card("pan"), card("queso")], NoteKind::Bidirectional, &["dairy".to_string()]).await.unwrap();
+    adapter.remove_tags(&report.added()[..1], &["dairy"]).await.unwrap();
+
+    // Two templates, two cards per note
+    let cards = adapter.find_cards("deck:Food").await.unwrap();
+    assert_eq!(cards.len(), 4);
+    assert_eq!(adapter.find_notes("tag:dairy").await.unwrap(), report.added()[1..].to_vec());
+    assert_eq!(adapter.find_notes("deck:Food -tag:dairy").await.unwrap(), report.added()[..1].to_vec());
+    assert_eq!(adapter.find_notes("front:QUESO").await.unwrap(), report.added()[1..].to_vec());
+    assert_eq!(adapter.find_notes("\"note:Wordcraft Bidirectional\" pan").await.unwrap().len(), 1);
+    assert!(adapter.find_cards("deck:Food or deck:Other").await.is_err());
+
+    assert!(adapter.suspend(&cards[..1]).await.unwrap());
+    assert!(!adapter.suspend(&cards[..1]).await.unwrap());
+    assert_eq!(adapter.find_cards("is:suspended").await.unwrap(), cards[..1].to_vec());
+    assert_eq!(adapter.are_suspended(&[cards[0], cards[1], 42]).await.unwrap(), vec![Some(true), Some(false), None]);
+
+    let info = adapter.cards_info(&cards[..1]).await.unwrap();
+    assert_eq!(info[0].deck_name, "Food");
+    assert_eq!(info[0].queue, -1);
+    assert!(info[0].question.contains("pan"));
+
+    let stats = adapter.get_deck_stats(&["Food"]).await.unwrap();
+    let food = stats.values().next().unwrap();
+    assert_eq!((food.total_in_deck, food.new_count), (4, 3));
+}
+
+#[tokio::test]
+async fn test_deleting_a_deck_deletes_its_notes() {
+    let (anki, _server, adapter) = start().await;
+    adapter.ensure_note_type_exists(NoteKind::Basic).await.unwrap();
+    adapter.create_deck("Keep").await.unwrap();
+    adapter.create_deck("Drop::Sub").await.unwrap();
+    adapter.add_cards("Keep", &[card("uno")]).await.unwrap();
+    adapter.add_cards("Drop::Sub", &[card("dos")]).await.unwrap();
+
+    adapter.delete_decks(&["Drop"], true).await.unwrap();
+
+    assert_eq!(adapter.deck_names().await.unwrap(), vec!["Default", "Keep"]);
+    let collection = anki.collection();
+    assert_eq!(collection.notes.len(), 1);
+    assert_eq!(collection.cards.len(), 1);
+}
+
+#[tokio::test]
+async fn test_media_round_trip() {
+    let (_anki, _server, adapter) = start().await;
+    let file = MediaFile { filename: "wordcraft-hola.mp3".to_string(), data: b"audio".to_vec() };
+
+    assert_eq!(adapter.store_media(&file).await.unwrap(), "wordcraft-hola.mp3");
+    assert_eq!(adapter.get_media_files_names("wordcraft-*").await.unwrap(), vec!["wordcraft-hola.mp3"]);
+    assert_eq!(adapter.retrieve_media_file("wordcraft-hola.mp3").await.unwrap().as_deref(), Some("YXVkaW8="));
+
+    adapter.delete_media_file("wordcraft-hola.mp3").await.unwrap();
+    assert_eq!(adapter.retrieve_media_file("wordcraft-hola.mp3").await.unwrap(), None);
+}
+
+#[test]
+fn test_unknown_actions_and_bad_params_are_errors() {
+    let anki = FakeAnki::new();
+
+    assert_eq!(anki.handle(&json!({ "action": "version", "version": 6 })), json!({ "result": 6, "error": null }));
+    assert_eq!(anki.handle(&json!({ "action": "exportPackage", "version": 6 }))["error"], "unsupported action");
+    let missing = anki.handle(&json!({ "action": "createDeck", "version": 6 }));
+    assert!(missing["error"].as_str().unwrap().starts_with("invalid params"));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_generate_adds_the_deck_end_to_end() {
+    let data_dir = tempfile::tempdir().unwrap();
+    std::env::set_var("XDG_DATA_HOME", data_dir.path());
+    let (anki, server, _adapter) = start().await;
+    let config = Config {
+        anki_connect_url: server.url.clone(),
+        engine: "fake".to_string(),
+        cache: CacheConfig { dir: Some(data_dir.path().join("cache").display().to_string()), ..CacheConfig::default() },
+        ..Config::default()
+    };
+    let args = |deck: Option<&str>| GenerateArgs {
+        generation: GenerationArgs {
+            native: Some("English".to_string()),
+            target: Some("Spanish".to_string()),
+            topic: Some("Food".to_string()),
+            deck: deck.map(str::to_string),
+            note_type: Some(NoteKind::Basic),
+            ..Default::default()
+        },
+        yes: true,
+        ..Default::default()
+    };
+
+    run_generate(&config, args(None)).await.expect("first run failed");
+    // The words are already in the deck, so a second run adds nothing
+    run_generate(&config, args(Some("Food in Spanish"))).await.expect("second run failed");
+    std::env::remove_var("XDG_DATA_HOME");
+
+    let collection = anki.collection();
+    assert!(collection.decks.contains_key("Food in Spanish"));
+    assert_eq!(collection.notes.len(), FAKE_CARD_COUNT);
+    assert!(collection.cards.values().all(|card| card.deck == "Food in Spanish"));
+    assert!(collection.models.contains_key(NoteKind::Basic.note_type().model_name));
+}
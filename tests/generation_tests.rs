@@ -0,0 +1,517 @@
+use autoflashcard::generation::{
+    build_system_message, extract_json, generate_flashcards, generate_flashcards_streaming, generation_prompt,
+    normalize_front, remove_known_cards, render_template, salvage_flashcards, Flashcard, FlashcardResponse, GenerationOptions,
+};
+use autoflashcard::level::ProficiencyLevel;
+use autoflashcard::note_type::NoteKind;
+use autoflashcard::prompt::FlashcardSettings;
+use autoflashcard::generator::{
+    ChatMessage, CompletionRequest, EngineConfig, FakeGenerator, FlashcardGenerator, Role, FAKE_CARD_COUNT,
+};
+use autoflashcard::config::Config;
+use autoflashcard::WordcraftError;
+use autoflashcard::ollama::{OllamaConfig, DEFAULT_OLLAMA_BASE_URL};
+
+#[test]
+fn test_extract_json_valid() {
+    let text = r#"Here is the response:
+    {
+        "deck_name": "Test Deck",
+        "cards": [
+            {
+                "front": "Hello",
+                "back": "Hola",
+                "example": "Hello, world!",
+                "example_translate": "¡Hola, mundo!"
+            }
+        ]
+    }
+    Some additional text"#;
+    
+    let result = extract_json(text);
+    assert!(result.is_ok());
+    
+    let json = result.unwrap();
+    assert!(json.contains("deck_name"));
+    assert!(json.contains("Test Deck"));
+}
+
+#[test]
+fn test_extract_json_no_json() {
+    let text = "This is just plain text without any JSON";
+    let result = extract_json(text);
+    assert!(result.is_err());
+    assert_eq!(result.unwrap_err().to_string(), "No JSON found in assistant's reply");
+}
+
+#[test]
+fn test_extract_json_nested_braces() {
+    let text = r#"Response: {"deck_name": "Test", "cards": [{"front": "a", "back": "b", "example": "{test}", "example_translate": "test"}]}"#;
+    let result = extract_json(text);
+    assert!(result.is_ok());
+    let json = result.unwrap();
+    assert!(json.contains("{test}"));
+}
+
+#[test]
+fn test_flashcard_serialization() {
+    let flashcard = Flashcard {
+        front: "Hello".to_string(),
+        back: "Hola".to_string(),
+        example: "Hello, friend!".to_string(),
+        example_translate: "¡Hola, amigo!".to_string(),
+        ..Default::default()
+    };
+    
+    let json = serde_json::to_string(&flashcard).unwrap();
+    assert!(json.contains("\"front\":\"Hello\""));
+    assert!(json.contains("\"back\":\"Hola\""));
+    
+    let deserialized: Flashcard = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized.front, "Hello");
+    assert_eq!(deserialized.back, "Hola");
+}
+
+#[test]
+fn test_flashcard_response_serialization() {
+    let response = FlashcardResponse {
+        deck_name: "Spanish Basics".to_string(),
+        cards: vec![
+            Flashcard {
+                front: "One".to_string(),
+                back: "Uno".to_string(),
+                example: "One apple".to_string(),
+                example_translate: "Una manzana".to_string(),
+                ..Default::default()
+            },
+            Flashcard {
+                front: "Two".to_string(),
+                back: "Dos".to_string(),
+                example: "Two cats".to_string(),
+                example_translate: "Dos gatos".to_string(),
+                ..Default::default()
+            },
+        ],
+    };
+    
+    let json = serde_json::to_string(&response).unwrap();
+    let deserialized: FlashcardResponse = serde_json::from_str(&json).unwrap();
+    
+    assert_eq!(deserialized.deck_name, "Spanish Basics");
+    assert_eq!(deserialized.cards.len(), 2);
+    assert_eq!(deserialized.cards[0].front, "One");
+    assert_eq!(deserialized.cards[1].front, "Two");
+}
+
+#[test]
+fn test_generate_flashcards_missing_api_key() {
+    // The OpenAI API needs a key
+    let config = Config { engine: "openai".to_string(), ..Config::default() };
+
+    let result = EngineConfig::from_config(&config);
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("OPEN_API_KEY"));
+}
+
+#[test]
+fn test_generate_flashcards_invalid_engine() {
+    let config = Config { engine: "invalid_engine".to_string(), ..Config::default() };
+
+    // An unknown engine is a configuration error, not a panic
+    let result = EngineConfig::from_config(&config);
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("invalid_engine"));
+}
+
+#[test]
+fn test_engine_config_from_config() {
+    let mut config = Config { engine: "Ollama".to_string(), ..Config::default() };
+    config.ollama.model = "llama3.2".to_string();
+    assert_eq!(EngineConfig::from_config(&config).unwrap(), EngineConfig::Ollama(OllamaConfig {
+        base_url: DEFAULT_OLLAMA_BASE_URL.to_string(),
+        model: "llama3.2".to_string(),
+    }));
+
+    config.engine = "fake".to_string();
+    assert_eq!(EngineConfig::from_config(&config).unwrap(), EngineConfig::Fake);
+
+    // A local OpenAI-compatible server needs no key
+    config.engine = "openai-compatible".to_string();
+    config.openai.base_url = "http://192.168.1.20:8080/v1".to_string();
+    config.openai.headers = vec![("X-Team".to_string(), "lab".to_string())];
+    match EngineConfig::from_config(&config).unwrap() {
+        EngineConfig::OpenAI(openai) => {
+            assert_eq!(openai.base_url, "http://192.168.1.20:8080/v1");
+            assert!(openai.api_key.is_none());
+            assert_eq!(openai.headers, vec![("X-Team".to_string(), "lab".to_string())]);
+            assert!(openai.structured_output);
+        }
+        other => panic!("Unexpected engine config: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_generate_flashcards_with_fake_generator() {
+    let generator = FakeGenerator::new();
+    let input = "Native Language: English\nTarget Language: Spanish\nTopic: Colors\n";
+
+    let response = generate_flashcards(&generator, input, &GenerationOptions::default()).await
+        .expect("Generation failed")
+        .response;
+
+    assert_eq!(response.deck_name, "Colors in Spanish");
+    assert_eq!(response.cards.len(), FAKE_CARD_COUNT);
+
+    let calls = generator.calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0][0].role, Role::System);
+    assert_eq!(calls[0][1], ChatMessage::user(input));
+}
+
+#[tokio::test]
+async fn test_generate_flashcards_drops_excluded_cards() {
+    let generator = FakeGenerator::new();
+    let options = GenerationOptions {
+        exclude: vec!["Spanish Colors 1".to_string(), "spanish colors 2".to_string()],
+        ..Default::default()
+    };
+
+    let response = generate_flashcards(&generator, "Target Language: Spanish\nTopic: Colors", &options).await
+        .expect("Generation failed")
+        .response;
+
+    assert_eq!(response.cards.len(), FAKE_CARD_COUNT - 2);
+    assert!(generator.calls()[0][0].content.contains("Spanish Colors 1"));
+}
+
+struct EchoGenerator;
+
+#[async_trait::async_trait]
+impl FlashcardGenerator for EchoGenerator {
+    fn engine(&self) -> &str {
+        "echo"
+    }
+
+    fn model(&self) -> &str {
+        "echo-1"
+    }
+
+    async fn complete(&self, _request: &CompletionRequest) -> Result<String, WordcraftError> {
+        Err(WordcraftError::llm_transport("model is offline"))
+    }
+}
+
+#[tokio::test]
+async fn test_generate_flashcards_with_custom_generator_error() {
+    let result = generate_flashcards(&EchoGenerator, "Topic: Food", &GenerationOptions::default()).await;
+
+    let err = result.unwrap_err();
+    assert!(matches!(err, WordcraftError::LlmTransport { .. }));
+    let message = err.to_string();
+    assert!(message.contains("echo (echo-1)"));
+    assert!(message.contains("model is offline"));
+}
+
+const VALID_DECK: &str = r#"{"deck_name": "Colors in Spanish", "cards": [
+    {"front": "rojo", "back": "red", "example": "Es rojo.", "example_translate": "It is red."}
+]}"#;
+
+// Second card is missing its example_translate, the third is cut off
+const BROKEN_DECK: &str = r#"{"deck_name": "Colors in Spanish", "cards": [
+    {"front": "rojo", "back": "red", "example": "Es rojo.", "example_translate": "It is red."},
+    {"front": "azul", "back": "blue", "example": "Es azul."},
+    {"front": "verde", "back": "green", "example": "Es {verde}.", "example_translate": "It is green."},
+    {"front": "negro", "back": "bla"#;
+
+#[tokio::test]
+async fn test_generate_flashcards_repairs_invalid_reply() {
+    let generator = FakeGenerator::with_replies(vec!["Sorry, here: {\"deck_name\": }", VALID_DECK]);
+
+    let outcome = generate_flashcards(&generator, "Topic: Colors", &GenerationOptions::default()).await
+        .expect("Generation failed");
+
+    assert!(!outcome.salvaged);
+    assert_eq!(outcome.response.cards.len(), 1);
+    assert_eq!(outcome.attempts.len(), 2);
+    assert!(outcome.attempts[0].error.is_some());
+    assert_eq!(outcome.attempts[1].error, None);
+
+    // The repair request carries the bad reply and the parse error
+    let calls = generator.calls();
+    assert_eq!(calls.len(), 2);
+    let repair = &calls[1];
+    assert_eq!(repair.len(), 4);
+    assert_eq!(repair[2], ChatMessage::assistant("Sorry, here: {\"deck_name\": }"));
+    assert_eq!(repair[3].role, Role::User);
+    assert!(repair[3].content.contains(outcome.attempts[0].error.as_deref().unwrap()));
+}
+
+#[tokio::test]
+async fn test_generate_flashcards_salvages_cards_after_repairs_run_out() {
+    let generator = FakeGenerator::with_replies(vec![BROKEN_DECK]);
+    let options = GenerationOptions { max_repair_attempts: 1, ..Default::default() };
+
+    let outcome = generate_flashcards(&generator, "Topic: Colors", &options).await
+        .expect("Generation failed");
+
+    assert!(outcome.salvaged);
+    assert_eq!(outcome.attempts.len(), 2);
+    assert!(outcome.attempts.iter().all(|attempt| attempt.error.is_some()));
+    assert_eq!(outcome.response.deck_name, "Colors in Spanish");
+    let fronts: Vec<&str> = outcome.response.cards.iter().map(|card| card.front.as_str()).collect();
+    assert_eq!(fronts, vec!["rojo", "verde"]);
+}
+
+#[tokio::test]
+async fn test_generate_flashcards_fails_when_nothing_can_be_salvaged() {
+    let generator = FakeGenerator::with_replies(vec!["I cannot help with that."]);
+    let options = GenerationOptions { max_repair_attempts: 0, ..Default::default() };
+
+    let err = generate_flashcards(&generator, "Topic: Colors", &options).await.unwrap_err();
+    match &err {
+        WordcraftError::LlmMalformedOutput { raw, .. } => assert_eq!(raw, "I cannot help with that."),
+        other => panic!("Unexpected error: {:?}", other),
+    }
+
+    assert!(err.to_string().contains("No usable flashcards after 1 attempts"));
+    assert_eq!(generator.calls().len(), 1);
+}
+
+#[tokio::test]
+async fn test_generate_flashcards_streaming_previews_each_card() {
+    let generator = FakeGenerator::new();
+    let options = GenerationOptions {
+        exclude: vec!["Spanish Colors 3".to_string()],
+        ..Default::default()
+    };
+
+    let mut previewed: Vec<String> = Vec::new();
+    let outcome = generate_flashcards_streaming(&generator, "Target Language: Spanish\nTopic: Colors", &options, |card| {
+        previewed.push(card.front.clone());
+    })
+    .await
+    .expect("Generation failed");
+
+    let fronts: Vec<String> = outcome.response.cards.iter().map(|card| card.front.clone()).collect();
+    assert_eq!(previewed, fronts);
+    assert_eq!(previewed.len(), FAKE_CARD_COUNT - 1);
+    assert!(!previewed.contains(&"Spanish Colors 3".to_string()));
+}
+
+#[tokio::test]
+async fn test_generate_flashcards_streaming_repairs_after_preview() {
+    let generator = FakeGenerator::with_replies(vec![BROKEN_DECK, VALID_DECK]);
+
+    let mut previewed: Vec<String> = Vec::new();
+    let outcome = generate_flashcards_streaming(&generator, "Topic: Colors", &GenerationOptions::default(), |card| {
+        previewed.push(card.front.clone());
+    })
+    .await
+    .expect("Generation failed");
+
+    // Only the complete cards of the broken first reply were previewed
+    assert_eq!(previewed, vec!["rojo", "verde"]);
+    assert_eq!(outcome.attempts.len(), 2);
+    assert_eq!(outcome.response.cards.len(), 1);
+}
+
+#[test]
+fn test_salvage_flashcards_without_deck_name() {
+    let text = r#"[{"front": "uno", "back": "one", "example": "Uno.", "example_translate": "One."}, {"front": "#;
+
+    let response = salvage_flashcards(text).expect("one card");
+
+    assert_eq!(response.deck_name, "Generated flashcards");
+    assert_eq!(response.cards.len(), 1);
+    assert!(salvage_flashcards("no cards here").is_none());
+}
+
+#[test]
+fn test_extract_json_with_markdown() {
+    let text = r#"Here's the JSON response:
+```json
+{
+    "deck_name": "Japanese Vocabulary",
+    "cards": [
+        {
+            "front": "犬",
+            "back": "dog",
+            "example": "犬が好きです",
+            "example_translate": "I like dogs"
+        }
+    ]
+}
+```"#;
+    
+    let result = extract_json(text);
+    assert!(result.is_ok());
+    let json = result.unwrap();
+    assert!(json.contains("Japanese Vocabulary"));
+    assert!(json.contains("犬"));
+}
+
+#[test]
+fn test_extract_json_multiple_json_blocks() {
+    let text = r#"First block: {"test": 1}
+    The actual response:
+    {
+        "deck_name": "Numbers",
+        "cards": []
+    }"#;
+    
+    let result = extract_json(text);
+    assert!(result.is_ok());
+    // Should extract the first valid JSON block
+    let json = result.unwrap();
+    assert!(json.contains("test") || json.contains("deck_name"));
+}
+
+#[test]
+fn test_normalize_front_drops_readings_and_case() {
+    assert_eq!(normalize_front("家 (いえ) (ie)"), "家");
+    assert_eq!(normalize_front("赤（あか）"), "赤");
+    assert_eq!(normalize_front("  La   Casa "), "la casa");
+}
+
+#[test]
+fn test_remove_known_cards() {
+    let card = |front: &str| Flashcard {
+        front: front.to_string(),
+        back: "back".to_string(),
+        example: "example".to_string(),
+        example_translate: "translation".to_string(),
+        ..Default::default()
+    };
+    let mut response = FlashcardResponse {
+        deck_name: "Places".to_string(),
+        cards: vec![card("家 (いえ) (ie)"), card("駅 (えき) (eki)"), card("学校 (がっこう) (gakkou)")],
+    };
+
+    remove_known_cards(&mut response, &["家".to_string(), "学校 (がっこう)".to_string()]);
+
+    assert_eq!(response.cards.len(), 1);
+    assert_eq!(response.cards[0].front, "駅 (えき) (eki)");
+}
+
+#[test]
+fn test_build_system_message_lists_excluded_words() {
+    let plain = build_system_message(&GenerationOptions::default());
+    assert!(!plain.contains("already knows"));
+
+    let options = GenerationOptions {
+        exclude: vec!["家".to_string(), "駅".to_string()],
+        ..Default::default()
+    };
+    let message = build_system_message(&options);
+    assert!(message.starts_with(&plain));
+    assert!(message.contains("already knows"));
+    assert!(message.contains("家\n駅"));
+}
+
+#[test]
+fn test_build_system_message_lists_each_excluded_word_once() {
+    let options = GenerationOptions {
+        exclude: vec!["家".to_string(), "駅".to_string(), "家".to_string(), "猫".to_string()],
+        learner_profile: Some("Learner profile\n- Mastered (use freely in example sentences): 駅".to_string()),
+        learner_profile_words: vec!["駅".to_string()],
+        ..Default::default()
+    };
+    let message = build_system_message(&options);
+    let excluded = message.rsplit_once("already knows").expect("no exclusion list").1;
+    assert_eq!(excluded.lines().filter(|line| !line.is_empty()).skip(1).collect::<Vec<_>>(), vec!["家", "猫"]);
+}
+
+#[test]
+fn test_build_system_message_fills_count_and_level() {
+    let plain = build_system_message(&GenerationOptions::default());
+    assert!(plain.contains("Result should contain at least 15 flashcards.\nFront of the flashcard"));
+    assert!(!plain.contains("{card_count}") && !plain.contains("{level}"));
+
+    let options = GenerationOptions {
+        card_count: Some(8),
+        level: Some(ProficiencyLevel::Jlpt(4)),
+        ..Default::default()
+    };
+    let message = build_system_message(&options);
+    assert!(message.contains("Result should contain exactly 8 flashcards.\nThe student's level is JLPT N4 (elementary)."));
+}
+
+#[test]
+fn test_generation_prompt_includes_count_and_level() {
+    let mut settings = FlashcardSettings {
+        native_language: "English".to_string(),
+        target_language: "Spanish".to_string(),
+        topic: "Colors".to_string(),
+        deck_name: None,
+        note_kind: NoteKind::Basic,
+        card_count: None,
+        level: None,
+    };
+
+    assert_eq!(generation_prompt(&settings), "Native Language: English\nTarget Language: Spanish\nTopic: Colors\n");
+    settings.card_count = Some(5);
+    settings.level = Some(ProficiencyLevel::Cefr(2));
+    assert!(generation_prompt(&settings).ends_with("Topic: Colors\nNumber of cards: 5\nLevel: CEFR A2\n"));
+}
+
+#[test]
+fn test_render_template_drops_empty_placeholder_lines() {
+    let template = "Count: {count}\n{note}\nEnd {note}";
+    assert_eq!(render_template(template, &[("count", "3"), ("note", "")]), "Count: 3\nEnd ");
+    assert_eq!(render_template(template, &[("count", "3"), ("note", "hi")]), "Count: 3\nhi\nEnd hi");
+    // Lines that were empty to begin with are kept
+    assert_eq!(render_template("a\n\nb", &[]), "a\n\nb");
+}
+
+#[tokio::test]
+async fn test_generate_flashcards_cuts_a_long_deck_to_the_count() {
+    let generator = FakeGenerator::new();
+    let options = GenerationOptions { card_count: Some(4), ..Default::default() };
+
+    let outcome = generate_flashcards(&generator, "Topic: Colors", &options).await.unwrap();
+
+    assert_eq!(outcome.response.cards.len(), 4);
+    assert_eq!(generator.calls().len(), 1);
+}
+
+const SECOND_DECK: &str = r#"{"deck_name": "Colors in Spanish", "cards": [
+    {"front": "rojo", "back": "red", "example": "Es rojo.", "example_translate": "It is red."},
+    {"front": "azul", "back": "blue", "example": "Es azul.", "example_translate": "It is blue."},
+    {"front": "verde", "back": "green", "example": "Es verde.", "example_translate": "It is green."}
+]}"#;
+
+#[tokio::test]
+async fn test_generate_flashcards_tops_up_a_short_deck() {
+    let generator = FakeGenerator::with_replies(vec![VALID_DECK, SECOND_DECK]);
+    let options = GenerationOptions { card_count: Some(3), ..Default::default() };
+
+    let outcome = generate_flashcards(&generator, "Topic: Colors", &options).await.unwrap();
+
+    let fronts: Vec<&str> = outcome.response.cards.iter().map(|card| card.front.as_str()).collect();
+    assert_eq!(fronts, vec!["rojo", "azul", "verde"]);
+    assert_eq!(outcome.attempts.len(), 2);
+
+    // The top-up shows the deck so far and asks for the missing cards
+    let calls = generator.calls();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[1][2].role, Role::Assistant);
+    assert!(calls[1][2].content.contains("rojo"));
+    assert!(calls[1][3].content.ends_with("Number of flashcards needed: 2"));
+}
+
+#[tokio::test]
+async fn test_generate_flashcards_stops_topping_up_after_max_top_ups() {
+    let generator = FakeGenerator::with_replies(vec![VALID_DECK]);
+    let options = GenerationOptions {
+        card_count: Some(5),
+        max_top_ups: 2,
+        ..Default::default()
+    };
+
+    let outcome = generate_flashcards(&generator, "Topic: Colors", &options).await.unwrap();
+
+    // Every top-up repeats the deck, so it stays short
+    assert_eq!(outcome.response.cards.len(), 1);
+    assert_eq!(generator.calls().len(), 3);
+    assert_eq!(outcome.attempts.len(), 3);
+}
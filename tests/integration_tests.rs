@@ -1,5 +1,6 @@
 use autoflashcard::anki_adapter::AnkiAdapter;
-use autoflashcard::langchain::{Flashcard, FlashcardResponse};
+use autoflashcard::generation::{Flashcard, FlashcardResponse};
+use autoflashcard::note_type::NoteKind;
 use mockito::Server;
 use serde_json::json;
 use serial_test::serial;
@@ -11,7 +12,6 @@ async fn test_full_workflow_with_mock_anki() {
     // Setup mock server
     let mut server = Server::new_async().await;
     let url = server.url();
-    std::env::set_var("ANKI_CONNECT_URL", &url);
     
     // Mock connection check
     let connection_mock = server.mock("POST", "/")
@@ -88,7 +88,7 @@ async fn test_full_workflow_with_mock_anki() {
     }).collect();
     
     // Create adapter and run through the workflow
-    let adapter = AnkiAdapter::new().expect("Failed to create adapter");
+    let adapter = AnkiAdapter::new(&url);
     
     // Check connection
     adapter.check_connection().await.expect("Connection check failed");
@@ -105,12 +105,14 @@ async fn test_full_workflow_with_mock_anki() {
                 back: "Hello".to_string(),
                 example: "Hola, ¿cómo estás?".to_string(),
                 example_translate: "Hello, how are you?".to_string(),
+                ..Default::default()
             },
             Flashcard {
                 front: "Adiós".to_string(),
                 back: "Goodbye".to_string(),
                 example: "Adiós, hasta luego".to_string(),
                 example_translate: "Goodbye, see you later".to_string(),
+                ..Default::default()
             },
         ],
     };
@@ -139,7 +141,6 @@ async fn test_full_workflow_with_mock_anki() {
     }
     
     // Cleanup
-    std::env::remove_var("ANKI_CONNECT_URL");
 }
 
 #[tokio::test]
@@ -148,7 +149,6 @@ async fn test_error_handling_in_workflow() {
     // Setup mock server
     let mut server = Server::new_async().await;
     let url = server.url();
-    std::env::set_var("ANKI_CONNECT_URL", &url);
     
     // Mock connection failure
     let connection_mock = server.mock("POST", "/")
@@ -162,7 +162,7 @@ async fn test_error_handling_in_workflow() {
         }).to_string())
         .create();
     
-    let adapter = AnkiAdapter::new().expect("Failed to create adapter");
+    let adapter = AnkiAdapter::new(&url);
     
     // Connection should fail
     let result = adapter.check_connection().await;
@@ -172,7 +172,6 @@ async fn test_error_handling_in_workflow() {
     connection_mock.assert();
     
     // Cleanup
-    std::env::remove_var("ANKI_CONNECT_URL");
 }
 
 #[tokio::test]
@@ -181,7 +180,6 @@ async fn test_duplicate_card_handling() {
     // Setup mock server
     let mut server = Server::new_async().await;
     let url = server.url();
-    std::env::set_var("ANKI_CONNECT_URL", &url);
     
     // Mock duplicate card error
     let duplicate_mock = server.mock("POST", "/")
@@ -195,7 +193,7 @@ async fn test_duplicate_card_handling() {
         }).to_string())
         .create();
     
-    let adapter = AnkiAdapter::new().expect("Failed to create adapter");
+    let adapter = AnkiAdapter::new(&url);
     
     // Adding duplicate card should fail
     let result = adapter.add_card(
@@ -212,7 +210,6 @@ async fn test_duplicate_card_handling() {
     duplicate_mock.assert();
     
     // Cleanup
-    std::env::remove_var("ANKI_CONNECT_URL");
 }
 
 #[tokio::test]
@@ -221,7 +218,6 @@ async fn test_model_already_exists_scenario() {
     // Setup mock server
     let mut server = Server::new_async().await;
     let url = server.url();
-    std::env::set_var("ANKI_CONNECT_URL", &url);
     
     // Mock model check (model already exists)
     let model_check_mock = server.mock("POST", "/")
@@ -234,8 +230,19 @@ async fn test_model_already_exists_scenario() {
             "error": null
         }).to_string())
         .create();
+
+    // Mock styling check (model is at the current version)
+    let _styling_mock = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "modelStyling"
+        }).to_string()))
+        .with_body(json!({
+            "result": { "css": NoteKind::Basic.note_type().styling() },
+            "error": null
+        }).to_string())
+        .create();
     
-    let adapter = AnkiAdapter::new().expect("Failed to create adapter");
+    let adapter = AnkiAdapter::new(&url);
     
     // Should succeed without creating model
     let result = adapter.ensure_wordcraft_model_exists().await;
@@ -244,7 +251,6 @@ async fn test_model_already_exists_scenario() {
     model_check_mock.assert();
     
     // Cleanup
-    std::env::remove_var("ANKI_CONNECT_URL");
 }
 
 #[test]
@@ -266,6 +272,7 @@ fn test_flashcard_response_with_many_cards() {
             back: format!("Back {}", i),
             example: format!("Example {}", i),
             example_translate: format!("Translation {}", i),
+            ..Default::default()
         }
     }).collect();
     
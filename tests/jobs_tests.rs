@@ -0,0 +1,309 @@
+use autoflashcard::anki_adapter::{deck_query, AnkiAdapter};
+use autoflashcard::cache::ResponseCache;
+use autoflashcard::generator::FakeGenerator;
+use autoflashcard::jobs::{state_path, JobCounts, JobFile, JobRunner, JobState, JobStatus, JobSummary};
+use autoflashcard::level::ProficiencyLevel;
+use autoflashcard::note_type::NoteKind;
+use autoflashcard::prompt_pack::PromptLibrary;
+use serde_json::json;
+use serial_test::serial;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+const JOBS_TOML: &str = r#"
+concurrency = 3
+
+[defaults]
+target = "Spanish"
+count = 10
+level = "B1"
+tags = ["onboarding"]
+
+[[job]]
+topic = "Colors"
+tags = ["colors", "onboarding"]
+
+[[job]]
+topic = "Food"
+deck = "Spanish::Food"
+target = "Japanese"
+note_type = "cloze"
+count = 5
+level = "N5"
+"#;
+
+#[test]
+fn test_job_file_applies_defaults() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("jobs.toml");
+    std::fs::write(&path, JOBS_TOML).unwrap();
+
+    let file = JobFile::load(&path).unwrap();
+    assert_eq!(file.concurrency, Some(3));
+
+    let jobs = file.jobs().unwrap();
+    assert_eq!(jobs.len(), 2);
+    assert_eq!(jobs[0].deck_name, "Colors in Spanish");
+    assert_eq!(jobs[0].settings.native_language, "English");
+    assert_eq!(jobs[0].settings.note_kind, NoteKind::Basic);
+    assert_eq!(jobs[0].settings.card_count, Some(10));
+    assert_eq!(jobs[0].settings.level, Some(ProficiencyLevel::Cefr(3)));
+    assert_eq!(jobs[0].tags, vec!["onboarding", "colors"]);
+
+    assert_eq!(jobs[1].deck_name, "Spanish::Food");
+    assert_eq!(jobs[1].settings.target_language, "Japanese");
+    assert_eq!(jobs[1].settings.deck_name.as_deref(), Some("Spanish::Food"));
+    assert_eq!(jobs[1].settings.note_kind, NoteKind::Cloze);
+    assert_eq!(jobs[1].settings.card_count, Some(5));
+    assert_eq!(jobs[1].settings.level, Some(ProficiencyLevel::Jlpt(5)));
+}
+
+#[test]
+fn test_job_file_as_json_and_errors() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("jobs.json");
+    std::fs::write(&path, json!({ "job": [{ "topic": "Animals", "note_type": "reverse" }] }).to_string()).unwrap();
+    let jobs = JobFile::load(&path).unwrap().jobs().unwrap();
+    assert_eq!(jobs[0].settings.note_kind, NoteKind::Bidirectional);
+
+    std::fs::write(&path, json!({ "job": [{ "deck": "Nameless" }] }).to_string()).unwrap();
+    assert_eq!(JobFile::load(&path).unwrap().jobs().unwrap_err().to_string(), "Job 1 has no topic");
+
+    std::fs::write(&path, json!({ "job": [{ "topic": "Food", "note_type": "flip" }] }).to_string()).unwrap();
+    assert!(JobFile::load(&path).unwrap_err().to_string().contains("Unknown note type 'flip'"));
+
+    std::fs::write(&path, json!({ "jobs": [] }).to_string()).unwrap();
+    assert!(JobFile::load(&path).unwrap_err().to_string().starts_with("Invalid job file"));
+}
+
+#[test]
+fn test_jobs_that_make_different_cards_have_different_keys() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("jobs.json");
+    std::fs::write(&path, json!({
+        "defaults": { "target": "Spanish", "deck": "Colors" },
+        "job": [
+            { "topic": "Colors" },
+            { "topic": "Colors", "note_type": "cloze" },
+            { "topic": "Colors", "count": 5 },
+            { "topic": "Colors", "level": "B1" },
+            { "topic": "Colors" }
+        ]
+    }).to_string()).unwrap();
+    let keys: Vec<String> = JobFile::load(&path).unwrap().jobs().unwrap().iter().map(|job| job.key()).collect();
+
+    assert_eq!(keys[0], keys[4]);
+    for (index, key) in keys[..4].iter().enumerate() {
+        assert!(keys[..4].iter().skip(index + 1).all(|other| other != key), "{} is not unique", key);
+    }
+}
+
+#[test]
+fn test_state_path_and_round_trip() {
+    let dir = TempDir::new().unwrap();
+    let path = state_path(&dir.path().join("jobs.toml"));
+    assert_eq!(path.file_name().unwrap(), "jobs.toml.state.json");
+
+    assert_eq!(JobState::load(&path).unwrap(), JobState::default());
+    let mut state = JobState::default();
+    state.completed.insert("Colors".to_string(), JobCounts { added: 3, duplicates: 1, failed: 0 });
+    state.save(&path).unwrap();
+    assert_eq!(JobState::load(&path).unwrap(), state);
+}
+
+fn reply() -> String {
+    json!({
+        "deck_name": "ignored",
+        "cards": [{ "front": "rojo", "back": "red", "example": "Es rojo.", "example_translate": "It is red." }]
+    }).to_string()
+}
+
+// Anki with the Wordcraft models in place; the "Broken" deck cannot be searched
+fn mock_anki(server: &mut mockito::ServerGuard) -> (mockito::Mock, Vec<mockito::Mock>) {
+    let styling = NoteKind::Basic.note_type().styling();
+    let mut mocks = vec![
+        server.mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJsonString(json!({ "action": "version" }).to_string()))
+            .with_body(json!({ "result": 6, "error": null }).to_string())
+            .create(),
+        server.mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJsonString(json!({ "action": "modelNames" }).to_string()))
+            .with_body(json!({ "result": ["Wordcraft"], "error": null }).to_string())
+            .create(),
+        server.mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJsonString(json!({ "action": "modelStyling" }).to_string()))
+            .with_body(json!({ "result": { "css": styling }, "error": null }).to_string())
+            .create(),
+        server.mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJsonString(json!({
+                "action": "findNotes",
+                "params": { "query": deck_query("Broken") }
+            }).to_string()))
+            .with_body(json!({ "result": null, "error": "collection is not available" }).to_string())
+            .create(),
+    ];
+    mocks.push(server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "findNotes",
+            "params": { "query": deck_query("Good") }
+        }).to_string()))
+        .with_body(json!({ "result": [], "error": null }).to_string())
+        .create());
+    mocks.push(server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({ "action": "createDeck" }).to_string()))
+        .with_body(json!({ "result": 1, "error": null }).to_string())
+        .create());
+    mocks.push(server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({ "action": "canAddNotesWithErrorDetail" }).to_string()))
+        .with_body(json!({ "result": [{ "canAdd": true }], "error": null }).to_string())
+        .create());
+
+    let add_notes = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "addNotes",
+            "params": { "notes": [{ "deckName": "Good", "tags": ["wordcraft", "language_learning", "batch"] }] }
+        }).to_string()))
+        .with_body(json!({ "result": [42], "error": null }).to_string())
+        .expect(1)
+        .create();
+
+    (add_notes, mocks)
+}
+
+#[tokio::test]
+#[serial]
+async fn test_runner_resumes_after_failure() {
+    let mut server = mockito::Server::new_async().await;
+    let url = server.url();
+    let (add_notes, _mocks) = mock_anki(&mut server);
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("jobs.json");
+    std::fs::write(&path, json!({
+        "defaults": { "target": "Spanish", "tags": ["batch"] },
+        "job": [{ "topic": "Colors", "deck": "Good" }, { "topic": "Food", "deck": "Broken" }]
+    }).to_string()).unwrap();
+    let jobs = JobFile::load(&path).unwrap().jobs().unwrap();
+
+    let runner = JobRunner {
+        generator: Arc::new(FakeGenerator::with_replies(vec![reply()])),
+        prompts: Arc::new(PromptLibrary::builtin().clone()),
+        adapter: Arc::new(AnkiAdapter::new(&url)),
+        concurrency: 2,
+        exclude: Vec::new(),
+        tts: None,
+        cache: None,
+    };
+
+    let first = runner.run(jobs.clone(), &state_path(&path)).await.unwrap();
+    assert_eq!(first.jobs[0].status, JobStatus::Done(JobCounts { added: 1, duplicates: 0, failed: 0 }));
+    assert!(matches!(&first.jobs[1].status, JobStatus::Failed { error } if error.contains("collection is not available")));
+    assert_eq!(first.failed(), 1);
+
+    // The finished job is not generated or added again
+    let second = runner.run(jobs, &state_path(&path)).await.unwrap();
+    assert_eq!(second.jobs[0].status, JobStatus::Skipped(JobCounts { added: 1, duplicates: 0, failed: 0 }));
+    assert!(matches!(second.jobs[1].status, JobStatus::Failed { .. }));
+    add_notes.assert();
+}
+
+#[tokio::test]
+#[serial]
+async fn test_jobs_with_failed_cards_are_not_finished_but_known_decks_are() {
+    let mut server = mockito::Server::new_async().await;
+    let url = server.url();
+    let (_add_notes, _mocks) = mock_anki(&mut server);
+    let _find_full = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "findNotes",
+            "params": { "query": deck_query("Full") }
+        }).to_string()))
+        .with_body(json!({ "result": [], "error": null }).to_string())
+        .create();
+    let _add_full = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "addNotes",
+            "params": { "notes": [{ "deckName": "Full" }] }
+        }).to_string()))
+        .with_body(json!({ "result": [null], "error": null }).to_string())
+        .create();
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("jobs.json");
+    std::fs::write(&path, json!({
+        "defaults": { "target": "Spanish", "tags": ["batch"] },
+        "job": [{ "topic": "Colors", "deck": "Good" }, { "topic": "Food", "deck": "Full" }]
+    }).to_string()).unwrap();
+    let jobs = JobFile::load(&path).unwrap().jobs().unwrap();
+
+    let runner = |exclude: Vec<String>| JobRunner {
+        generator: Arc::new(FakeGenerator::with_replies(vec![reply()])),
+        prompts: Arc::new(PromptLibrary::builtin().clone()),
+        adapter: Arc::new(AnkiAdapter::new(&url)),
+        concurrency: 1,
+        exclude,
+        tts: None,
+        cache: None,
+    };
+
+    // The only card is one the learner knows, so there is nothing left to add
+    let summary = runner(vec!["rojo".to_string()]).run(jobs[..1].to_vec(), &state_path(&path)).await.unwrap();
+    assert_eq!(summary.jobs[0].status, JobStatus::Done(JobCounts::default()));
+
+    let summary = runner(Vec::new()).run(jobs, &state_path(&path)).await.unwrap();
+    assert_eq!(summary.jobs[0].status, JobStatus::Skipped(JobCounts::default()));
+    assert_eq!(summary.jobs[1].status, JobStatus::Failed { error: "0 cards added, 1 could not be added".to_string() });
+
+    let state = JobState::load(&state_path(&path)).unwrap();
+    assert_eq!(state.completed.len(), 1);
+    assert!(state.completed.keys().all(|key| key.starts_with("Good|")));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_jobs_reuse_cached_decks() {
+    let mut server = mockito::Server::new_async().await;
+    let url = server.url();
+    let (_add_notes, _mocks) = mock_anki(&mut server);
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("jobs.json");
+    std::fs::write(&path, json!({
+        "defaults": { "target": "Spanish", "tags": ["batch"] },
+        "job": [{ "topic": "Colors", "deck": "Good" }]
+    }).to_string()).unwrap();
+    let jobs = JobFile::load(&path).unwrap().jobs().unwrap();
+
+    let generator = FakeGenerator::with_replies(vec![reply()]);
+    let runner = JobRunner {
+        generator: Arc::new(generator.clone()),
+        prompts: Arc::new(PromptLibrary::builtin().clone()),
+        adapter: Arc::new(AnkiAdapter::new(&url)),
+        concurrency: 1,
+        exclude: Vec::new(),
+        tts: None,
+        cache: Some(Arc::new(ResponseCache::new(dir.path().join("cache"), Duration::from_secs(60)))),
+    };
+
+    runner.run(jobs.clone(), &state_path(&path)).await.unwrap();
+    // A restarted run generates the deck again, from the cache
+    std::fs::remove_file(state_path(&path)).unwrap();
+    let summary = runner.run(jobs, &state_path(&path)).await.unwrap();
+
+    assert!(matches!(summary.jobs[0].status, JobStatus::Done(_)));
+    assert_eq!(generator.calls().len(), 1);
+}
+
+#[test]
+fn test_summary_serializes_status_inline() {
+    let summary = JobSummary {
+        jobs: vec![autoflashcard::jobs::JobReport {
+            deck: "Good".to_string(),
+            topic: "Colors".to_string(),
+            status: JobStatus::Failed { error: "boom".to_string() },
+        }],
+    };
+    let value = serde_json::to_value(&summary).unwrap();
+    assert_eq!(value, json!({ "jobs": [{ "deck": "Good", "topic": "Colors", "status": "failed", "error": "boom" }] }));
+}
@@ -0,0 +1,171 @@
+use autoflashcard::json_extract::{balanced_spans, candidates, fenced_blocks, StreamingObjects};
+use autoflashcard::generation::{extract_json, parse_flashcard_response, Flashcard, FlashcardResponse};
+use autoflashcard::schema::flashcard_response_schema;
+use proptest::prelude::*;
+
+#[test]
+fn test_balanced_spans_ignore_brackets_in_strings() {
+    let text = r#"Note {see below} {"front": "a \"}\" b", "list": [1, 2]} done"#;
+
+    assert_eq!(balanced_spans(text), vec![
+        "{see below}",
+        r#"{"front": "a \"}\" b", "list": [1, 2]}"#,
+        "[1, 2]",
+    ]);
+}
+
+#[test]
+fn test_fenced_blocks_skip_language_tag() {
+    let text = "Intro\n```json\n{\"a\": 1}\n```\nNotes\n```\n[2]";
+
+    assert_eq!(fenced_blocks(text), vec!["{\"a\": 1}\n", "[2]"]);
+}
+
+#[test]
+fn test_candidates_prefer_fenced_blocks() {
+    let text = "Example: {\"deck_name\": \"Wrong\", \"cards\": []}\n```json\n{\"deck_name\": \"Right\", \"cards\": []}\n```";
+
+    let candidates = candidates(text);
+    let position = |name: &str| candidates.iter().position(|candidate| candidate.contains(name)).unwrap();
+
+    assert_eq!(position("Right"), 0);
+    assert!(position("Wrong") > position("Right"));
+}
+
+#[test]
+fn test_extract_json_skips_brace_sentences_after_the_deck() {
+    let text = r#"{"deck_name": "Colors", "cards": []}
+Tip: you can also write {rojo} on the card, or use {azul} as a second example."#;
+
+    let json = extract_json(text).unwrap();
+
+    assert!(json.contains("Colors"));
+    assert!(!json.contains("azul"));
+}
+
+#[test]
+fn test_extract_json_accepts_bare_card_array() {
+    let text = r#"Here are your cards:
+[{"front": "uno", "back": "one", "example": "Uno.", "example_translate": "One."}]
+Let me know if you need more!"#;
+
+    let response: FlashcardResponse = serde_json::from_str(&extract_json(text).unwrap()).unwrap();
+
+    assert_eq!(response.deck_name, "Generated flashcards");
+    assert_eq!(response.cards[0].front, "uno");
+}
+
+#[test]
+fn test_streaming_objects_yield_cards_as_they_close() {
+    let mut objects = StreamingObjects::new();
+
+    assert!(objects.push(r#"```json
+{"deck_name": "Col"#).is_empty());
+    assert!(objects.push(r#"ors", "cards": [{"front": "ro"#).is_empty());
+    assert_eq!(objects.push(r##"jo", "example": "} ]"}, {"front""##), vec![r#"{"front": "rojo", "example": "} ]"}"#]);
+    assert_eq!(objects.push(r#": "azul"}]}
+```"#), vec![r#"{"front": "azul"}"#]);
+    assert!(objects.text().ends_with("```"));
+}
+
+fn card_strategy() -> impl Strategy<Value = Flashcard> {
+    // Card text may contain the characters that trip up naive extraction
+    let text = "[a-zA-Z0-9 {}\\[\\]\"\\\\`:,.]{0,20}";
+    (text, text, text, text).prop_map(|(front, back, example, example_translate)| Flashcard {
+        front,
+        back,
+        example,
+        example_translate,
+        ..Default::default()
+    })
+}
+
+fn deck_strategy() -> impl Strategy<Value = FlashcardResponse> {
+    ("[a-zA-Z ]{1,20}", prop::collection::vec(card_strategy(), 0..5))
+        .prop_map(|(deck_name, cards)| FlashcardResponse { deck_name, cards })
+}
+
+// Prose around the payload: plain sentences, optionally with a balanced
+// brace aside that is not a deck
+fn prose_strategy() -> impl Strategy<Value = String> {
+    ("[a-zA-Z0-9 .,:;!?()\n-]{0,40}", prop::option::of("[a-z ]{0,10}"))
+        .prop_map(|(text, aside)| match aside {
+            Some(aside) => format!("{} {{{}}} ", text, aside),
+            None => text,
+        })
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Wrapping {
+    Bare,
+    Fenced,
+    FencedWithLanguage,
+}
+
+fn wrapping_strategy() -> impl Strategy<Value = Wrapping> {
+    prop_oneof![Just(Wrapping::Bare), Just(Wrapping::Fenced), Just(Wrapping::FencedWithLanguage)]
+}
+
+fn wrap(payload: &str, wrapping: Wrapping, before: &str, after: &str) -> String {
+    match wrapping {
+        Wrapping::Bare => format!("{}\n{}\n{}", before, payload, after),
+        Wrapping::Fenced => format!("{}\n```\n{}\n```\n{}", before, payload, after),
+        Wrapping::FencedWithLanguage => format!("{}\n```json\n{}\n```\n{}", before, payload, after),
+    }
+}
+
+proptest! {
+    #[test]
+    fn prop_wrapped_deck_round_trips(
+        deck in deck_strategy(),
+        pretty in any::<bool>(),
+        wrapping in wrapping_strategy(),
+        before in prose_strategy(),
+        after in prose_strategy(),
+    ) {
+        let payload = if pretty {
+            serde_json::to_string_pretty(&deck).unwrap()
+        } else {
+            serde_json::to_string(&deck).unwrap()
+        };
+        let text = wrap(&payload, wrapping, &before, &after);
+
+        let parsed = parse_flashcard_response(&text, &flashcard_response_schema()).unwrap();
+
+        prop_assert_eq!(serde_json::to_value(&parsed).unwrap(), serde_json::to_value(&deck).unwrap());
+    }
+
+    #[test]
+    fn prop_wrapped_card_array_round_trips(
+        cards in prop::collection::vec(card_strategy(), 1..5),
+        wrapping in wrapping_strategy(),
+        before in prose_strategy(),
+        after in prose_strategy(),
+    ) {
+        let payload = serde_json::to_string(&cards).unwrap();
+        let text = wrap(&payload, wrapping, &before, &after);
+
+        let parsed = parse_flashcard_response(&text, &flashcard_response_schema()).unwrap();
+
+        prop_assert_eq!(serde_json::to_value(&parsed.cards).unwrap(), serde_json::to_value(&cards).unwrap());
+    }
+
+    #[test]
+    fn prop_streamed_cards_match_regardless_of_chunking(
+        deck in deck_strategy(),
+        chunk_size in 1usize..16,
+        before in prose_strategy(),
+    ) {
+        let text = format!("{}\n{}", before, serde_json::to_string(&deck).unwrap());
+        let chars: Vec<char> = text.chars().collect();
+
+        let mut objects = StreamingObjects::new();
+        let mut streamed = Vec::new();
+        for chunk in chars.chunks(chunk_size) {
+            streamed.extend(objects.push(&chunk.iter().collect::<String>()));
+        }
+
+        let expected: Vec<String> = deck.cards.iter().map(|card| serde_json::to_string(card).unwrap()).collect();
+        prop_assert_eq!(streamed, expected);
+    }
+}
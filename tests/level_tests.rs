@@ -0,0 +1,58 @@
+use autoflashcard::level::ProficiencyLevel;
+
+#[test]
+fn test_parse_levels_on_every_scale() {
+    let cases = [
+        ("B1", ProficiencyLevel::Cefr(3)),
+        ("cefr c2", ProficiencyLevel::Cefr(6)),
+        ("n5", ProficiencyLevel::Jlpt(5)),
+        ("JLPT N1", ProficiencyLevel::Jlpt(1)),
+        ("HSK3", ProficiencyLevel::Hsk(3)),
+        ("hsk 9", ProficiencyLevel::Hsk(9)),
+        ("TOPIK-2", ProficiencyLevel::Topik(2)),
+    ];
+    for (text, level) in cases {
+        assert_eq!(text.parse::<ProficiencyLevel>(), Ok(level), "{}", text);
+    }
+}
+
+#[test]
+fn test_parse_rejects_levels_off_the_scale() {
+    for text in ["A3", "N6", "N0", "HSK 10", "TOPIK 7", "JLPT 3", "fluent", ""] {
+        let err = text.parse::<ProficiencyLevel>().unwrap_err();
+        assert!(err.starts_with("Unknown level"), "{}", text);
+    }
+}
+
+#[test]
+fn test_display_and_description() {
+    assert_eq!(ProficiencyLevel::Cefr(4).to_string(), "CEFR B2");
+    assert_eq!(ProficiencyLevel::Jlpt(4).to_string(), "JLPT N4");
+    assert_eq!(ProficiencyLevel::Hsk(2).to_string(), "HSK 2");
+    assert_eq!(ProficiencyLevel::Topik(6).to_string(), "TOPIK 6");
+
+    assert_eq!(ProficiencyLevel::Cefr(1).description(), "beginner");
+    assert_eq!(ProficiencyLevel::Jlpt(1).description(), "advanced");
+    assert_eq!(ProficiencyLevel::Jlpt(5).description(), "beginner");
+    assert_eq!(ProficiencyLevel::Hsk(8).description(), "proficient");
+    assert_eq!(ProficiencyLevel::Topik(3).framework(), "TOPIK");
+}
+
+#[test]
+fn test_levels_off_the_scale_do_not_panic() {
+    assert_eq!(ProficiencyLevel::Cefr(0).to_string(), "CEFR 0");
+    assert_eq!(ProficiencyLevel::Cefr(7).to_string(), "CEFR 7");
+    assert_eq!(ProficiencyLevel::Cefr(0).description(), "beginner");
+    assert_eq!(ProficiencyLevel::Jlpt(9).description(), "beginner");
+}
+
+#[test]
+fn test_levels_in_job_files() {
+    #[derive(serde::Deserialize)]
+    struct Entry {
+        level: ProficiencyLevel,
+    }
+    let entry: Entry = toml::from_str("level = \"N3\"").unwrap();
+    assert_eq!(entry.level, ProficiencyLevel::Jlpt(3));
+    assert!(toml::from_str::<Entry>("level = \"Z9\"").is_err());
+}
@@ -1,4 +1,6 @@
 use mockito::{Mock, Server};
+use autoflashcard::anki_adapter::AnkiAdapter;
+use autoflashcard::note_type::NoteKind;
 use serde_json::json;
 
 pub struct MockAnkiServer {
@@ -8,10 +10,12 @@ pub struct MockAnkiServer {
 impl MockAnkiServer {
     pub async fn new() -> Self {
         let server = Server::new_async().await;
-        std::env::set_var("ANKI_CONNECT_URL", &server.url());
-        
         Self { server }
     }
+
+    pub fn adapter(&self) -> AnkiAdapter {
+        AnkiAdapter::new(self.server.url())
+    }
     
     pub fn mock_successful_connection(&mut self) -> Mock {
         self.server.mock("POST", "/")
@@ -52,6 +56,19 @@ impl MockAnkiServer {
             .create()
     }
     
+    pub fn mock_model_styling(&mut self, css: &str) -> Mock {
+        self.server.mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJsonString(json!({
+                "action": "modelStyling",
+                "version": 6
+            }).to_string()))
+            .with_body(json!({
+                "result": { "css": css },
+                "error": null
+            }).to_string())
+            .create()
+    }
+    
     pub fn mock_create_model_success(&mut self) -> Mock {
         self.server.mock("POST", "/")
             .match_body(mockito::Matcher::PartialJsonString(json!({
@@ -205,6 +222,7 @@ impl MockAnkiServer {
             connection,
             model_check,
             model_create,
+            model_styling: self.server.mock("POST", "/").create(), // Dummy mock, won't be called
             deck_create,
             card_additions,
         }
@@ -214,6 +232,7 @@ impl MockAnkiServer {
     pub fn setup_existing_model_workflow(&mut self, deck_name: &str, card_count: usize) -> WorkflowMocks {
         let connection = self.mock_successful_connection();
         let model_check = self.mock_model_exists(vec!["Basic", "Cloze", "Wordcraft"]); // Model exists
+        let model_styling = self.mock_model_styling(&NoteKind::Basic.note_type().styling()); // Model is current
         let deck_create = self.mock_create_deck_success(deck_name);
         let card_additions = self.mock_multiple_card_additions(deck_name, card_count);
         
@@ -221,6 +240,7 @@ impl MockAnkiServer {
             connection,
             model_check,
             model_create: self.server.mock("POST", "/").create(), // Dummy mock, won't be called
+            model_styling,
             deck_create,
             card_additions,
         }
@@ -231,6 +251,7 @@ pub struct WorkflowMocks {
     pub connection: Mock,
     pub model_check: Mock,
     pub model_create: Mock,
+    pub model_styling: Mock,
     pub deck_create: Mock,
     pub card_additions: Vec<Mock>,
 }
@@ -251,16 +272,9 @@ impl WorkflowMocks {
     }
 }
 
-impl Drop for MockAnkiServer {
-    fn drop(&mut self) {
-        std::env::remove_var("ANKI_CONNECT_URL");
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
-    use autoflashcard::anki_adapter::AnkiAdapter;
     use serial_test::serial;
     
     #[tokio::test]
@@ -269,7 +283,7 @@ mod tests {
         let mut mock_server = MockAnkiServer::new().await;
         let _connection_mock = mock_server.mock_successful_connection();
         
-        let adapter = AnkiAdapter::new().expect("Failed to create adapter");
+        let adapter = mock_server.adapter();
         let result = adapter.check_connection().await;
         
         assert!(result.is_ok());
@@ -281,7 +295,7 @@ mod tests {
         let mut mock_server = MockAnkiServer::new().await;
         let mocks = mock_server.setup_successful_workflow("TestDeck", 2);
         
-        let adapter = AnkiAdapter::new().expect("Failed to create adapter");
+        let adapter = mock_server.adapter();
         
         // Test the workflow
         adapter.check_connection().await.expect("Connection failed");
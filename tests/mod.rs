@@ -1,8 +1,26 @@
 // Test modules
 mod anki_adapter_tests;
-mod langchain_tests;
+mod apkg_reader_tests;
+mod apkg_tests;
+mod cache_tests;
+mod cli_tests;
+mod config_tests;
+mod fake_anki_tests;
+mod generation_tests;
+mod level_tests;
+mod note_type_tests;
+mod ollama_tests;
+mod openai_tests;
 mod prompt_tests;
+mod prompt_pack_tests;
 mod integration_tests;
+mod jobs_tests;
+mod json_extract_tests;
+mod rag_tests;
+mod recording_tests;
+mod review_tests;
+mod schema_tests;
+mod tts_tests;
 mod mock_server;
 
 // Re-export mock utilities for other test modules
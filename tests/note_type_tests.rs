@@ -0,0 +1,125 @@
+use autoflashcard::generation::{build_system_message, Flashcard, GenerationOptions};
+use autoflashcard::note_type::{
+    cloze_text, model_version, note_type, plain_word, NoteKind, NOTE_KINDS, WORDCRAFT_MODEL_VERSION,
+};
+use autoflashcard::schema::{flashcard_response_schema, flashcard_response_schema_for, validate};
+use serde_json::json;
+
+fn card() -> Flashcard {
+    Flashcard {
+        front: "家 (いえ) (ie)".to_string(),
+        back: "house".to_string(),
+        example: "私は家にいます。".to_string(),
+        example_translate: "I am at home.".to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_note_kind_round_trips_through_its_name() {
+    for kind in NOTE_KINDS {
+        assert_eq!(kind.name().parse::<NoteKind>().unwrap(), kind);
+        assert_eq!(kind.to_string(), kind.name());
+    }
+    assert_eq!("Reverse".parse::<NoteKind>().unwrap(), NoteKind::Bidirectional);
+    assert_eq!("".parse::<NoteKind>().unwrap(), NoteKind::Basic);
+
+    let err = "flip".parse::<NoteKind>().unwrap_err();
+    assert!(err.contains("Unknown note type 'flip'"));
+}
+
+#[test]
+fn test_every_note_type_has_its_own_model() {
+    let names: Vec<&str> = NOTE_KINDS.iter().map(|kind| kind.note_type().model_name).collect();
+    assert_eq!(names, vec!["Wordcraft", "Wordcraft Bidirectional", "Wordcraft Type Answer", "Wordcraft Cloze"]);
+}
+
+#[test]
+fn test_bidirectional_model_has_a_production_card() {
+    let model = note_type(NoteKind::Bidirectional).create_model();
+    let templates: Vec<&str> = model.card_templates.iter().map(|template| template.name.as_deref().unwrap()).collect();
+
+    assert_eq!(templates, vec!["Recognition", "Production"]);
+    assert!(model.card_templates[1].front.contains("{{Back}}"));
+    assert!(!model.card_templates[1].front.contains("{{Front}}"));
+    assert_eq!(model.is_cloze, None);
+}
+
+#[test]
+fn test_type_answer_falls_back_to_the_plain_word() {
+    let note_type = note_type(NoteKind::TypeAnswer);
+    assert!(note_type.templates[0].front.contains("{{type:Answer}}"));
+
+    assert_eq!(note_type.field_values(&card())[2], "家");
+
+    let typed = Flashcard { answer: Some("いえ".to_string()), ..card() };
+    assert_eq!(note_type.field_values(&typed)[2], "いえ");
+}
+
+#[test]
+fn test_cloze_note_uses_the_generated_cloze() {
+    let note_type = note_type(NoteKind::Cloze);
+    let clozed = Flashcard { cloze: Some("私は{{c1::家}}にいます。".to_string()), ..card() };
+
+    let note = note_type.note("Japanese", &clozed);
+    assert_eq!(note.model_name, "Wordcraft Cloze");
+    assert_eq!(note.fields["Text"], "私は{{c1::家}}にいます。");
+    assert_eq!(note.fields["Front"], "家 (いえ) (ie)");
+    assert_eq!(note_type.create_model().is_cloze, Some(true));
+}
+
+#[test]
+fn test_cloze_text_fallbacks() {
+    assert_eq!(cloze_text(&card()), "私は{{c1::家}}にいます。");
+
+    let missing = Flashcard { example: "I live here.".to_string(), ..card() };
+    assert_eq!(cloze_text(&missing), "{{c1::家}}<br>I live here.");
+
+    let no_deletion = Flashcard { cloze: Some("私は家にいます。".to_string()), ..card() };
+    assert_eq!(cloze_text(&no_deletion), "私は{{c1::家}}にいます。");
+}
+
+#[test]
+fn test_plain_word_drops_readings() {
+    assert_eq!(plain_word("家 (いえ) (ie)"), "家");
+    assert_eq!(plain_word("el perro"), "el perro");
+}
+
+#[test]
+fn test_schema_asks_for_the_fields_of_the_note_type() {
+    assert_eq!(flashcard_response_schema_for(NoteKind::Basic), flashcard_response_schema());
+    assert_eq!(flashcard_response_schema_for(NoteKind::Bidirectional), flashcard_response_schema());
+
+    let schema = flashcard_response_schema_for(NoteKind::Cloze);
+    assert_eq!(
+        schema["properties"]["cards"]["items"]["required"],
+        json!(["back", "cloze", "example", "example_translate", "front"])
+    );
+
+    let without_cloze = json!({
+        "deck_name": "Japanese",
+        "cards": [{ "front": "家", "back": "house", "example": "家", "example_translate": "house" }]
+    });
+    let errors = validate(&without_cloze, &schema).unwrap_err();
+    assert_eq!(errors, vec!["$.cards[0]: missing required field 'cloze'"]);
+}
+
+#[test]
+fn test_system_message_asks_for_extra_fields() {
+    let basic = build_system_message(&GenerationOptions::default());
+    let type_answer = build_system_message(&GenerationOptions { note_kind: NoteKind::TypeAnswer, ..Default::default() });
+
+    assert!(!basic.contains("\"answer\""));
+    assert!(type_answer.contains("\"answer\""));
+    assert!(type_answer.starts_with(&basic));
+}
+
+#[test]
+fn test_models_carry_their_version() {
+    for kind in NOTE_KINDS {
+        let model = kind.note_type().create_model();
+        assert_eq!(model_version(&model.css), WORDCRAFT_MODEL_VERSION);
+        assert!(model.css.contains(".card {"));
+    }
+    assert_eq!(model_version(".card { color: black; }"), 1);
+}
@@ -0,0 +1,146 @@
+use autoflashcard::generator::{ChatMessage, CompletionRequest, FlashcardGenerator};
+use autoflashcard::generation::{generate_flashcards, GenerationOptions};
+use autoflashcard::ollama::{OllamaConfig, OllamaGenerator};
+use autoflashcard::schema::flashcard_response_schema;
+use serde_json::json;
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn local_generator(server: &MockServer) -> OllamaGenerator {
+    OllamaGenerator::new(OllamaConfig {
+        base_url: server.uri(),
+        model: "gemma2".to_string(),
+    })
+}
+
+fn chat_reply(content: &str) -> serde_json::Value {
+    json!({
+        "model": "gemma2",
+        "created_at": "2024-10-01T12:00:00Z",
+        "message": { "role": "assistant", "content": content },
+        "done": true
+    })
+}
+
+#[tokio::test]
+async fn test_ollama_sends_schema_as_format() {
+    let server = MockServer::start().await;
+    let schema = flashcard_response_schema();
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .and(body_partial_json(json!({
+            "model": "gemma2",
+            "stream": false,
+            "messages": [{ "role": "user", "content": "ping" }],
+            "format": schema
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(chat_reply("pong")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let request = CompletionRequest {
+        messages: vec![ChatMessage::user("ping")],
+        response_schema: Some(schema.clone()),
+    };
+    let reply = local_generator(&server).complete(&request).await.expect("completion failed");
+
+    assert_eq!(reply, "pong");
+}
+
+#[tokio::test]
+async fn test_ollama_reports_http_errors() {
+    let server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(404).set_body_string("model 'gemma2' not found"))
+        .mount(&server)
+        .await;
+
+    let message = local_generator(&server)
+        .complete(&CompletionRequest::new(vec![ChatMessage::user("ping")]))
+        .await
+        .unwrap_err()
+        .to_string();
+
+    assert!(message.contains("404"));
+    assert!(message.contains("not found"));
+}
+
+#[tokio::test]
+async fn test_generate_flashcards_through_ollama() {
+    let server = MockServer::start().await;
+    let deck = json!({
+        "deck_name": "Food in Japanese",
+        "cards": [{
+            "front": "寿司 (すし)",
+            "back": "sushi",
+            "example": "寿司が好きです。",
+            "example_translate": "I like sushi."
+        }]
+    });
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(chat_reply(&deck.to_string())))
+        .mount(&server)
+        .await;
+
+    let response = generate_flashcards(&local_generator(&server), "Topic: Food", &GenerationOptions::default())
+        .await
+        .expect("generation failed")
+        .response;
+
+    assert_eq!(response.deck_name, "Food in Japanese");
+    assert_eq!(response.cards[0].back, "sushi");
+}
+
+#[tokio::test]
+async fn test_ollama_streams_ndjson_chunks() {
+    let server = MockServer::start().await;
+    let lines: Vec<String> = ["{\"deck", "_name\": \"Food\"", ", \"cards\": []}"]
+        .iter()
+        .map(|content| json!({ "model": "gemma2", "message": { "role": "assistant", "content": content }, "done": false }).to_string())
+        .chain(std::iter::once(json!({ "model": "gemma2", "message": { "role": "assistant", "content": "" }, "done": true }).to_string()))
+        .collect();
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .and(body_partial_json(json!({ "stream": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_string(lines.join("\n")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut chunks: Vec<String> = Vec::new();
+    let reply = local_generator(&server)
+        .complete_stream(&CompletionRequest::new(vec![ChatMessage::user("ping")]), &mut |chunk: &str| {
+            chunks.push(chunk.to_string())
+        })
+        .await
+        .expect("completion failed");
+
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(reply, "{\"deck_name\": \"Food\", \"cards\": []}");
+}
+
+#[tokio::test]
+async fn test_ollama_stream_reports_errors_in_body() {
+    let server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{\"error\": \"out of memory\"}\n"))
+        .mount(&server)
+        .await;
+
+    let message = local_generator(&server)
+        .complete_stream(&CompletionRequest::new(vec![ChatMessage::user("ping")]), &mut |_: &str| {})
+        .await
+        .unwrap_err()
+        .to_string();
+
+    assert!(message.contains("out of memory"));
+}
@@ -0,0 +1,252 @@
+use autoflashcard::generator::{ChatMessage, CompletionRequest, FlashcardGenerator};
+use autoflashcard::generation::{generate_flashcards, GenerationOptions};
+use autoflashcard::openai::{parse_headers, OpenAIConfig, OpenAIGenerator, DEFAULT_OPENAI_BASE_URL};
+use autoflashcard::schema::{flashcard_response_schema, SCHEMA_NAME};
+use serde_json::json;
+use wiremock::matchers::{body_partial_json, header, method, path};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+fn local_config(server: &MockServer) -> OpenAIConfig {
+    OpenAIConfig {
+        base_url: format!("{}/v1", server.uri()),
+        model: "qwen2.5-7b-instruct".to_string(),
+        api_key: None,
+        headers: vec![],
+        structured_output: true,
+    }
+}
+
+fn completion(content: &str) -> serde_json::Value {
+    json!({
+        "id": "chatcmpl-1",
+        "object": "chat.completion",
+        "created": 1700000000,
+        "model": "qwen2.5-7b-instruct",
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": content },
+            "finish_reason": "stop"
+        }]
+    })
+}
+
+// Matches requests that carry no Authorization header
+struct NoAuthorization;
+
+impl wiremock::Match for NoAuthorization {
+    fn matches(&self, request: &Request) -> bool {
+        !request.headers.iter().any(|(name, _)| name.as_str().eq_ignore_ascii_case("authorization"))
+    }
+}
+
+#[tokio::test]
+async fn test_openai_compatible_endpoint_without_key() {
+    let server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(NoAuthorization)
+        .and(body_partial_json(json!({
+            "model": "qwen2.5-7b-instruct",
+            "messages": [
+                { "role": "system", "content": "Be brief." },
+                { "role": "user", "content": "Hi" }
+            ]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(completion("Hello!")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let generator = OpenAIGenerator::new(local_config(&server)).expect("client");
+    let reply = generator
+        .complete(&CompletionRequest::new(vec![ChatMessage::system("Be brief."), ChatMessage::user("Hi")]))
+        .await
+        .expect("completion failed");
+
+    assert_eq!(reply, "Hello!");
+    assert_eq!(generator.model(), "qwen2.5-7b-instruct");
+}
+
+#[tokio::test]
+async fn test_openai_compatible_sends_key_and_custom_headers() {
+    let server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(header("authorization", "Bearer sk-local"))
+        .and(header("x-team", "language-lab"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(completion("ok")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let config = OpenAIConfig {
+        api_key: Some("sk-local".to_string()),
+        headers: vec![("X-Team".to_string(), "language-lab".to_string())],
+        ..local_config(&server)
+    };
+    let generator = OpenAIGenerator::new(config).expect("client");
+
+    assert_eq!(generator.complete(&CompletionRequest::new(vec![ChatMessage::user("ping")])).await.unwrap(), "ok");
+}
+
+#[tokio::test]
+async fn test_openai_compatible_reports_http_errors() {
+    let server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(503).set_body_string("model is still loading"))
+        .mount(&server)
+        .await;
+
+    let generator = OpenAIGenerator::new(local_config(&server)).expect("client");
+    let message = generator.complete(&CompletionRequest::new(vec![ChatMessage::user("ping")])).await.unwrap_err().to_string();
+
+    assert!(message.contains("503"));
+    assert!(message.contains("model is still loading"));
+}
+
+#[tokio::test]
+async fn test_generate_flashcards_through_openai_compatible_server() {
+    let server = MockServer::start().await;
+    let deck = json!({
+        "deck_name": "Colors in Spanish",
+        "cards": [{
+            "front": "rojo",
+            "back": "red",
+            "example": "El coche es rojo.",
+            "example_translate": "The car is red."
+        }]
+    });
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(completion(&format!("Here you go:\n{}", deck))))
+        .mount(&server)
+        .await;
+
+    let generator = OpenAIGenerator::new(local_config(&server)).expect("client");
+    let response = generate_flashcards(&generator, "Topic: Colors", &GenerationOptions::default())
+        .await
+        .expect("generation failed")
+        .response;
+
+    assert_eq!(response.deck_name, "Colors in Spanish");
+    assert_eq!(response.cards[0].front, "rojo");
+}
+
+// Matches requests whose JSON body has no `response_format`
+struct NoResponseFormat;
+
+impl wiremock::Match for NoResponseFormat {
+    fn matches(&self, request: &Request) -> bool {
+        request.body_json::<serde_json::Value>()
+            .map(|body| body.get("response_format").is_none())
+            .unwrap_or(false)
+    }
+}
+
+#[tokio::test]
+async fn test_openai_sends_response_schema() {
+    let server = MockServer::start().await;
+    let schema = flashcard_response_schema();
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(json!({
+            "response_format": {
+                "type": "json_schema",
+                "json_schema": { "name": SCHEMA_NAME, "strict": true, "schema": schema }
+            }
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(completion("{}")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let generator = OpenAIGenerator::new(local_config(&server)).expect("client");
+    let request = CompletionRequest {
+        messages: vec![ChatMessage::user("ping")],
+        response_schema: Some(schema.clone()),
+    };
+    assert_eq!(generator.complete(&request).await.unwrap(), "{}");
+}
+
+#[tokio::test]
+async fn test_openai_structured_output_can_be_disabled() {
+    let server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(NoResponseFormat)
+        .respond_with(ResponseTemplate::new(200).set_body_json(completion("ok")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let config = OpenAIConfig {
+        structured_output: false,
+        ..local_config(&server)
+    };
+    let generator = OpenAIGenerator::new(config).expect("client");
+    let request = CompletionRequest {
+        messages: vec![ChatMessage::user("ping")],
+        response_schema: Some(flashcard_response_schema()),
+    };
+    assert_eq!(generator.complete(&request).await.unwrap(), "ok");
+}
+
+#[tokio::test]
+async fn test_openai_streams_server_sent_events() {
+    let server = MockServer::start().await;
+    let events: String = ["Hel", "lo", "!"]
+        .iter()
+        .map(|content| format!("data: {}\n\n", json!({ "choices": [{ "index": 0, "delta": { "content": content } }] })))
+        .chain(std::iter::once("data: [DONE]\n\n".to_string()))
+        .collect();
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(json!({ "stream": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(events, "text/event-stream"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let generator = OpenAIGenerator::new(local_config(&server)).expect("client");
+    let mut chunks: Vec<String> = Vec::new();
+    let reply = generator
+        .complete_stream(&CompletionRequest::new(vec![ChatMessage::user("Hi")]), &mut |chunk: &str| {
+            chunks.push(chunk.to_string())
+        })
+        .await
+        .expect("completion failed");
+
+    assert_eq!(chunks, vec!["Hel", "lo", "!"]);
+    assert_eq!(reply, "Hello!");
+}
+
+#[test]
+fn test_parse_headers() {
+    let headers = parse_headers("X-Team: language-lab; X-Trace:  on ;").unwrap();
+    assert_eq!(headers, vec![
+        ("X-Team".to_string(), "language-lab".to_string()),
+        ("X-Trace".to_string(), "on".to_string()),
+    ]);
+    assert!(parse_headers("").unwrap().is_empty());
+    assert!(parse_headers("missing-colon").is_err());
+}
+
+#[test]
+fn test_default_endpoint_detection() {
+    let config = OpenAIConfig {
+        base_url: format!("{}/", DEFAULT_OPENAI_BASE_URL),
+        model: "gpt-4o-mini".to_string(),
+        api_key: None,
+        headers: vec![],
+        structured_output: true,
+    };
+    assert!(config.is_default_endpoint());
+}
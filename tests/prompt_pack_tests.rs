@@ -0,0 +1,106 @@
+use autoflashcard::error::WordcraftError;
+use autoflashcard::generation::{build_system_message, FlashcardResponse, GenerationOptions};
+use autoflashcard::prompt_pack::{PromptConfig, PromptLibrary, DEFAULT_PACK};
+use tempfile::TempDir;
+
+#[test]
+fn test_packs_are_picked_by_name_or_alias() {
+    let library = PromptLibrary::builtin();
+
+    assert_eq!(library.pack("Japanese").language, "japanese");
+    assert_eq!(library.pack(" ja ").language, "japanese");
+    assert_eq!(library.pack("日本語").language, "japanese");
+    assert_eq!(library.pack("Mandarin").language, "chinese");
+    assert_eq!(library.pack("Spanish").language, "spanish");
+    assert_eq!(library.pack("Klingon").language, DEFAULT_PACK);
+}
+
+#[test]
+fn test_templates_only_carry_their_own_language_notes() {
+    let library = PromptLibrary::builtin();
+
+    let japanese = library.template("Japanese");
+    assert!(japanese.contains("Hiragana and Romaji"));
+    assert!(japanese.contains("家 (いえ) (ie)"));
+
+    let spanish = library.template("Spanish");
+    assert!(!spanish.contains("Kanji"));
+    assert!(spanish.contains("la estación"));
+    assert!(!spanish.contains("{notes}") && !spanish.contains("{example}"));
+    // Filled in later, per request
+    assert!(spanish.contains("{card_count}") && spanish.contains("{level}"));
+}
+
+#[test]
+fn test_builtin_examples_are_valid_decks() {
+    for pack in PromptLibrary::builtin().packs.values() {
+        let deck: FlashcardResponse = serde_json::from_str(&pack.example)
+            .unwrap_or_else(|err| panic!("{}: {}", pack.language, err));
+        assert_eq!(deck.cards.len(), 1, "{}", pack.language);
+    }
+}
+
+#[test]
+fn test_system_message_follows_the_target_language() {
+    let options = GenerationOptions {
+        system_template: PromptLibrary::builtin().template("Korean"),
+        ..Default::default()
+    };
+    let message = build_system_message(&options);
+    assert!(message.contains("Revised Romanization"));
+    assert!(message.contains("Result should contain at least 15 flashcards."));
+    assert!(!message.contains("Kanji"));
+
+    let default = build_system_message(&GenerationOptions::default());
+    assert!(default.contains("romanization"));
+    assert!(!default.contains("Kanji"));
+}
+
+#[test]
+fn test_user_files_override_and_add_packs() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join("base.txt"), "Make {card_count} cards.\n{notes}\n{example}").unwrap();
+    std::fs::write(dir.path().join("japanese.toml"), "notes = \"Only use Hiragana.\"").unwrap();
+    std::fs::write(dir.path().join("Thai.toml"), "aliases = [\"th\"]\nnotes = \"Add RTGS romanization.\"").unwrap();
+    std::fs::write(dir.path().join("README.md"), "not a template").unwrap();
+
+    let library = PromptLibrary::from_config(&PromptConfig { dir: Some(dir.path().display().to_string()) }).unwrap();
+    assert_eq!(library.overrides.len(), 3);
+
+    let japanese = library.template("Japanese");
+    assert!(japanese.starts_with("Make {card_count} cards.\nOnly use Hiragana.\n"));
+    // Fields the override leaves out come from the built-in pack
+    assert!(japanese.contains("家 (いえ) (ie)"));
+
+    let thai = library.pack("th");
+    assert_eq!(thai.language, "thai");
+    assert_eq!(thai.example, PromptLibrary::builtin().pack(DEFAULT_PACK).example);
+    assert!(library.template("Thai").contains("Add RTGS romanization."));
+}
+
+#[test]
+fn test_new_packs_use_the_users_default_example() {
+    let dir = TempDir::new().unwrap();
+    let example = r#"{"deck_name": "Colors in Catalan", "cards": []}"#;
+    std::fs::write(dir.path().join("default.toml"), format!("example = '{}'", example)).unwrap();
+    // Sorts before default.toml
+    std::fs::write(dir.path().join("catalan.toml"), "aliases = [\"ca\"]").unwrap();
+
+    let library = PromptLibrary::load(dir.path()).unwrap();
+    assert_eq!(library.pack("ca").example, example);
+    assert_eq!(library.pack(DEFAULT_PACK).example, example);
+}
+
+#[test]
+fn test_missing_and_broken_prompt_directories() {
+    let dir = TempDir::new().unwrap();
+    let missing = PromptLibrary::load(&dir.path().join("missing")).unwrap();
+    assert!(missing.overrides.is_empty());
+    assert_eq!(missing.base, PromptLibrary::builtin().base);
+
+    std::fs::write(dir.path().join("spanish.toml"), "notas = \"typo\"").unwrap();
+    let err = PromptLibrary::load(dir.path()).unwrap_err();
+    assert!(matches!(err, WordcraftError::Config(_)));
+    assert!(err.to_string().contains("Invalid prompt pack"));
+    assert!(err.to_string().contains("spanish.toml"));
+}
@@ -1,5 +1,6 @@
-use autoflashcard::prompt::FlashcardSettings;
-use std::io::Cursor;
+use autoflashcard::level::ProficiencyLevel;
+use autoflashcard::note_type::NoteKind;
+use autoflashcard::prompt::{FlashcardSettings, SettingsOverrides};
 
 #[test]
 fn test_flashcard_settings_struct() {
@@ -8,6 +9,9 @@ fn test_flashcard_settings_struct() {
         target_language: "Spanish".to_string(),
         topic: "Colors".to_string(),
         deck_name: Some("Spanish Colors".to_string()),
+        note_kind: NoteKind::Cloze,
+        card_count: Some(20),
+        level: None,
     };
     
     assert_eq!(settings.native_language, "English");
@@ -23,6 +27,9 @@ fn test_flashcard_settings_with_no_deck() {
         target_language: "French".to_string(),
         topic: "Animals".to_string(),
         deck_name: None,
+        note_kind: NoteKind::default(),
+        card_count: None,
+        level: None,
     };
     
     assert_eq!(settings.native_language, "English");
@@ -36,9 +43,6 @@ fn test_flashcard_settings_with_no_deck() {
 
 #[cfg(test)]
 mod mock_tests {
-    use super::*;
-    use std::io::Cursor;
-    
     // These tests demonstrate what we would test if the module was refactored
     // to accept input/output streams as parameters
     
@@ -140,4 +144,42 @@ mod refactoring_example {
         assert_eq!(result, "Spanish");
         assert_eq!(String::from_utf8(writer).unwrap(), "Enter language: ");
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_settings_from_flags_use_defaults() {
+    let given = SettingsOverrides {
+        topic: Some("Colors".to_string()),
+        note_kind: Some(NoteKind::TypeAnswer),
+        ..Default::default()
+    };
+
+    let settings = FlashcardSettings::resolve(given, false).unwrap();
+    assert_eq!(settings.native_language, "English");
+    assert_eq!(settings.target_language, "Japanese");
+    assert_eq!(settings.topic, "Colors");
+    assert!(settings.deck_name.is_none());
+    assert_eq!(settings.note_kind, NoteKind::TypeAnswer);
+    assert_eq!(settings.card_count, None);
+}
+
+#[test]
+fn test_settings_keep_count_and_level() {
+    let given = SettingsOverrides {
+        topic: Some("Food".to_string()),
+        card_count: Some(8),
+        level: Some(ProficiencyLevel::Jlpt(4)),
+        ..Default::default()
+    };
+    let settings = FlashcardSettings::resolve(given, false).unwrap();
+    assert_eq!(settings.card_count, Some(8));
+    assert_eq!(settings.level, Some(ProficiencyLevel::Jlpt(4)));
+
+    let none = SettingsOverrides { topic: Some("Food".to_string()), card_count: Some(0), ..Default::default() };
+    assert!(FlashcardSettings::resolve(none, false).unwrap_err().contains("at least 1"));
+}
+
+#[test]
+fn test_settings_without_topic_need_a_terminal() {
+    let err = FlashcardSettings::resolve(SettingsOverrides::default(), false).unwrap_err();
+    assert!(err.contains("--topic"));
+}
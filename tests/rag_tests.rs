@@ -0,0 +1,186 @@
+use autoflashcard::anki_adapter::AnkiAdapter;
+use autoflashcard::rag::{
+    build_index, classify, default_index_path, SelectionStrategy, VocabularyEntry, VocabularyIndex, WordStatus,
+};
+use serde_json::json;
+use serial_test::serial;
+
+fn card_info(card_id: i64, front: &str, interval: i64, factor: i64, reps: i64, lapses: i64) -> serde_json::Value {
+    json!({
+        "cardId": card_id,
+        "note": card_id * 10,
+        "deckName": "Japanese",
+        "modelName": "Wordcraft",
+        "fields": { "Front": { "value": front, "order": 0 } },
+        "interval": interval,
+        "factor": factor,
+        "type": if reps == 0 { 0 } else { 2 },
+        "queue": 0,
+        "due": 0,
+        "reps": reps,
+        "lapses": lapses
+    })
+}
+
+fn entry(word: &str, status: WordStatus, interval: i64, lapses: i64) -> VocabularyEntry {
+    VocabularyEntry {
+        word: word.to_string(),
+        deck: "Japanese".to_string(),
+        status,
+        interval,
+        ease_factor: 2500,
+        reps: 5,
+        lapses,
+        again_count: 0,
+        review_count: 5,
+        last_review: None,
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_build_index_classifies_cards() {
+    let mut server = mockito::Server::new_async().await;
+    let url = server.url();
+
+    let _find = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "findCards",
+            "params": { "query": "note:Wordcraft" }
+        }).to_string()))
+        .with_body(json!({ "result": [1, 2, 3, 4], "error": null }).to_string())
+        .create();
+
+    let _info = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "cardsInfo"
+        }).to_string()))
+        .with_body(json!({
+            "result": [
+                card_info(1, "家 (いえ) (ie)", 45, 2600, 8, 0),
+                card_info(2, "駅 (えき) (eki)", 3, 1700, 9, 4),
+                card_info(3, "<b>空港</b>", 0, 0, 0, 0),
+                card_info(4, "学校", 4, 2500, 3, 0)
+            ],
+            "error": null
+        }).to_string())
+        .create();
+
+    let _reviews = server.mock("POST", "/")
+        .match_body(mockito::Matcher::PartialJsonString(json!({
+            "action": "getReviewsOfCards"
+        }).to_string()))
+        .with_body(json!({
+            "result": {
+                "1": [
+                    { "id": 1700000000000i64, "usn": -1, "ease": 3, "ivl": 20, "lastIvl": 8, "factor": 2600, "time": 4000, "type": 1 },
+                    { "id": 1710000000000i64, "usn": -1, "ease": 3, "ivl": 45, "lastIvl": 20, "factor": 2600, "time": 3000, "type": 1 }
+                ],
+                "2": [
+                    { "id": 1700000000000i64, "usn": -1, "ease": 1, "ivl": 1, "lastIvl": 3, "factor": 1700, "time": 9000, "type": 1 }
+                ],
+                "4": []
+            },
+            "error": null
+        }).to_string())
+        .create();
+
+    let adapter = AnkiAdapter::new(&url);
+    let index = build_index(&adapter, "note:Wordcraft").await.expect("Index build failed");
+
+    let status = |word: &str| index.entries.iter().find(|entry| entry.word == word).map(|entry| entry.status);
+    assert_eq!(index.entries.len(), 4);
+    assert_eq!(status("家 (いえ) (ie)"), Some(WordStatus::Mastered));
+    assert_eq!(status("駅 (えき) (eki)"), Some(WordStatus::Struggling));
+    assert_eq!(status("空港"), Some(WordStatus::Unseen));
+    assert_eq!(status("学校"), Some(WordStatus::Learning));
+
+    let home = index.entries.iter().find(|entry| entry.word.starts_with('家')).unwrap();
+    assert_eq!(home.review_count, 2);
+    assert_eq!(home.last_review, Some(1710000000000));
+}
+
+#[test]
+fn test_classify_uses_again_ratio() {
+    let mut word = entry("猫", WordStatus::Unseen, 30, 0);
+    word.review_count = 10;
+    word.again_count = 4;
+    assert_eq!(classify(&word), WordStatus::Struggling);
+
+    word.again_count = 1;
+    assert_eq!(classify(&word), WordStatus::Mastered);
+
+    word.reps = 0;
+    word.review_count = 0;
+    assert_eq!(classify(&word), WordStatus::Unseen);
+}
+
+#[test]
+fn test_selection_strategy_limits_and_orders() {
+    let index = VocabularyIndex {
+        built_at: 0,
+        query: "note:Wordcraft".to_string(),
+        entries: vec![
+            entry("犬", WordStatus::Mastered, 30, 0),
+            entry("猫", WordStatus::Mastered, 90, 0),
+            entry("鳥", WordStatus::Mastered, 60, 0),
+            entry("魚", WordStatus::Struggling, 2, 1),
+            entry("馬", WordStatus::Struggling, 2, 5),
+            entry("牛", WordStatus::Unseen, 0, 0),
+        ],
+    };
+
+    let strategy = SelectionStrategy {
+        max_mastered: 2,
+        ..Default::default()
+    };
+    let selection = strategy.select(&index);
+    assert_eq!(selection.mastered, vec!["猫", "鳥"]);
+    assert_eq!(selection.struggling, vec!["馬", "魚"]);
+    assert_eq!(selection.unseen, vec!["牛"]);
+    assert!(selection.learning.is_empty());
+
+    let summary = strategy.summarize(&index).expect("summary expected");
+    assert!(summary.contains("6 words: 3 mastered, 0 learning, 2 struggling, 1 not studied yet"));
+    assert!(summary.contains("猫, 鳥"));
+    assert!(!summary.contains("犬"));
+}
+
+#[test]
+fn test_summarize_empty_index() {
+    assert!(SelectionStrategy::default().summarize(&VocabularyIndex::default()).is_none());
+}
+
+#[test]
+fn test_index_store_roundtrip() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let path = dir.path().join("nested").join("vocabulary.json");
+
+    let index = VocabularyIndex {
+        built_at: 1_700_000_000,
+        query: "deck:\"Japanese\"".to_string(),
+        entries: vec![entry("家", WordStatus::Mastered, 40, 0)],
+    };
+    index.save(&path).expect("save failed");
+
+    let loaded = VocabularyIndex::load(&path).expect("load failed");
+    assert_eq!(loaded, index);
+    assert_eq!(loaded.words(), vec!["家".to_string()]);
+}
+
+#[test]
+fn test_index_is_kept_per_query() {
+    assert_ne!(default_index_path("deck:\"Japanese\""), default_index_path("deck:\"Spanish\""));
+
+    let dir = tempfile::tempdir().expect("tempdir");
+    let path = dir.path().join("vocabulary.json");
+    let index = VocabularyIndex {
+        built_at: 1_700_000_000,
+        query: "deck:\"Japanese\"".to_string(),
+        entries: vec![entry("家", WordStatus::Mastered, 40, 0)],
+    };
+    index.save(&path).expect("save failed");
+
+    assert_eq!(VocabularyIndex::load_for_query(&path, "deck:\"Japanese\""), Some(index));
+    assert_eq!(VocabularyIndex::load_for_query(&path, "deck:\"Spanish\""), None);
+}
@@ -0,0 +1,152 @@
+use autoflashcard::config::Config;
+use autoflashcard::error::WordcraftError;
+use autoflashcard::generator::{build_generator, CompletionRequest, ChatMessage, FakeGenerator, FlashcardGenerator};
+use autoflashcard::generation::{generate_flashcards, generate_flashcards_streaming, GenerationOptions};
+use autoflashcard::recording::{fixture_key, fixture_path, Fixture, FixturesConfig, RecordingGenerator, ReplayGenerator};
+use tempfile::TempDir;
+
+const VALID_DECK: &str = r#"{"deck_name": "Colors in Spanish", "cards": [
+    {"front": "rojo", "back": "red", "example": "Es rojo.", "example_translate": "It is red."}
+]}"#;
+
+fn read_fixtures(dir: &TempDir) -> Vec<Fixture> {
+    std::fs::read_dir(dir.path()).unwrap()
+        .map(|entry| serde_json::from_str(&std::fs::read_to_string(entry.unwrap().path()).unwrap()).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_recorded_generation_replays_without_the_model() {
+    let dir = TempDir::new().unwrap();
+    let recorder = RecordingGenerator::new(Box::new(FakeGenerator::new()), dir.path());
+    let options = GenerationOptions::default();
+
+    let recorded = generate_flashcards(&recorder, "Topic: Colors\nTarget Language: Spanish\n", &options).await.unwrap();
+
+    let fixtures = read_fixtures(&dir);
+    assert_eq!(fixtures.len(), 1);
+    assert_eq!((fixtures[0].engine.as_str(), fixtures[0].model.as_str()), ("fake", "fake"));
+    assert!(fixtures[0].request.messages.iter().any(|message| message.content.contains("Topic: Colors")));
+
+    let replay = ReplayGenerator::load(dir.path()).unwrap();
+    assert_eq!(replay.len(), 1);
+    let replayed = generate_flashcards(&replay, "Topic: Colors\nTarget Language: Spanish\n", &options).await.unwrap();
+    assert_eq!(serde_json::to_value(&replayed.response).unwrap(), serde_json::to_value(&recorded.response).unwrap());
+}
+
+#[tokio::test]
+async fn test_repair_turns_are_recorded_and_replayed() {
+    let dir = TempDir::new().unwrap();
+    let model = FakeGenerator::with_replies(vec!["Sure! Here are your cards: [oops", VALID_DECK]);
+    let recorder = RecordingGenerator::new(Box::new(model), dir.path());
+
+    let mut streamed = String::new();
+    let recorded = generate_flashcards_streaming(&recorder, "Topic: Colors", &GenerationOptions::default(), |card| {
+        streamed.push_str(&card.front);
+    }).await.unwrap();
+    assert_eq!(recorded.attempts.len(), 2);
+    assert_eq!(read_fixtures(&dir).len(), 2);
+
+    let replay = ReplayGenerator::load(dir.path()).unwrap();
+    let replayed = generate_flashcards(&replay, "Topic: Colors", &GenerationOptions::default()).await.unwrap();
+    assert_eq!(replayed.attempts.len(), 2);
+    assert_eq!(replayed.response.cards[0].front, "rojo");
+}
+
+#[tokio::test]
+async fn test_a_fixture_that_cannot_be_written_keeps_the_completion() {
+    let dir = TempDir::new().unwrap();
+    // A file where the fixture directory should be
+    let blocked = dir.path().join("fixtures");
+    std::fs::write(&blocked, "").unwrap();
+    let recorder = RecordingGenerator::new(Box::new(FakeGenerator::with_replies(vec![VALID_DECK])), &blocked);
+
+    let request = CompletionRequest::new(vec![ChatMessage::user("Topic: Colors")]);
+    assert_eq!(recorder.complete(&request).await.unwrap(), VALID_DECK);
+}
+
+#[tokio::test]
+async fn test_replay_reports_a_missing_fixture() {
+    let dir = TempDir::new().unwrap();
+    let replay = ReplayGenerator::load(dir.path()).unwrap();
+    assert!(replay.is_empty());
+
+    let request = CompletionRequest::new(vec![ChatMessage::user("Topic: Food")]);
+    let err = replay.complete(&request).await.unwrap_err();
+
+    assert!(matches!(err, WordcraftError::LlmTransport { .. }));
+    assert!(err.to_string().contains(&format!("{}.json is missing", fixture_key(&request))));
+}
+
+#[tokio::test]
+async fn test_fixtures_are_found_by_request_not_file_name() {
+    let dir = TempDir::new().unwrap();
+    let request = CompletionRequest::new(vec![ChatMessage::user("Topic: Food")]);
+    let fixture = Fixture {
+        engine: "ollama".to_string(),
+        model: "gemma2".to_string(),
+        request: request.clone(),
+        completion: VALID_DECK.to_string(),
+    };
+    std::fs::write(dir.path().join("bug-report-42.json"), serde_json::to_string(&fixture).unwrap()).unwrap();
+    std::fs::write(dir.path().join("notes.txt"), "not a fixture").unwrap();
+
+    let replay = ReplayGenerator::load(dir.path()).unwrap();
+    assert_eq!(replay.complete(&request).await.unwrap(), VALID_DECK);
+    assert_eq!(fixture_path(dir.path(), &request), dir.path().join(format!("{}.json", fixture_key(&request))));
+
+    // A different schema is a different request
+    let structured = CompletionRequest { response_schema: Some(serde_json::json!({"type": "object"})), ..request };
+    assert_ne!(fixture_key(&structured), fixture_key(&CompletionRequest::new(vec![ChatMessage::user("Topic: Food")])));
+}
+
+#[tokio::test]
+async fn test_replay_ignores_the_system_prompt() {
+    let dir = TempDir::new().unwrap();
+    let recorder = RecordingGenerator::new(Box::new(FakeGenerator::with_replies(vec![VALID_DECK])), dir.path());
+    let recorded_on = |known: &str| CompletionRequest::new(vec![
+        ChatMessage::system(format!("Skip these words: {}", known)),
+        ChatMessage::user("Topic: Colors"),
+    ]);
+    recorder.complete(&recorded_on("azul, verde")).await.unwrap();
+
+    // Another machine knows other words, but asks for the same deck
+    let replay = ReplayGenerator::load(dir.path()).unwrap();
+    assert_eq!(replay.complete(&recorded_on("negro")).await.unwrap(), VALID_DECK);
+
+    // A repair turn is a different request
+    let mut repair = recorded_on("negro");
+    repair.messages.push(ChatMessage::assistant("[oops"));
+    repair.messages.push(ChatMessage::user("Reply with valid JSON."));
+    assert_ne!(fixture_key(&repair), fixture_key(&recorded_on("negro")));
+}
+
+#[test]
+fn test_broken_fixture_directory_is_a_config_error() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join("broken.json"), "{").unwrap();
+    assert!(matches!(ReplayGenerator::load(dir.path()), Err(WordcraftError::Config(_))));
+
+    let missing = dir.path().join("missing");
+    let err = ReplayGenerator::load(&missing).err().unwrap();
+    assert!(err.to_string().starts_with("Could not read fixtures from"));
+}
+
+#[test]
+fn test_build_generator_wraps_the_engine() {
+    let dir = TempDir::new().unwrap();
+    let dir_name = dir.path().display().to_string();
+    let recording = Config {
+        engine: "fake".to_string(),
+        fixtures: FixturesConfig { record: Some(dir_name.clone()), replay: None },
+        ..Config::default()
+    };
+    assert_eq!(build_generator(&recording).unwrap().engine(), "fake");
+
+    let replaying = Config {
+        fixtures: FixturesConfig { record: None, replay: Some(dir_name.clone()) },
+        ..Config::default()
+    };
+    let generator = build_generator(&replaying).unwrap();
+    assert_eq!((generator.engine(), generator.model()), ("replay", dir_name.as_str()));
+}
@@ -0,0 +1,269 @@
+use autoflashcard::generator::{FakeGenerator, Role};
+use autoflashcard::generation::{Flashcard, GenerationOptions};
+use autoflashcard::note_type::NoteKind;
+use autoflashcard::review::{draw, CardSource, GeneratorSource, Mode, ReviewRequest, ReviewSession, Verdict};
+use ratatui::backend::TestBackend;
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::Terminal;
+
+fn card(front: &str) -> Flashcard {
+    Flashcard {
+        front: front.to_string(),
+        back: format!("{} (back)", front),
+        example: format!("Example with {}", front),
+        example_translate: format!("Translation of {}", front),
+        ..Default::default()
+    }
+}
+
+fn session() -> ReviewSession {
+    ReviewSession::new(vec![card("rojo"), card("azul"), card("verde")], NoteKind::Basic)
+}
+
+fn press(session: &mut ReviewSession, code: KeyCode) -> Option<ReviewRequest> {
+    session.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+}
+
+fn type_text(session: &mut ReviewSession, text: &str) {
+    for c in text.chars() {
+        press(session, KeyCode::Char(c));
+    }
+}
+
+#[test]
+fn test_cards_start_pending_and_nothing_is_approved() {
+    let session = session();
+
+    assert_eq!(session.count(Verdict::Pending), 3);
+    assert!(session.approved().is_empty());
+}
+
+#[test]
+fn test_accept_and_reject_move_to_the_next_card() {
+    let mut session = session();
+
+    press(&mut session, KeyCode::Char('a'));
+    press(&mut session, KeyCode::Char('r'));
+    press(&mut session, KeyCode::Char('a'));
+
+    let verdicts: Vec<Verdict> = session.cards.iter().map(|card| card.verdict).collect();
+    assert_eq!(verdicts, vec![Verdict::Accepted, Verdict::Rejected, Verdict::Accepted]);
+    // Stays on the last card
+    assert_eq!(session.selected, 2);
+
+    let fronts: Vec<String> = session.approved().into_iter().map(|card| card.front).collect();
+    assert_eq!(fronts, vec!["rojo", "verde"]);
+}
+
+#[test]
+fn test_accept_all_keeps_rejected_cards_out() {
+    let mut session = session();
+
+    press(&mut session, KeyCode::Down);
+    press(&mut session, KeyCode::Char('x'));
+    press(&mut session, KeyCode::Char('A'));
+
+    assert_eq!(session.count(Verdict::Accepted), 2);
+    assert_eq!(session.count(Verdict::Rejected), 1);
+    assert!(session.approved().iter().all(|card| card.front != "azul"));
+}
+
+#[test]
+fn test_selection_stays_in_bounds() {
+    let mut session = session();
+
+    press(&mut session, KeyCode::Up);
+    assert_eq!(session.selected, 0);
+    for _ in 0..5 {
+        press(&mut session, KeyCode::Char('j'));
+    }
+    assert_eq!(session.selected, 2);
+}
+
+#[test]
+fn test_edit_saves_fields_on_enter() {
+    let mut session = session();
+
+    press(&mut session, KeyCode::Char('e'));
+    assert_eq!(session.mode, Mode::Edit { field: 0, buffer: "rojo".to_string() });
+
+    // Replace the front, then move to the back and append to it
+    for _ in 0.."rojo".len() {
+        press(&mut session, KeyCode::Backspace);
+    }
+    type_text(&mut session, "el rojo");
+    press(&mut session, KeyCode::Tab);
+    type_text(&mut session, "!");
+    press(&mut session, KeyCode::Enter);
+
+    assert_eq!(session.mode, Mode::Browse);
+    assert_eq!(session.cards[0].card.front, "el rojo");
+    assert_eq!(session.cards[0].card.back, "rojo (back)!");
+}
+
+#[test]
+fn test_edit_is_discarded_on_escape() {
+    let mut session = session();
+
+    press(&mut session, KeyCode::Enter);
+    type_text(&mut session, "xyz");
+    press(&mut session, KeyCode::BackTab);
+    assert!(matches!(session.mode, Mode::Edit { field: 3, .. }));
+    type_text(&mut session, "abc");
+    press(&mut session, KeyCode::Esc);
+
+    // The field left with Shift-Tab was saved, the one left with Esc was not
+    assert_eq!(session.cards[0].card.front, "rojoxyz");
+    assert_eq!(session.cards[0].card.example_translate, "Translation of rojo");
+}
+
+#[test]
+fn test_cloze_sessions_edit_the_cloze_text() {
+    let cloze = Flashcard { cloze: Some("El {{c1::rojo}} coche.".to_string()), ..card("rojo") };
+    let mut session = ReviewSession::new(vec![cloze], NoteKind::Cloze);
+
+    // The cloze is the last field, one Shift-Tab back from the front
+    press(&mut session, KeyCode::Enter);
+    press(&mut session, KeyCode::BackTab);
+    assert_eq!(session.mode, Mode::Edit { field: 4, buffer: "El {{c1::rojo}} coche.".to_string() });
+    for _ in 0.."{{c1::rojo}} coche.".len() {
+        press(&mut session, KeyCode::Backspace);
+    }
+    type_text(&mut session, "{{c1::coche}} rojo.");
+    press(&mut session, KeyCode::Enter);
+    assert_eq!(session.cards[0].card.cloze.as_deref(), Some("El {{c1::coche}} rojo."));
+
+    // An emptied cloze is derived from the example again
+    press(&mut session, KeyCode::Enter);
+    press(&mut session, KeyCode::BackTab);
+    for _ in 0.."El {{c1::coche}} rojo.".len() {
+        press(&mut session, KeyCode::Backspace);
+    }
+    press(&mut session, KeyCode::Enter);
+    assert_eq!(session.cards[0].card.cloze, None);
+}
+
+#[test]
+fn test_type_answer_sessions_edit_the_answer() {
+    let mut session = ReviewSession::new(vec![card("el rojo")], NoteKind::TypeAnswer);
+
+    // Front, Back, then Answer, which starts empty when the model gave none
+    press(&mut session, KeyCode::Enter);
+    press(&mut session, KeyCode::Tab);
+    press(&mut session, KeyCode::Tab);
+    assert_eq!(session.mode, Mode::Edit { field: 2, buffer: String::new() });
+    type_text(&mut session, "rojo");
+    press(&mut session, KeyCode::Tab);
+    assert!(matches!(&session.mode, Mode::Edit { field: 3, buffer } if buffer == "Example with el rojo"));
+    press(&mut session, KeyCode::Esc);
+
+    assert_eq!(session.cards[0].card.answer.as_deref(), Some("rojo"));
+    assert_eq!(session.cards[0].card.example, "Example with el rojo");
+}
+
+#[test]
+fn test_letters_are_typed_while_editing() {
+    let mut session = session();
+
+    press(&mut session, KeyCode::Char('e'));
+    // Browse keys have no effect while a field is edited
+    assert_eq!(press(&mut session, KeyCode::Char('q')), None);
+    assert_eq!(press(&mut session, KeyCode::Char('s')), None);
+
+    assert_eq!(session.mode, Mode::Edit { field: 0, buffer: "rojoqs".to_string() });
+}
+
+#[test]
+fn test_keys_that_need_the_outside_return_requests() {
+    let mut session = session();
+
+    press(&mut session, KeyCode::Down);
+    assert_eq!(press(&mut session, KeyCode::Char('g')), Some(ReviewRequest::Regenerate(1)));
+    assert_eq!(press(&mut session, KeyCode::Char('m')), Some(ReviewRequest::MoreCards));
+    assert_eq!(press(&mut session, KeyCode::Char('s')), Some(ReviewRequest::Finish));
+    assert_eq!(press(&mut session, KeyCode::Char('q')), Some(ReviewRequest::Cancel));
+    assert_eq!(
+        session.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+        Some(ReviewRequest::Cancel)
+    );
+}
+
+#[test]
+fn test_regenerated_and_added_cards_are_reviewed_again() {
+    let mut session = session();
+    press(&mut session, KeyCode::Char('a'));
+
+    session.replace(0, card("negro"));
+    session.extend(vec![card("blanco")]);
+
+    assert_eq!(session.cards[0].card.front, "negro");
+    assert_eq!(session.cards[0].verdict, Verdict::Pending);
+    assert_eq!(session.fronts(), vec!["negro", "azul", "verde", "blanco"]);
+    assert!(session.approved().is_empty());
+}
+
+#[test]
+fn test_draw_shows_cards_and_counts() {
+    let mut session = session();
+    press(&mut session, KeyCode::Char('r'));
+
+    let mut terminal = Terminal::new(TestBackend::new(120, 20)).unwrap();
+    terminal.draw(|frame| draw(frame, &session)).unwrap();
+
+    let screen: String = terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect();
+    assert!(screen.contains("0 accepted, 1 rejected, 2 pending"));
+    assert!(screen.contains("azul (back)"));
+    assert!(screen.contains("Card 2 of 3"));
+}
+
+#[test]
+fn test_draw_handles_an_empty_session() {
+    let session = ReviewSession::new(Vec::new(), NoteKind::Basic);
+
+    let mut terminal = Terminal::new(TestBackend::new(80, 12)).unwrap();
+    terminal.draw(|frame| draw(frame, &session)).unwrap();
+}
+
+const REPLACEMENT: &str = r#"{"deck_name": "Colors", "cards": [
+    {"front": "azul", "back": "blue", "example": "Es azul.", "example_translate": "It is blue."},
+    {"front": "negro", "back": "black", "example": "Es negro.", "example_translate": "It is black."}
+]}"#;
+
+#[tokio::test]
+async fn test_replacement_asks_for_one_card_and_skips_existing_ones() {
+    let generator = FakeGenerator::with_replies(vec![REPLACEMENT]);
+    let source = GeneratorSource {
+        generator: &generator,
+        prompt: "Topic: Colors\n".to_string(),
+        count: Some(10),
+        options: GenerationOptions::default(),
+    };
+
+    let existing = vec!["rojo".to_string(), "azul".to_string()];
+    let new_card = source.replacement(&card("rojo"), &existing).await.unwrap();
+
+    assert_eq!(new_card.front, "negro");
+    let calls = generator.calls();
+    let user_message = calls[0].iter().rev().find(|message| message.role == Role::User).unwrap();
+    assert!(user_message.content.contains("Number of cards: 1"));
+    assert!(user_message.content.contains("Replace the rejected card: rojo"));
+}
+
+#[tokio::test]
+async fn test_more_uses_the_requested_count() {
+    let generator = FakeGenerator::with_replies(vec![REPLACEMENT]);
+    let source = GeneratorSource {
+        generator: &generator,
+        prompt: "Topic: Colors\n".to_string(),
+        count: Some(10),
+        options: GenerationOptions::default(),
+    };
+
+    let cards = source.more(&["negro".to_string()]).await.unwrap();
+
+    assert_eq!(cards.len(), 1);
+    assert_eq!(cards[0].front, "azul");
+    let calls = generator.calls();
+    let user_message = calls[0].iter().rev().find(|message| message.role == Role::User).unwrap();
+    assert!(user_message.content.contains("Number of cards: 10"));
+}
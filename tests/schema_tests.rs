@@ -0,0 +1,65 @@
+use autoflashcard::generation::parse_flashcard_response;
+use autoflashcard::schema::{flashcard_response_schema, validate};
+use serde_json::json;
+
+#[test]
+fn test_schema_is_self_contained_and_strict() {
+    let schema = flashcard_response_schema();
+
+    assert_eq!(schema["type"], "object");
+    assert_eq!(schema["additionalProperties"], false);
+    assert_eq!(schema["required"], json!(["cards", "deck_name"]));
+    assert!(schema.get("definitions").is_none());
+    assert!(!schema.to_string().contains("$ref"));
+
+    let card = &schema["properties"]["cards"]["items"];
+    assert_eq!(card["additionalProperties"], false);
+    assert_eq!(card["required"], json!(["back", "example", "example_translate", "front"]));
+}
+
+#[test]
+fn test_validate_accepts_well_formed_reply() {
+    let value = json!({
+        "deck_name": "Colors in Spanish",
+        "cards": [{ "front": "rojo", "back": "red", "example": "Es rojo.", "example_translate": "It is red." }]
+    });
+
+    assert!(validate(&value, &flashcard_response_schema()).is_ok());
+}
+
+#[test]
+fn test_validate_reports_every_violation_with_path() {
+    let value = json!({
+        "deck_name": 3,
+        "cards": [{ "front": "rojo", "back": "red", "example": "Es rojo.", "notes": "extra" }]
+    });
+
+    let errors = validate(&value, &flashcard_response_schema()).unwrap_err();
+
+    assert!(errors.contains(&"$.deck_name: expected string, found number".to_string()));
+    assert!(errors.contains(&"$.cards[0]: missing required field 'example_translate'".to_string()));
+    assert!(errors.contains(&"$.cards[0].notes: unexpected field".to_string()));
+    assert_eq!(errors.len(), 3);
+}
+
+#[test]
+fn test_parse_flashcard_response_rejects_schema_violations() {
+    let schema = flashcard_response_schema();
+    let text = r#"{"deck_name": "Colors", "cards": [{"front": "rojo", "back": "red"}]}"#;
+
+    let message = parse_flashcard_response(text, &schema).unwrap_err().to_string();
+
+    assert!(message.contains("does not match the flashcard schema"));
+    assert!(message.contains("missing required field 'example'"));
+}
+
+#[test]
+fn test_parse_flashcard_response_from_fenced_reply() {
+    let schema = flashcard_response_schema();
+    let text = "```json\n{\"deck_name\": \"Colors\", \"cards\": []}\n```";
+
+    let response = parse_flashcard_response(text, &schema).expect("valid reply");
+
+    assert_eq!(response.deck_name, "Colors");
+    assert!(response.cards.is_empty());
+}
@@ -0,0 +1,100 @@
+use autoflashcard::generation::Flashcard;
+use autoflashcard::note_type::NoteKind;
+use autoflashcard::tts::{add_audio, language_code, media_filename, silent_wav, CommandTts, SilentTts, TtsEngine};
+
+fn card() -> Flashcard {
+    Flashcard {
+        front: "家 (いえ) (ie)".to_string(),
+        back: "house".to_string(),
+        example: "私は家にいます。".to_string(),
+        example_translate: "I am at home.".to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_add_audio_speaks_word_and_example() {
+    let tts = SilentTts::new();
+    let mut card = card();
+
+    let files = add_audio(&tts, &mut card, "ja").await.expect("synthesis failed");
+
+    assert_eq!(tts.calls(), vec![
+        ("家".to_string(), "ja".to_string()),
+        ("私は家にいます。".to_string(), "ja".to_string()),
+    ]);
+    assert_eq!(files.len(), 2);
+    assert_eq!(card.front_audio.as_deref(), Some(files[0].filename.as_str()));
+    assert_eq!(card.example_audio.as_deref(), Some(files[1].filename.as_str()));
+
+    let fields = NoteKind::Basic.note_type().note("Japanese", &card).fields;
+    assert_eq!(fields["FrontAudio"], format!("[sound:{}]", files[0].filename));
+    assert_eq!(fields["ExampleAudio"], format!("[sound:{}]", files[1].filename));
+}
+
+#[tokio::test]
+async fn test_add_audio_skips_empty_example() {
+    let tts = SilentTts::new();
+    let mut card = Flashcard { example: "  ".to_string(), ..card() };
+
+    let files = add_audio(&tts, &mut card, "ja").await.unwrap();
+
+    assert_eq!(files.len(), 1);
+    assert!(card.example_audio.is_none());
+    assert_eq!(NoteKind::Basic.note_type().note("Japanese", &card).fields["ExampleAudio"], "");
+}
+
+#[test]
+fn test_media_filename_is_content_hashed() {
+    let audio = silent_wav(800);
+
+    assert_eq!(media_filename(&audio, "wav"), media_filename(&audio.clone(), ".wav"));
+    assert_ne!(media_filename(&audio, "wav"), media_filename(&silent_wav(1600), "wav"));
+    assert_eq!(media_filename(b"abc", "mp3"), "wordcraft-a9993e364706816aba3e25717850c26c9cd0d89d.mp3");
+}
+
+#[test]
+fn test_silent_wav_header() {
+    let wav = silent_wav(10);
+    assert_eq!(wav.len(), 54);
+    assert_eq!(&wav[0..4], b"RIFF");
+    assert_eq!(&wav[8..12], b"WAVE");
+    assert_eq!(u32::from_le_bytes(wav[40..44].try_into().unwrap()), 10);
+}
+
+#[test]
+fn test_language_code() {
+    assert_eq!(language_code("Japanese"), "ja");
+    assert_eq!(language_code(" spanish "), "es");
+    assert_eq!(language_code("pt-BR"), "pt-br");
+}
+
+#[tokio::test]
+async fn test_command_tts_reads_stdout_and_output_file() {
+    let stdout = CommandTts::new("cat", "wav").unwrap();
+    assert_eq!(stdout.synthesize("hola", "es").await.unwrap(), b"hola");
+
+    let file = CommandTts::new("tee {output}", "wav").unwrap();
+    assert_eq!(file.name(), "tee");
+    assert_eq!(file.synthesize("hola", "es").await.unwrap(), b"hola");
+
+    let args = CommandTts::new("echo -n {lang}:{text}", "wav").unwrap();
+    assert_eq!(args.synthesize("hola mundo", "es").await.unwrap(), b"es:hola mundo");
+
+    // Quoted arguments keep their spaces
+    let quoted = CommandTts::new("printf '%s|%s' 'My Voices/es.onnx' \"{text}\"", "wav").unwrap();
+    assert_eq!(quoted.synthesize("hola mundo", "es").await.unwrap(), b"My Voices/es.onnx|hola mundo");
+}
+
+#[tokio::test]
+async fn test_command_tts_reports_failures() {
+    let failing = CommandTts::new("false", "wav").unwrap();
+    assert!(failing.synthesize("hola", "es").await.unwrap_err().to_string().contains("false exited with"));
+
+    let missing = CommandTts::new("wordcraft-no-such-tts", "wav").unwrap();
+    assert!(missing.synthesize("hola", "es").await.unwrap_err().to_string().starts_with("Could not run wordcraft-no-such-tts"));
+
+    assert!(CommandTts::new("   ", "wav").is_err());
+    let unclosed = CommandTts::new("piper --model 'voice.onnx", "wav").err().unwrap();
+    assert!(unclosed.to_string().contains("unclosed quote"));
+}